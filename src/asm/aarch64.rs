@@ -0,0 +1,520 @@
+use std::io::Write;
+
+use crate::asm::backend::{Backend, BinaryOperation, Comparison, Function};
+use crate::asm::labels::{LabelIndex, Labels};
+use crate::asm::parameter_offset;
+use crate::asm::registers::{RegisterIndex, Registers};
+use crate::asm::strings::Strings;
+use crate::asm::target::Target;
+use crate::compiler::Program;
+use crate::symbols::{Storage, Symbol};
+use crate::types::Type;
+
+// The allocator hands out the temporaries x9 to x15. x0 to x8 carry the arguments and results
+// of calls, x16 and x17 are kept as scratch registers for addresses and large constants, x18 is
+// reserved by some platforms and x19 to x28 would have to be saved by main before it used them.
+const REGISTERS: [&str; 7] = [
+    "x9", "x10", "x11", "x12", "x13", "x14", "x15"
+];
+
+const WORD_REGISTERS: [&str; 7] = [
+    "w9", "w10", "w11", "w12", "w13", "w14", "w15"
+];
+
+fn x(r: RegisterIndex) -> &'static str {
+    REGISTERS[r.0 as usize]
+}
+
+fn w(r: RegisterIndex) -> &'static str {
+    WORD_REGISTERS[r.0 as usize]
+}
+
+/// The AArch64 backend, writing GNU assembler syntax for the AAPCS64 calling convention
+pub struct AArch64<W: Write> {
+    out: W,
+    target: Target,
+    registers: Registers,
+    labels: Labels,
+    strings: Strings,
+    bounds_checked: bool,
+    frame_size: u32,
+}
+
+impl<W: Write> AArch64<W> {
+    // Loads any 64 bit constant 16 bits at a time, skipping the pieces that are zero
+    fn load_constant(&mut self, register: &str, value: u64) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        writeln!(self.out, "\tmovz\t{}, #{}", register, value & 0xffff)?;
+        for shift in &[16, 32, 48] {
+            let piece = (value >> shift) & 0xffff;
+            if piece != 0 {
+                writeln!(self.out, "\tmovk\t{}, #{}, lsl #{}", register, piece, shift)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Compares the register against a constant, which only fits in the instruction when it is small
+    fn compare_constant(&mut self, r: RegisterIndex, value: u64) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        if value < 4096 {
+            writeln!(self.out, "\tcmp\t{}, #{}", x(r), value)?;
+        } else {
+            self.load_constant("x16", value)?;
+            writeln!(self.out, "\tcmp\t{}, x16", x(r))?;
+        }
+        Ok(())
+    }
+
+    // Globals are reached relative to the page they are on, so the code works wherever it is loaded
+    fn address(&mut self, register: &str, symbol: &str) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        writeln!(self.out, "\tadrp\t{}, {}", register, symbol)?;
+        writeln!(self.out, "\tadd\t{}, {}, :lo12:{}", register, register, symbol)?;
+        Ok(())
+    }
+
+    fn address_of_storage(&mut self, register: &str, symbol: &Symbol) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        match symbol.storage {
+            Storage::Global => self.address(register, &symbol.name),
+            Storage::Frame(offset) => self.subtract("x29", register, -offset as u64),
+        }
+    }
+
+    // Sets `destination` to `source` less the constant, which only fits in the instruction when it
+    // is small. `destination` holds the constant otherwise, so it can not be `sp`.
+    fn subtract(&mut self, source: &str, destination: &str, value: u64) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        if value < 4096 {
+            writeln!(self.out, "\tsub\t{}, {}, #{}", destination, source, value)?;
+        } else {
+            self.load_constant(destination, value)?;
+            writeln!(self.out, "\tsub\t{}, {}, {}", destination, source, destination)?;
+        }
+        Ok(())
+    }
+
+    fn load_from(&mut self, r: RegisterIndex, address: &str, ty: &Type) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        let (instruction, destination) = match (ty.size(), ty.is_signed()) {
+            (1, true) => ("ldrsb", x(r)),
+            // Writing a w register clears the upper 32 bits of the x register
+            (1, false) => ("ldrb", w(r)),
+            (4, true) => ("ldrsw", x(r)),
+            (4, false) => ("ldr", w(r)),
+            _ => ("ldr", x(r)),
+        };
+        writeln!(self.out, "\t{}\t{}, [{}]", instruction, destination, address)?;
+        Ok(())
+    }
+
+    fn store_to(&mut self, r: RegisterIndex, address: &str, ty: &Type) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        match ty.size() {
+            1 => writeln!(self.out, "\tstrb\t{}, [{}]", w(r), address)?,
+            4 => writeln!(self.out, "\tstr\t{}, [{}]", w(r), address)?,
+            _ => writeln!(self.out, "\tstr\t{}, [{}]", x(r), address)?,
+        }
+        Ok(())
+    }
+
+    // x9 to x15 belong to the caller, so the ones holding values are pushed around a call. Each
+    // takes a 16 byte slot to keep the stack pointer aligned.
+    fn save(&mut self) -> core::result::Result<Vec<RegisterIndex>, Box<dyn std::error::Error>> {
+        let saved = self.registers.allocated();
+        for r in &saved {
+            writeln!(self.out, "\tstr\t{}, [sp, #-16]!", x(*r))?;
+        }
+        Ok(saved)
+    }
+
+    fn restore(&mut self, saved: &[RegisterIndex]) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        for r in saved.iter().rev() {
+            writeln!(self.out, "\tldr\t{}, [sp], #16", x(*r))?;
+        }
+        Ok(())
+    }
+
+    fn call_symbol(&mut self, symbol: &str, r: RegisterIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        writeln!(self.out, "\tmov\tx0, {}", x(r))?;
+        self.registers.free_register(r);
+        let saved = self.save()?;
+        writeln!(self.out, "\tbl\t{}", symbol)?;
+        self.restore(&saved)
+    }
+
+    fn read_int(&mut self, r: RegisterIndex, ty: &Type) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        let saved = self.save()?;
+        writeln!(self.out, "\tbl\t{}", self.target.runtime("readint"))?;
+        self.restore(&saved)?;
+
+        let skip = self.labels.allocate_label();
+        writeln!(self.out, "\tcmp\tx0, #1")?;
+        writeln!(self.out, "\tb.ne\t{}", skip)?;
+        match ty.size() {
+            1 => writeln!(self.out, "\tstrb\tw1, [{}]", x(r))?,
+            4 => writeln!(self.out, "\tstr\tw1, [{}]", x(r))?,
+            _ => writeln!(self.out, "\tstr\tx1, [{}]", x(r))?,
+        }
+        self.label(skip)?;
+        self.registers.free_register(r);
+
+        let status = self.registers.allocate_register()?;
+        writeln!(self.out, "\tmov\t{}, x0", x(status))?;
+        Ok(status)
+    }
+
+    fn read_line(&mut self, buffer: RegisterIndex, size: RegisterIndex) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        writeln!(self.out, "\tmov\tx0, {}", x(buffer))?;
+        writeln!(self.out, "\tmov\tx1, {}", x(size))?;
+        self.registers.free_register(buffer);
+        self.registers.free_register(size);
+
+        let saved = self.save()?;
+        writeln!(self.out, "\tbl\t{}", self.target.runtime("readline"))?;
+        self.restore(&saved)?;
+
+        let length = self.registers.allocate_register()?;
+        writeln!(self.out, "\tmov\t{}, x0", x(length))?;
+        Ok(length)
+    }
+
+    // Reports an out of bounds index on the line passed in x0 and aborts
+    fn bounds_fail(&mut self) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        let message = self.strings.intern("Array index out of bounds on line %ld\n");
+        writeln!(self.out, "{}:", self.target.runtime("boundsfail"))?;
+        writeln!(self.out, "\tstp\tx29, x30, [sp, #-16]!")?;
+        writeln!(self.out, "\tmov\tx29, sp")?;
+        writeln!(self.out, "\tmov\tx1, x0")?;
+        self.address("x0", &message.name())?;
+        writeln!(self.out, "\tbl\t{}", self.target.function("printf"))?;
+        writeln!(self.out, "\tmov\tx0, #0")?;
+        writeln!(self.out, "\tbl\t{}", self.target.function("fflush"))?;
+        writeln!(self.out, "\tbl\t{}", self.target.function("abort"))?;
+        Ok(())
+    }
+}
+
+impl<W: Write> Backend<W> for AArch64<W> {
+    type Register = RegisterIndex;
+
+    fn new(target: Target, out: W) -> Self {
+        AArch64 {
+            out,
+            target,
+            registers: Registers::new().with_count(REGISTERS.len()),
+            labels: Labels::for_target(target),
+            strings: Strings::new(),
+            bounds_checked: false,
+            frame_size: 0,
+        }
+    }
+
+    fn survey(&mut self, program: &Program) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        self.frame_size = program.symbols.frame_size();
+        Ok(())
+    }
+
+    fn prologue(&mut self) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        let signed = self.strings.intern("%ld\n");
+        let unsigned = self.strings.intern("%lu\n");
+        let read = self.strings.intern("%ld");
+        let printf = self.target.function("printf");
+
+        writeln!(self.out, "\t// Start of preamble")?;
+        writeln!(self.out, "\t.text")?;
+        for (routine, format) in &[("printint", signed), ("printuint", unsigned)] {
+            writeln!(self.out, "{}:", self.target.runtime(routine))?;
+            writeln!(self.out, "\tstp\tx29, x30, [sp, #-16]!")?;
+            writeln!(self.out, "\tmov\tx29, sp")?;
+            writeln!(self.out, "\tmov\tx1, x0")?;
+            self.address("x0", &format.name())?;
+            writeln!(self.out, "\tbl\t{}", printf)?;
+            writeln!(self.out, "\tldp\tx29, x30, [sp], #16")?;
+            writeln!(self.out, "\tret")?;
+        }
+        writeln!(self.out, "{}:", self.target.runtime("printstr"))?;
+        writeln!(self.out, "\tstp\tx29, x30, [sp, #-16]!")?;
+        writeln!(self.out, "\tmov\tx29, sp")?;
+        writeln!(self.out, "\tbl\t{}", self.target.function("puts"))?;
+        writeln!(self.out, "\tldp\tx29, x30, [sp], #16")?;
+        writeln!(self.out, "\tret")?;
+        // readint gives the scanf status in x0 and the value it read in x1
+        writeln!(self.out, "{}:", self.target.runtime("readint"))?;
+        writeln!(self.out, "\tstp\tx29, x30, [sp, #-32]!")?;
+        writeln!(self.out, "\tmov\tx29, sp")?;
+        writeln!(self.out, "\tadd\tx1, sp, #16")?;
+        self.address("x0", &read.name())?;
+        writeln!(self.out, "\tbl\t{}", self.target.function("scanf"))?;
+        writeln!(self.out, "\tsxtw\tx0, w0")?;
+        writeln!(self.out, "\tldr\tx1, [sp, #16]")?;
+        writeln!(self.out, "\tldp\tx29, x30, [sp], #32")?;
+        writeln!(self.out, "\tret")?;
+        // readline takes the buffer in x0 and its size in x1, and gives the length in x0
+        writeln!(self.out, "{}:", self.target.runtime("readline"))?;
+        writeln!(self.out, "\tstp\tx29, x30, [sp, #-48]!")?;
+        writeln!(self.out, "\tmov\tx29, sp")?;
+        writeln!(self.out, "\tstp\tx19, x20, [sp, #16]")?;
+        writeln!(self.out, "\tstr\tx21, [sp, #32]")?;
+        writeln!(self.out, "\tmov\tx19, x0")?;
+        writeln!(self.out, "\tsub\tx20, x1, #1")?;
+        writeln!(self.out, "\tmov\tx21, #0")?;
+        writeln!(self.out, "{}:", self.target.runtime("readline_next"))?;
+        writeln!(self.out, "\tcmp\tx21, x20")?;
+        writeln!(self.out, "\tb.ge\t{}", self.target.runtime("readline_done"))?;
+        writeln!(self.out, "\tbl\t{}", self.target.function("getchar"))?;
+        writeln!(self.out, "\tcmn\tw0, #1")?;
+        writeln!(self.out, "\tb.eq\t{}", self.target.runtime("readline_end"))?;
+        writeln!(self.out, "\tcmp\tw0, #10")?;
+        writeln!(self.out, "\tb.eq\t{}", self.target.runtime("readline_done"))?;
+        writeln!(self.out, "\tstrb\tw0, [x19, x21]")?;
+        writeln!(self.out, "\tadd\tx21, x21, #1")?;
+        writeln!(self.out, "\tb\t{}", self.target.runtime("readline_next"))?;
+        writeln!(self.out, "{}:", self.target.runtime("readline_end"))?;
+        writeln!(self.out, "\tstrb\twzr, [x19, x21]")?;
+        writeln!(self.out, "\tmov\tx0, #-1")?;
+        writeln!(self.out, "\tcbz\tx21, {}", self.target.runtime("readline_return"))?;
+        writeln!(self.out, "{}:", self.target.runtime("readline_done"))?;
+        writeln!(self.out, "\tstrb\twzr, [x19, x21]")?;
+        writeln!(self.out, "\tmov\tx0, x21")?;
+        writeln!(self.out, "{}:", self.target.runtime("readline_return"))?;
+        writeln!(self.out, "\tldp\tx19, x20, [sp, #16]")?;
+        writeln!(self.out, "\tldr\tx21, [sp, #32]")?;
+        writeln!(self.out, "\tldp\tx29, x30, [sp], #48")?;
+        writeln!(self.out, "\tret")?;
+        writeln!(self.out, "\t.globl\t{}", self.target.symbol("main"))?;
+        writeln!(self.out, "{}:", self.target.symbol("main"))?;
+        writeln!(self.out, "\tstp\tx29, x30, [sp, #-16]!")?;
+        writeln!(self.out, "\tmov\tx29, sp")?;
+        // sub only takes a 12 bit constant, so a larger frame is sized through x16
+        if self.frame_size < 4096 {
+            writeln!(self.out, "\tsub\tsp, sp, #{}", self.frame_size)?;
+        } else {
+            self.load_constant("x16", self.frame_size as u64)?;
+            writeln!(self.out, "\tsub\tsp, sp, x16")?;
+        }
+        writeln!(self.out, "\tstr\tx0, [x29, #{}]", parameter_offset(0))?;
+        writeln!(self.out, "\tstr\tx1, [x29, #{}]", parameter_offset(1))?;
+        writeln!(self.out, "\t// End of preamble")?;
+        Ok(())
+    }
+
+    fn epilogue(&mut self) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        writeln!(self.out, "\tmov\tw0, #0")?;
+        writeln!(self.out, "\tmov\tsp, x29")?;
+        writeln!(self.out, "\tldp\tx29, x30, [sp], #16")?;
+        writeln!(self.out, "\tret")?;
+        if self.bounds_checked {
+            self.bounds_fail()?;
+        }
+        self.strings.write(self.target, self.out.by_ref())?;
+        writeln!(self.out, "\t.section\t.note.GNU-stack,\"\",@progbits")?;
+        Ok(())
+    }
+
+    fn comment(&mut self, comment: &str) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        writeln!(self.out, "\t// {}", comment)?;
+        Ok(())
+    }
+
+    fn load(&mut self, value: u64, _ty: &Type) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        let r = self.registers.allocate_register()?;
+        self.load_constant(x(r), value)?;
+        Ok(r)
+    }
+
+    fn load_string(&mut self, string: &str) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        let string = self.strings.intern(string);
+        let r = self.registers.allocate_register()?;
+        self.address(x(r), &string.name())?;
+        Ok(r)
+    }
+
+    fn binary(&mut self, operation: BinaryOperation, r1: RegisterIndex, r2: RegisterIndex, ty: &Type) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        let instruction = match operation {
+            BinaryOperation::Add => "add",
+            BinaryOperation::Subtract => "sub",
+            BinaryOperation::Multiply => "mul",
+            BinaryOperation::Divide => if ty.is_signed() { "sdiv" } else { "udiv" },
+            BinaryOperation::LeftShift => "lsl",
+            BinaryOperation::RightShift => if ty.is_signed() { "asr" } else { "lsr" },
+        };
+        writeln!(self.out, "\t{}\t{}, {}, {}", instruction, x(r1), x(r1), x(r2))?;
+        self.registers.free_register(r2);
+        Ok(r1)
+    }
+
+    // add and sub take a 12 bit unsigned constant, and multiplying by a power of two is a shift
+    fn binary_constant(&mut self, operation: BinaryOperation, r: RegisterIndex, value: u64, ty: &Type) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        match operation {
+            BinaryOperation::Add if value < 4096 => writeln!(self.out, "\tadd\t{}, {}, #{}", x(r), x(r), value)?,
+            BinaryOperation::Subtract if value < 4096 => writeln!(self.out, "\tsub\t{}, {}, #{}", x(r), x(r), value)?,
+            BinaryOperation::Multiply if value.is_power_of_two() => writeln!(self.out, "\tlsl\t{}, {}, #{}", x(r), x(r), value.trailing_zeros())?,
+            _ => {
+                let constant = self.load(value, ty)?;
+                return self.binary(operation, r, constant, ty);
+            }
+        }
+        Ok(r)
+    }
+
+    // Unsigned operands are ordered by the carry flag, with lo, ls, hi and hs
+    fn compare(&mut self, comparison: Comparison, r1: RegisterIndex, r2: RegisterIndex, ty: &Type) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        let signed = ty.is_signed();
+        let condition = match comparison {
+            Comparison::Equal => "eq",
+            Comparison::NotEqual => "ne",
+            Comparison::LessThan => if signed { "lt" } else { "lo" },
+            Comparison::GreaterThan => if signed { "gt" } else { "hi" },
+            Comparison::LessThanEqual => if signed { "le" } else { "ls" },
+            Comparison::GreaterThanEqual => if signed { "ge" } else { "hs" },
+        };
+        writeln!(self.out, "\tcmp\t{}, {}", x(r1), x(r2))?;
+        writeln!(self.out, "\tcset\t{}, {}", x(r2), condition)?;
+        self.registers.free_register(r1);
+        Ok(r2)
+    }
+
+    fn extend(&mut self, r: RegisterIndex, ty: &Type) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        match (ty.size(), ty.is_signed()) {
+            (1, true) => writeln!(self.out, "\tsxtb\t{}, {}", x(r), w(r))?,
+            (1, false) => writeln!(self.out, "\tand\t{}, {}, #0xff", x(r), x(r))?,
+            (4, true) => writeln!(self.out, "\tsxtw\t{}, {}", x(r), w(r))?,
+            (4, false) => writeln!(self.out, "\tmov\t{}, {}", w(r), w(r))?,
+            _ => {}
+        }
+        Ok(r)
+    }
+
+    fn declare_global(&mut self, symbol: &Symbol) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        writeln!(self.out, "\t.comm\t{},{},{}", symbol.name, symbol.ty.size(), self.target.common_alignment(symbol.ty.alignment()))?;
+        Ok(())
+    }
+
+    fn load_symbol(&mut self, symbol: &Symbol, ty: &Type) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        let r = self.registers.allocate_register()?;
+        self.address_of_storage("x16", symbol)?;
+        self.load_from(r, "x16", ty)?;
+        Ok(r)
+    }
+
+    fn store_symbol(&mut self, symbol: &Symbol, r: RegisterIndex, ty: &Type) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        self.address_of_storage("x16", symbol)?;
+        self.store_to(r, "x16", ty)?;
+        Ok(r)
+    }
+
+    fn address_of(&mut self, symbol: &Symbol, _ty: &Type) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        let r = self.registers.allocate_register()?;
+        self.address_of_storage(x(r), symbol)?;
+        Ok(r)
+    }
+
+    fn load_dereference(&mut self, r: RegisterIndex, ty: &Type) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        self.load_from(r, x(r), ty)?;
+        Ok(r)
+    }
+
+    fn store_dereference(&mut self, value: RegisterIndex, pointer: RegisterIndex, ty: &Type) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        self.store_to(value, x(pointer), ty)?;
+        self.registers.free_register(pointer);
+        Ok(value)
+    }
+
+    fn bounds_check(&mut self, r: RegisterIndex, length: u32, line: u32) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        self.bounds_checked = true;
+        let ok = self.labels.allocate_label();
+        self.compare_constant(r, length as u64)?;
+        writeln!(self.out, "\tb.lo\t{}", ok)?;
+        self.load_constant("x0", line as u64)?;
+        writeln!(self.out, "\tbl\t{}", self.target.runtime("boundsfail"))?;
+        self.label(ok)?;
+        Ok(r)
+    }
+
+    fn call(&mut self, function: Function, arguments: &[RegisterIndex]) -> core::result::Result<Option<RegisterIndex>, Box<dyn std::error::Error>> {
+        match (function, arguments) {
+            (Function::PrintInt, [r]) => self.call_symbol(&self.target.runtime("printint"), *r).map(|_| None),
+            (Function::PrintUnsignedInt, [r]) => self.call_symbol(&self.target.runtime("printuint"), *r).map(|_| None),
+            (Function::PrintString, [r]) => self.call_symbol(&self.target.runtime("printstr"), *r).map(|_| None),
+            (Function::ReadInt(ty), [r]) => self.read_int(*r, &ty).map(Some),
+            (Function::ReadLine, [buffer, size]) => self.read_line(*buffer, *size).map(Some),
+            (Function::Exit, [r]) => self.call_symbol(&self.target.function("exit"), *r).map(|_| None),
+            (function, arguments) => panic!("Unable to call {:?} with {} arguments", function, arguments.len()),
+        }
+    }
+
+    fn return_value(&mut self, r: RegisterIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        writeln!(self.out, "\tmov\tx0, {}", x(r))?;
+        writeln!(self.out, "\tmov\tsp, x29")?;
+        writeln!(self.out, "\tldp\tx29, x30, [sp], #16")?;
+        writeln!(self.out, "\tret")?;
+        self.registers.free_register(r);
+        Ok(())
+    }
+
+    fn allocate_label(&mut self) -> LabelIndex {
+        self.labels.allocate_label()
+    }
+
+    fn label(&mut self, label: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        writeln!(self.out, "{}:", label)?;
+        Ok(())
+    }
+
+    fn jump(&mut self, label: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        writeln!(self.out, "\tb\t{}", label)?;
+        Ok(())
+    }
+
+    fn jump_if_zero(&mut self, r: RegisterIndex, label: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        writeln!(self.out, "\tcbz\t{}, {}", x(r), label)?;
+        self.registers.free_register(r);
+        Ok(())
+    }
+
+    fn jump_if_equal(&mut self, r: RegisterIndex, value: u64, label: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        self.compare_constant(r, value)?;
+        writeln!(self.out, "\tb.eq\t{}", label)?;
+        Ok(())
+    }
+
+    // The table holds offsets from itself rather than addresses so that it needs no relocations
+    fn jump_table(&mut self, r: RegisterIndex, low: u64, targets: &[LabelIndex], default: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        let table = self.labels.allocate_label();
+        if low != 0 {
+            self.load_constant("x16", low)?;
+            writeln!(self.out, "\tsub\t{}, {}, x16", x(r), x(r))?;
+        }
+        self.compare_constant(r, targets.len() as u64)?;
+        writeln!(self.out, "\tb.hs\t{}", default)?;
+        self.address("x16", &table.name())?;
+        writeln!(self.out, "\tldrsw\t{}, [x16, {}, lsl #2]", x(r), x(r))?;
+        writeln!(self.out, "\tadd\tx16, x16, {}", x(r))?;
+        writeln!(self.out, "\tbr\tx16")?;
+        writeln!(self.out, "\t.section\t{}", self.target.read_only_section())?;
+        writeln!(self.out, "\t.p2align\t2")?;
+        writeln!(self.out, "{}:", table)?;
+        for target in targets {
+            writeln!(self.out, "\t.word\t{}-{}", target, table)?;
+        }
+        writeln!(self.out, "\t.text")?;
+        Ok(())
+    }
+
+    fn move_register(&mut self, from: RegisterIndex, to: RegisterIndex) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        writeln!(self.out, "\tmov\t{}, {}", x(to), x(from))?;
+        self.registers.free_register(from);
+        Ok(to)
+    }
+
+    fn select(&mut self, condition: RegisterIndex, when_true: RegisterIndex, when_false: RegisterIndex) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        writeln!(self.out, "\tcmp\t{}, #0", x(condition))?;
+        writeln!(self.out, "\tcsel\t{}, {}, {}, ne", x(when_true), x(when_true), x(when_false))?;
+        self.registers.free_register(condition);
+        self.registers.free_register(when_false);
+        Ok(when_true)
+    }
+
+    fn free_register(&mut self, r: RegisterIndex) {
+        self.registers.free_register(r);
+    }
+
+    fn free_all_registers(&mut self) {
+        self.registers.free_all();
+    }
+}
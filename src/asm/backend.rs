@@ -0,0 +1,239 @@
+use std::fmt;
+use std::io::Write;
+
+use crate::asm::labels::LabelIndex;
+use crate::asm::syntax::Syntax;
+use crate::asm::target::Target;
+use crate::compiler::{jump_table_range, Program};
+use crate::symbols::Symbol;
+use crate::types::Type;
+
+/// Arithmetic on two registers, leaving the result in one of them
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BinaryOperation {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    LeftShift,
+    RightShift,
+}
+
+/// Comparisons of two registers, giving 1 when they hold and 0 when they do not
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    LessThan,
+    GreaterThan,
+    LessThanEqual,
+    GreaterThanEqual,
+}
+
+/// The functions of the runtime a program can call. The assembly backends write the routines of
+/// the runtime into each program that needs them, on top of the C library.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Function {
+    /// Prints a signed integer and a newline
+    PrintInt,
+    /// Prints an unsigned integer and a newline
+    PrintUnsignedInt,
+    /// Prints the string its argument points to and a newline
+    PrintString,
+    /// Reads an integer into the value of the given type its argument points to, leaving it alone
+    /// when there was no integer to read. Gives the status from `scanf`.
+    ReadInt(Type),
+    /// Reads characters into the buffer its first argument points to until a newline, the end of
+    /// the input or the buffer, of the size given by its second argument, has room only for the
+    /// terminating zero. Gives the number of characters read, or -1 at the end of the input.
+    ReadLine,
+    /// Ends the program with its argument as the status through the C library, so buffered output
+    /// is written out first. It does not return, so nothing needs restoring after the call.
+    Exit,
+}
+
+/// Everything the compiler needs from a machine to turn a program into assembly for it. The
+/// compiler walks the tree and asks for values to be computed into registers, which the backend
+/// allocates, names and frees. Operations taking registers free the ones they no longer need and
+/// give back the register holding their result. The emitters of WebAssembly, LLVM IR and C are
+/// backends too, whose registers stand for a value on the stack, an SSA value or an expression.
+pub trait Backend<W: Write>: Sized {
+    type Register: Copy + fmt::Debug;
+
+    fn new(target: Target, out: W) -> Self;
+
+    /// Chooses how instructions are written, before anything has been
+    fn set_syntax(&mut self, syntax: Syntax) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        match syntax {
+            Syntax::Att => Ok(()),
+            unsupported => Err(format!("Unable to write {} syntax assembly, it is only written for x86-64", unsupported).into()),
+        }
+    }
+
+    /// Looks over the whole program before anything is written, for a backend that has to know
+    /// how a variable is used before it decides where to keep it
+    fn survey(&mut self, _program: &Program) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    /// Writes everything before the first statement of `main`. The assembly backends keep argc
+    /// and argv in the frame for a `main` that takes them, with its variables below.
+    fn prologue(&mut self) -> core::result::Result<(), Box<dyn std::error::Error>>;
+
+    /// Writes a relocatable object rather than assembly, before anything has been written
+    fn set_object_output(&mut self) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        Err("Unable to write an object file, they are only written for x86-64".into())
+    }
+
+    /// Writes the end of `main` and anything the program needs after it
+    fn epilogue(&mut self) -> core::result::Result<(), Box<dyn std::error::Error>>;
+
+    fn comment(&mut self, comment: &str) -> core::result::Result<(), Box<dyn std::error::Error>>;
+
+    /// Loads a constant of type `ty`
+    fn load(&mut self, value: u64, ty: &Type) -> core::result::Result<Self::Register, Box<dyn std::error::Error>>;
+
+    fn load_string(&mut self, string: &str) -> core::result::Result<Self::Register, Box<dyn std::error::Error>>;
+
+    /// Combines two values of type `ty`, which is only wide enough to hold the result once `extend` is applied
+    fn binary(&mut self, operation: BinaryOperation, r1: Self::Register, r2: Self::Register, ty: &Type) -> core::result::Result<Self::Register, Box<dyn std::error::Error>>;
+
+    /// Combines a value of type `ty` with a constant, as `binary` does. The constant is loaded like
+    /// any other value unless an instruction can take it as it is, which leaves a register free.
+    fn binary_constant(&mut self, operation: BinaryOperation, r: Self::Register, value: u64, ty: &Type) -> core::result::Result<Self::Register, Box<dyn std::error::Error>> {
+        let constant = self.load(value, ty)?;
+        self.binary(operation, r, constant, ty)
+    }
+
+    /// Compares two values of type `ty`
+    fn compare(&mut self, comparison: Comparison, r1: Self::Register, r2: Self::Register, ty: &Type) -> core::result::Result<Self::Register, Box<dyn std::error::Error>>;
+
+    /// Sign or zero extends the low bytes of a register holding a value of type `ty` to the whole register
+    fn extend(&mut self, r: Self::Register, ty: &Type) -> core::result::Result<Self::Register, Box<dyn std::error::Error>>;
+
+    /// Converts a value of type `from` to type `to`, which in a register only needs extending when
+    /// `to` can not hold every value of `from`
+    fn convert(&mut self, r: Self::Register, from: &Type, to: &Type) -> core::result::Result<Self::Register, Box<dyn std::error::Error>> {
+        if to.can_represent(from) {
+            return Ok(r);
+        }
+        self.extend(r, to)
+    }
+
+    fn declare_global(&mut self, symbol: &Symbol) -> core::result::Result<(), Box<dyn std::error::Error>>;
+
+    /// Declares a variable of `main` at its offset in the frame, which the assembly backends set
+    /// aside in the prologue
+    fn declare_local(&mut self, _symbol: &Symbol) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    /// Takes in a parameter of `main`, which the assembly backends find where the prologue saved it
+    fn declare_parameter(&mut self, _symbol: &Symbol) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    fn load_symbol(&mut self, symbol: &Symbol, ty: &Type) -> core::result::Result<Self::Register, Box<dyn std::error::Error>>;
+
+    fn store_symbol(&mut self, symbol: &Symbol, r: Self::Register, ty: &Type) -> core::result::Result<Self::Register, Box<dyn std::error::Error>>;
+
+    /// Gives the address of the variable as a pointer of type `ty`, which points at the first
+    /// element when the variable is an array used as a value
+    fn address_of(&mut self, symbol: &Symbol, ty: &Type) -> core::result::Result<Self::Register, Box<dyn std::error::Error>>;
+
+    /// Replaces the address in the register with the value of type `ty` it points at
+    fn load_dereference(&mut self, r: Self::Register, ty: &Type) -> core::result::Result<Self::Register, Box<dyn std::error::Error>>;
+
+    /// Stores the value of type `ty` in `value` at the address in `pointer`, giving back the value
+    fn store_dereference(&mut self, value: Self::Register, pointer: Self::Register, ty: &Type) -> core::result::Result<Self::Register, Box<dyn std::error::Error>>;
+
+    /// Aborts the program, reporting the line, when the index in the register is not less than
+    /// `length`. Negative indexes compare as large unsigned values, so one unsigned comparison
+    /// covers both ends. The routine reporting a failed check is only written when an index is checked.
+    fn bounds_check(&mut self, r: Self::Register, length: u32, line: u32) -> core::result::Result<Self::Register, Box<dyn std::error::Error>>;
+
+    /// Calls a function of the runtime with the arguments in registers, giving its result if it has one
+    fn call(&mut self, function: Function, arguments: &[Self::Register]) -> core::result::Result<Option<Self::Register>, Box<dyn std::error::Error>>;
+
+    /// Returns from `main` with the value in the register as the exit status
+    fn return_value(&mut self, r: Self::Register) -> core::result::Result<(), Box<dyn std::error::Error>>;
+
+    fn allocate_label(&mut self) -> LabelIndex;
+
+    fn label(&mut self, label: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>>;
+
+    fn jump(&mut self, label: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>>;
+
+    /// Jumps when the register holds zero, freeing it
+    fn jump_if_zero(&mut self, r: Self::Register, label: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>>;
+
+    /// Jumps when the register holds the constant, keeping it for further comparisons
+    fn jump_if_equal(&mut self, r: Self::Register, value: u64, label: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>>;
+
+    /// Jumps to the label at index `r - low` of `targets`, or to `default` when there is none
+    fn jump_table(&mut self, r: Self::Register, low: u64, targets: &[LabelIndex], default: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>>;
+
+    /// Jumps to the label of the case matching the value of type `ty` in the register, or to
+    /// `default` when none does, freeing the register. `cases` pairs each case value with its
+    /// label, `targets` holds every label in the order the statements they label come in and
+    /// `end` is where the switch finishes. A jump table is used when the cases are dense enough,
+    /// otherwise a compare against each case in turn.
+    fn switch(&mut self, r: Self::Register, ty: &Type, cases: &[(u64, LabelIndex)], _targets: &[LabelIndex], default: LabelIndex, _end: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        match jump_table_range(cases, ty.is_signed()) {
+            Some((low, length)) => {
+                let mut table = vec![default; length as usize];
+                for (value, label) in cases {
+                    table[value.wrapping_sub(low) as usize] = *label;
+                }
+                self.jump_table(r, low, &table, default)?;
+            }
+            None => {
+                for (value, label) in cases {
+                    self.jump_if_equal(r, *value, *label)?;
+                }
+                self.jump(default)?;
+            }
+        }
+        self.free_register(r);
+        Ok(())
+    }
+
+    /// Gives the value `when_true` computes unless the condition is zero, in which case it gives
+    /// the value `when_false` computes. Only the chosen arm is run.
+    fn conditional<T, F>(&mut self, condition: Self::Register, when_true: T, when_false: F) -> core::result::Result<Self::Register, Box<dyn std::error::Error>>
+        where T: FnOnce(&mut Self) -> core::result::Result<Self::Register, Box<dyn std::error::Error>>,
+              F: FnOnce(&mut Self) -> core::result::Result<Self::Register, Box<dyn std::error::Error>> {
+        let false_label = self.allocate_label();
+        let end_label = self.allocate_label();
+        self.jump_if_zero(condition, false_label)?;
+
+        // The true arm's register stays allocated while the false arm is
+        // generated so that both arms can leave their result in it
+        let result = when_true(self)?;
+        self.jump(end_label)?;
+
+        self.label(false_label)?;
+        let when_false = when_false(self)?;
+        self.move_register(when_false, result)?;
+
+        self.label(end_label)?;
+        Ok(result)
+    }
+
+    /// Copies the value in `from` into `to`, freeing `from`
+    fn move_register(&mut self, from: Self::Register, to: Self::Register) -> core::result::Result<Self::Register, Box<dyn std::error::Error>>;
+
+    /// Gives `when_true` unless the condition is zero, in which case it gives `when_false`
+    fn select(&mut self, condition: Self::Register, when_true: Self::Register, when_false: Self::Register) -> core::result::Result<Self::Register, Box<dyn std::error::Error>>;
+
+    fn free_register(&mut self, r: Self::Register);
+
+    /// Throws away the value of a statement run for what it does rather than what it gives
+    fn discard(&mut self, r: Self::Register) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        self.free_register(r);
+        Ok(())
+    }
+
+    /// Frees every register, between statements where no value is left live
+    fn free_all_registers(&mut self);
+}
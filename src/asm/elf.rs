@@ -0,0 +1,417 @@
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+
+use crate::asm::encoder::{encode, Fixup};
+use crate::asm::instruction::{Instruction, Line, Operand};
+use crate::asm::strings::Strings;
+
+/// The sections holding the program, numbered as in the section header table
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Section {
+    Text = 1,
+    Data = 3,
+    Bss = 4,
+    ReadOnly = 5,
+}
+
+impl Section {
+    // Where the symbol standing for the start of the section is in the symbol table
+    fn symbol(&self) -> u32 {
+        match self {
+            Section::Text => 1,
+            Section::Data => 2,
+            Section::Bss => 3,
+            Section::ReadOnly => 4,
+        }
+    }
+}
+
+// .rela.text, .rela.rodata and .note.GNU-stack come between the sections of the program
+const SYMBOL_TABLE: u16 = 8;
+const STRING_TABLE: u16 = 9;
+const SECTION_NAMES: u16 = 10;
+
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+const SHT_RELA: u32 = 4;
+const SHT_NOBITS: u32 = 8;
+
+const SHF_WRITE: u64 = 0x1;
+const SHF_ALLOC: u64 = 0x2;
+const SHF_EXECINSTR: u64 = 0x4;
+const SHF_INFO_LINK: u64 = 0x40;
+
+const STB_LOCAL: u8 = 0;
+const STB_GLOBAL: u8 = 1;
+const STT_NOTYPE: u8 = 0;
+const STT_OBJECT: u8 = 1;
+const STT_SECTION: u8 = 3;
+
+const R_X86_64_PC32: u32 = 2;
+const R_X86_64_PLT32: u32 = 4;
+
+// What goes into the text and read only sections
+#[derive(Clone, Debug)]
+enum Item {
+    Label(String),
+    Instruction(Instruction),
+    /// Pads to a multiple of the alignment
+    Align(u64),
+    /// The distance from the second label to the first, in four bytes
+    Difference(String, String),
+    Bytes(Vec<u8>),
+}
+
+// What a relocation is against, the start of a section or a symbol the object does not define
+#[derive(Clone, Debug, PartialEq)]
+enum Against {
+    Section(Section),
+    Symbol(String),
+}
+
+#[derive(Clone, Debug)]
+struct Relocation {
+    offset: u64,
+    against: Against,
+    kind: u32,
+    addend: i64,
+}
+
+// A section once every item is placed
+struct Placed {
+    /// Where each item starts
+    offsets: Vec<u64>,
+    size: u64,
+}
+
+/// Assembles the lines of an x86-64 program, with the strings it uses, into a relocatable ELF64
+/// object and writes it out
+pub fn write<W: Write>(lines: &[Line], strings: &Strings, mut out: W) -> core::result::Result<(), Box<dyn std::error::Error>> {
+    let mut text = Vec::new();
+    let mut read_only = Vec::new();
+    let mut globals = HashSet::new();
+    // Globals are given space in .bss, kept local to the object where `.comm` would have the
+    // linker merge them with any other of the same name
+    let mut bss = Vec::new();
+    let mut bss_size = 0;
+    let mut bss_alignment = 1;
+
+    let mut section = Section::Text;
+    for line in lines {
+        let items = if section == Section::Text { &mut text } else { &mut read_only };
+        match line {
+            Line::Instruction(instruction) | Line::Commented(instruction, _) | Line::Compact(instruction) =>
+                items.push(Item::Instruction(instruction.clone())),
+            Line::Label(label) => items.push(Item::Label(label.clone())),
+            Line::Comment(_) | Line::Blank => {}
+            Line::Directive(directive) => {
+                let (name, operands) = directive.split_once('\t').unwrap_or((directive, ""));
+                match (name, operands) {
+                    (".text", _) => section = Section::Text,
+                    (".section", ".rodata") => section = Section::ReadOnly,
+                    // The object always says its stack need not be executable
+                    (".section", operands) if operands.starts_with(".note.GNU-stack") => {}
+                    // The instructions are the same whichever syntax they would have been printed in
+                    (".intel_syntax noprefix", _) => {}
+                    (".globl", symbol) => {
+                        globals.insert(symbol.to_string());
+                    }
+                    (".p2align", power) => items.push(Item::Align(1 << power.parse::<u32>()?)),
+                    (".long", difference) => match difference.split_once('-') {
+                        Some((to, from)) => items.push(Item::Difference(to.to_string(), from.to_string())),
+                        None => return Err(format!("Unable to assemble [{}], expected the difference of two labels", directive).into()),
+                    },
+                    (".comm", operands) => match operands.split(',').collect::<Vec<_>>()[..] {
+                        [symbol, size, alignment] => {
+                            let (size, alignment) = (size.parse::<u64>()?, alignment.parse::<u64>()?);
+                            bss_size = align(bss_size, alignment);
+                            bss.push((symbol.to_string(), bss_size, size));
+                            bss_size += size;
+                            bss_alignment = bss_alignment.max(alignment);
+                        }
+                        _ => return Err(format!("Unable to assemble [{}], expected a symbol, size and alignment", directive).into()),
+                    },
+                    _ => return Err(format!("Unable to assemble the directive [{}]", directive).into()),
+                }
+            }
+        }
+    }
+    for (index, string) in strings.iter() {
+        read_only.push(Item::Label(index.name()));
+        read_only.push(Item::Bytes(string.bytes().chain(Some(0)).collect()));
+    }
+
+    // Every jump to a label in .text starts out short, and any found not to reach is made long
+    // until they all do, as making one longer can push another out of reach
+    let text_labels: HashSet<String> = text.iter().filter_map(|item| match item {
+        Item::Label(label) => Some(label.clone()),
+        _ => None,
+    }).collect();
+    let mut short: Vec<bool> = text.iter().map(|item| match item {
+        Item::Instruction(Instruction::Jump(Operand::Symbol(label))) | Item::Instruction(Instruction::JumpIf(_, Operand::Symbol(label))) =>
+            text_labels.contains(label),
+        _ => false,
+    }).collect();
+    let placed_text = loop {
+        let placed = place(&text, &short)?;
+        let labels = labels(&text, &placed, Section::Text);
+        let mut lengthened = false;
+        for (index, item) in text.iter().enumerate() {
+            if let (Item::Instruction(instruction), true) = (item, short[index]) {
+                let fixup = encode(instruction, true)?.fixup.ok_or("Unable to find the label a jump refers to")?;
+                let (_, target) = labels[&fixup.symbol];
+                let distance = target as i64 + fixup.addend - (placed.offsets[index] + fixup.offset as u64) as i64;
+                if distance < i8::MIN as i64 || distance > i8::MAX as i64 {
+                    short[index] = false;
+                    lengthened = true;
+                }
+            }
+        }
+        if !lengthened {
+            break placed;
+        }
+    };
+    let placed_read_only = place(&read_only, &[])?;
+
+    let mut defined = labels(&text, &placed_text, Section::Text);
+    defined.extend(labels(&read_only, &placed_read_only, Section::ReadOnly));
+    for (symbol, offset, _) in &bss {
+        defined.insert(symbol.clone(), (Section::Bss, *offset));
+    }
+
+    let (text_bytes, text_relocations) = fill(&text, &placed_text, &short, Section::Text, &defined)?;
+    let (read_only_bytes, read_only_relocations) = fill(&read_only, &placed_read_only, &[], Section::ReadOnly, &defined)?;
+
+    // Local symbols come first: the sections, then the labels and globals of the program, leaving
+    // out the labels the assembler would keep to itself
+    let mut names = vec![0];
+    let mut symbols = vec![[0u8; 24]];
+    for section in &[Section::Text, Section::Data, Section::Bss, Section::ReadOnly] {
+        symbols.push(symbol(0, STB_LOCAL, STT_SECTION, *section as u16, 0, 0));
+    }
+    let mut named = Vec::new();
+    for (items, section) in &[(&text, Section::Text), (&read_only, Section::ReadOnly)] {
+        for item in items.iter() {
+            if let Item::Label(label) = item {
+                if !label.starts_with(".L") {
+                    named.push((label.clone(), *section, defined[label].1, 0));
+                }
+            }
+        }
+    }
+    for (symbol, offset, size) in &bss {
+        named.push((symbol.clone(), Section::Bss, *offset, *size));
+    }
+    let (global, local): (Vec<_>, Vec<_>) = named.into_iter().partition(|(name, _, _, _)| globals.contains(name));
+    for (name, section, offset, size) in &local {
+        let kind = if *section == Section::Bss { STT_OBJECT } else { STT_NOTYPE };
+        symbols.push(symbol(string(&mut names, name), STB_LOCAL, kind, *section as u16, *offset, *size));
+    }
+    let first_global = symbols.len() as u32;
+    let mut indexes = HashMap::new();
+    for (name, section, offset, size) in &global {
+        indexes.insert(name.clone(), symbols.len() as u32);
+        symbols.push(symbol(string(&mut names, name), STB_GLOBAL, STT_NOTYPE, *section as u16, *offset, *size));
+    }
+    for relocation in text_relocations.iter().chain(read_only_relocations.iter()) {
+        if let Against::Symbol(name) = &relocation.against {
+            if !indexes.contains_key(name) {
+                indexes.insert(name.clone(), symbols.len() as u32);
+                symbols.push(symbol(string(&mut names, name), STB_GLOBAL, STT_NOTYPE, 0, 0, 0));
+            }
+        }
+    }
+    let relocations = |relocations: &[Relocation]| -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for relocation in relocations {
+            let index = match &relocation.against {
+                Against::Section(section) => section.symbol(),
+                Against::Symbol(name) => indexes[name],
+            };
+            bytes.extend_from_slice(&relocation.offset.to_le_bytes());
+            bytes.extend_from_slice(&((index as u64) << 32 | relocation.kind as u64).to_le_bytes());
+            bytes.extend_from_slice(&relocation.addend.to_le_bytes());
+        }
+        bytes
+    };
+    let rela_text = relocations(&text_relocations);
+    let rela_read_only = relocations(&read_only_relocations);
+    let read_only_alignment = read_only.iter().filter_map(|item| match item {
+        Item::Align(alignment) => Some(*alignment),
+        _ => None,
+    }).max().unwrap_or(1);
+
+    let mut section_names = vec![0];
+    let mut headers = vec![[0u8; 64]];
+    let mut file = vec![0u8; 64];
+    let mut add = |name: &str, kind: u32, flags: u64, contents: &[u8], size: u64, link: u32, info: u32, alignment: u64, entry_size: u64| {
+        let name = string(&mut section_names, name);
+        let padding = align(file.len() as u64, alignment) as usize - file.len();
+        file.extend(std::iter::repeat_n(0, padding));
+        headers.push(section_header(name, kind, flags, file.len() as u64, size, link, info, alignment, entry_size));
+        file.extend_from_slice(contents);
+    };
+    add(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &text_bytes, text_bytes.len() as u64, 0, 0, 1, 0);
+    add(".rela.text", SHT_RELA, SHF_INFO_LINK, &rela_text, rela_text.len() as u64, SYMBOL_TABLE as u32, Section::Text as u32, 8, 24);
+    add(".data", SHT_PROGBITS, SHF_ALLOC | SHF_WRITE, &[], 0, 0, 0, 1, 0);
+    add(".bss", SHT_NOBITS, SHF_ALLOC | SHF_WRITE, &[], bss_size, 0, 0, bss_alignment, 0);
+    add(".rodata", SHT_PROGBITS, SHF_ALLOC, &read_only_bytes, read_only_bytes.len() as u64, 0, 0, read_only_alignment, 0);
+    add(".rela.rodata", SHT_RELA, SHF_INFO_LINK, &rela_read_only, rela_read_only.len() as u64, SYMBOL_TABLE as u32, Section::ReadOnly as u32, 8, 24);
+    add(".note.GNU-stack", SHT_PROGBITS, 0, &[], 0, 0, 0, 1, 0);
+    let symbol_table = symbols.concat();
+    add(".symtab", SHT_SYMTAB, 0, &symbol_table, symbol_table.len() as u64, STRING_TABLE as u32, first_global, 8, 24);
+    add(".strtab", SHT_STRTAB, 0, &names, names.len() as u64, 0, 0, 1, 0);
+    let shstrtab = string(&mut section_names, ".shstrtab");
+    let section_names_offset = file.len() as u64;
+    file.extend_from_slice(&section_names);
+    headers.push(section_header(shstrtab, SHT_STRTAB, 0, section_names_offset, section_names.len() as u64, 0, 0, 1, 0));
+
+    let section_headers = align(file.len() as u64, 8);
+    file.resize(section_headers as usize, 0);
+    for header in &headers {
+        file.extend_from_slice(header);
+    }
+
+    // The ELF header: a 64 bit little endian relocatable object for x86-64
+    let mut header = Vec::with_capacity(64);
+    header.extend_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    header.extend_from_slice(&1u16.to_le_bytes());
+    header.extend_from_slice(&62u16.to_le_bytes());
+    header.extend_from_slice(&1u32.to_le_bytes());
+    header.extend_from_slice(&0u64.to_le_bytes());
+    header.extend_from_slice(&0u64.to_le_bytes());
+    header.extend_from_slice(&section_headers.to_le_bytes());
+    header.extend_from_slice(&0u32.to_le_bytes());
+    header.extend_from_slice(&64u16.to_le_bytes());
+    header.extend_from_slice(&0u16.to_le_bytes());
+    header.extend_from_slice(&0u16.to_le_bytes());
+    header.extend_from_slice(&64u16.to_le_bytes());
+    header.extend_from_slice(&(headers.len() as u16).to_le_bytes());
+    header.extend_from_slice(&SECTION_NAMES.to_le_bytes());
+    file[..64].copy_from_slice(&header);
+
+    out.write_all(&file)?;
+    Ok(())
+}
+
+fn align(offset: u64, alignment: u64) -> u64 {
+    offset.div_ceil(alignment) * alignment
+}
+
+// Adds the name to a string table, giving where it starts
+fn string(table: &mut Vec<u8>, name: &str) -> u32 {
+    let start = table.len() as u32;
+    table.extend_from_slice(name.as_bytes());
+    table.push(0);
+    start
+}
+
+fn symbol(name: u32, binding: u8, kind: u8, section: u16, value: u64, size: u64) -> [u8; 24] {
+    let mut symbol = [0u8; 24];
+    symbol[0..4].copy_from_slice(&name.to_le_bytes());
+    symbol[4] = binding << 4 | kind;
+    symbol[6..8].copy_from_slice(&section.to_le_bytes());
+    symbol[8..16].copy_from_slice(&value.to_le_bytes());
+    symbol[16..24].copy_from_slice(&size.to_le_bytes());
+    symbol
+}
+
+#[allow(clippy::too_many_arguments)]
+fn section_header(name: u32, kind: u32, flags: u64, offset: u64, size: u64, link: u32, info: u32, alignment: u64, entry_size: u64) -> [u8; 64] {
+    let mut header = [0u8; 64];
+    header[0..4].copy_from_slice(&name.to_le_bytes());
+    header[4..8].copy_from_slice(&kind.to_le_bytes());
+    header[8..16].copy_from_slice(&flags.to_le_bytes());
+    header[24..32].copy_from_slice(&offset.to_le_bytes());
+    header[32..40].copy_from_slice(&size.to_le_bytes());
+    header[40..44].copy_from_slice(&link.to_le_bytes());
+    header[44..48].copy_from_slice(&info.to_le_bytes());
+    header[48..56].copy_from_slice(&alignment.to_le_bytes());
+    header[56..64].copy_from_slice(&entry_size.to_le_bytes());
+    header
+}
+
+// Works out where each item goes, with the jumps marked short taking their short form
+fn place(items: &[Item], short: &[bool]) -> core::result::Result<Placed, Box<dyn std::error::Error>> {
+    let mut offsets = Vec::with_capacity(items.len());
+    let mut offset = 0;
+    for (index, item) in items.iter().enumerate() {
+        offset = match item {
+            Item::Align(alignment) => align(offset, *alignment),
+            _ => offset,
+        };
+        offsets.push(offset);
+        offset += match item {
+            Item::Label(_) | Item::Align(_) => 0,
+            Item::Instruction(instruction) => encode(instruction, short.get(index).copied().unwrap_or(false))?.bytes.len() as u64,
+            Item::Difference(_, _) => 4,
+            Item::Bytes(bytes) => bytes.len() as u64,
+        };
+    }
+    Ok(Placed { offsets, size: offset })
+}
+
+fn labels(items: &[Item], placed: &Placed, section: Section) -> HashMap<String, (Section, u64)> {
+    let mut labels = HashMap::new();
+    for (item, offset) in items.iter().zip(&placed.offsets) {
+        if let Item::Label(label) = item {
+            labels.insert(label.clone(), (section, *offset));
+        }
+    }
+    labels
+}
+
+// The bytes of a section, with every reference to a label of the same section filled in and
+// relocations left for the linker to fill in the rest
+fn fill(items: &[Item], placed: &Placed, short: &[bool], section: Section, defined: &HashMap<String, (Section, u64)>) -> core::result::Result<(Vec<u8>, Vec<Relocation>), Box<dyn std::error::Error>> {
+    let mut bytes = Vec::with_capacity(placed.size as usize);
+    let mut relocations = Vec::new();
+    for (index, item) in items.iter().enumerate() {
+        bytes.resize(placed.offsets[index] as usize, 0);
+        match item {
+            Item::Label(_) | Item::Align(_) => {}
+            Item::Bytes(data) => bytes.extend_from_slice(data),
+            Item::Instruction(instruction) => {
+                let encoding = encode(instruction, short.get(index).copied().unwrap_or(false))?;
+                let start = bytes.len();
+                bytes.extend_from_slice(&encoding.bytes);
+                if let Some(Fixup { offset, width, symbol, plt, addend }) = encoding.fixup {
+                    let position = (start + offset) as u64;
+                    match defined.get(&symbol) {
+                        Some((target, value)) if *target == section => {
+                            let distance = *value as i64 + addend - position as i64;
+                            let field = &mut bytes[start + offset..start + offset + width];
+                            match width {
+                                1 if distance >= i8::MIN as i64 && distance <= i8::MAX as i64 => field.copy_from_slice(&(distance as i8).to_le_bytes()),
+                                4 if distance >= i32::MIN as i64 && distance <= i32::MAX as i64 => field.copy_from_slice(&(distance as i32).to_le_bytes()),
+                                _ => return Err(format!("Unable to reach [{}] from {:#x}", symbol, position).into()),
+                            }
+                        }
+                        Some((target, value)) =>
+                            relocations.push(Relocation { offset: position, against: Against::Section(*target), kind: R_X86_64_PC32, addend: *value as i64 + addend }),
+                        None => {
+                            let kind = if plt { R_X86_64_PLT32 } else { R_X86_64_PC32 };
+                            relocations.push(Relocation { offset: position, against: Against::Symbol(symbol), kind, addend });
+                        }
+                    }
+                }
+            }
+            Item::Difference(to, from) => {
+                let position = bytes.len() as u64;
+                match (defined.get(to), defined.get(from)) {
+                    (Some((to_section, to)), Some((from_section, from))) if *to_section == section && *from_section == section =>
+                        bytes.extend_from_slice(&((*to as i64 - *from as i64) as i32).to_le_bytes()),
+                    // The address of the entry less the linker's is the distance of the entry from `from`
+                    (Some((to_section, to)), Some((from_section, from))) if *from_section == section => {
+                        relocations.push(Relocation { offset: position, against: Against::Section(*to_section), kind: R_X86_64_PC32, addend: (*to + position - from) as i64 });
+                        bytes.extend_from_slice(&[0; 4]);
+                    }
+                    _ => return Err(format!("Unable to assemble the difference of [{}] and [{}]", to, from).into()),
+                }
+            }
+        }
+    }
+    bytes.resize(placed.size as usize, 0);
+    Ok((bytes, relocations))
+}
@@ -0,0 +1,401 @@
+use crate::asm::instruction::{Address, Arithmetic, Condition, Instruction, Operand, Register, Shift, Size};
+
+/// A reference from an instruction to a symbol, filled in once the address of the symbol is known.
+/// Every reference is relative to the end of the instruction, as the processor reads it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fixup {
+    /// Where the reference starts within the instruction
+    pub offset: usize,
+    /// How many bytes the reference takes, 1 for a short jump and 4 otherwise
+    pub width: usize,
+    pub symbol: String,
+    /// Whether the symbol is a function of a shared library, called through the procedure linkage table
+    pub plt: bool,
+    /// Added to the address of the symbol less the address of the reference, which is the distance
+    /// back from the end of the instruction to the reference
+    pub addend: i64,
+}
+
+/// The machine code of an instruction
+#[derive(Clone, Debug, PartialEq)]
+pub struct Encoding {
+    pub bytes: Vec<u8>,
+    pub fixup: Option<Fixup>,
+}
+
+/// Encodes the instruction as x86-64 machine code. A jump to a label is encoded with a one byte
+/// displacement, reaching 128 bytes either way, when `short_jump` is set and four bytes when not,
+/// which is how the assembler picks between them too.
+pub fn encode(instruction: &Instruction, short_jump: bool) -> core::result::Result<Encoding, Box<dyn std::error::Error>> {
+    let mut encoder = Encoder { bytes: Vec::new(), fixup: None };
+    match instruction {
+        Instruction::Move(size, source, destination) => encoder.move_value(*size, source, destination)?,
+        Instruction::LoadConstant(value, destination) =>
+            encoder.move_immediate(Size::QuadWord, *value as i64, &Operand::register(*destination))?,
+        Instruction::SignExtend(Size::Byte, to, source, Operand::Register(destination, _)) =>
+            encoder.modrm(*to == Size::QuadWord, forces_rex(&[source]), &[0x0f, 0xbe], *destination as u8, source, &[])?,
+        Instruction::SignExtend(Size::DoubleWord, Size::QuadWord, source, Operand::Register(destination, _)) =>
+            encoder.modrm(true, false, &[0x63], *destination as u8, source, &[])?,
+        Instruction::ZeroExtend(Size::Byte, to, source, Operand::Register(destination, _)) =>
+            encoder.modrm(*to == Size::QuadWord, forces_rex(&[source]), &[0x0f, 0xb6], *destination as u8, source, &[])?,
+        Instruction::LoadAddress(address, destination) =>
+            encoder.modrm(true, false, &[0x8d], *destination as u8, &Operand::Memory(address.clone()), &[])?,
+        Instruction::Arithmetic(operation, size, source, destination) => encoder.arithmetic(*operation, *size, source, destination)?,
+        Instruction::Shift(shift, size, destination) => {
+            let extension = match shift {
+                Shift::Left => 4,
+                Shift::RightLogical => 5,
+                Shift::RightArithmetic => 7,
+            };
+            encoder.unary(*size, &[0xd2, 0xd3], extension, destination)?
+        }
+        Instruction::Increment(size, destination) => encoder.unary(*size, &[0xfe, 0xff], 0, destination)?,
+        Instruction::Divide(size, divisor) => encoder.unary(*size, &[0xf6, 0xf7], 6, divisor)?,
+        Instruction::SignedDivide(size, divisor) => encoder.unary(*size, &[0xf6, 0xf7], 7, divisor)?,
+        Instruction::SignExtendAccumulator => encoder.bytes.extend_from_slice(&[0x48, 0x99]),
+        Instruction::Set(condition, destination) =>
+            encoder.modrm(false, forces_rex(&[destination]), &[0x0f, 0x90 + condition_code(*condition)], 0, destination, &[])?,
+        Instruction::ConditionalMove(condition, size, source, Operand::Register(destination, _)) if *size != Size::Byte =>
+            encoder.modrm(*size == Size::QuadWord, false, &[0x0f, 0x40 + condition_code(*condition)], *destination as u8, source, &[])?,
+        Instruction::Jump(Operand::Symbol(symbol)) if short_jump => encoder.relative(&[0xeb], symbol, 1),
+        Instruction::Jump(Operand::Symbol(symbol)) => encoder.relative(&[0xe9], symbol, 4),
+        Instruction::Jump(target) => encoder.modrm(false, false, &[0xff], 4, target, &[])?,
+        Instruction::JumpIf(condition, Operand::Symbol(symbol)) if short_jump => encoder.relative(&[0x70 + condition_code(*condition)], symbol, 1),
+        Instruction::JumpIf(condition, Operand::Symbol(symbol)) => encoder.relative(&[0x0f, 0x80 + condition_code(*condition)], symbol, 4),
+        Instruction::Call(Operand::Symbol(symbol)) => encoder.relative(&[0xe8], symbol, 4),
+        Instruction::Call(target) => encoder.modrm(false, false, &[0xff], 2, target, &[])?,
+        Instruction::Push(register) => encoder.register_in_opcode(0x50, *register),
+        Instruction::Pop(register) => encoder.register_in_opcode(0x58, *register),
+        Instruction::Leave => encoder.bytes.push(0xc9),
+        Instruction::Return => encoder.bytes.push(0xc3),
+        Instruction::Nop => encoder.bytes.push(0x90),
+        unsupported => return Err(format!("Unable to encode [{:?}]", unsupported).into()),
+    }
+    Ok(encoder.finish())
+}
+
+// The low bytes of %rsp, %rbp, %rsi and %rdi are only named with a REX prefix, without which
+// the same numbers give %ah, %ch, %dh and %bh
+fn forces_rex(operands: &[&Operand]) -> bool {
+    operands.iter().any(|operand| match operand {
+        Operand::Register(register, Size::Byte) => (4..8).contains(&(*register as u8)),
+        _ => false,
+    })
+}
+
+// The number the processor gives a condition, added to the opcode of `j`, `set` and `cmov`
+fn condition_code(condition: Condition) -> u8 {
+    match condition {
+        Condition::Below => 0x2,
+        Condition::AboveEqual => 0x3,
+        Condition::Equal => 0x4,
+        Condition::NotEqual => 0x5,
+        Condition::BelowEqual => 0x6,
+        Condition::Above => 0x7,
+        Condition::Less => 0xc,
+        Condition::GreaterEqual => 0xd,
+        Condition::LessEqual => 0xe,
+        Condition::Greater => 0xf,
+    }
+}
+
+fn fits_in_byte(value: i64) -> bool {
+    value >= i8::MIN as i64 && value <= i8::MAX as i64
+}
+
+fn fits_in_double_word(value: i64) -> bool {
+    value >= i32::MIN as i64 && value <= i32::MAX as i64
+}
+
+struct Encoder {
+    bytes: Vec<u8>,
+    fixup: Option<Fixup>,
+}
+
+impl Encoder {
+    // The REX prefix, needed for 64 bit operands, for registers numbered from 8 and for the low
+    // bytes of a few of the others
+    fn rex(&mut self, wide: bool, reg: u8, index: u8, base: u8, forced: bool) {
+        let rex = 0x40 | (wide as u8) << 3 | (reg >> 3 & 1) << 2 | (index >> 3 & 1) << 1 | (base >> 3 & 1);
+        if rex != 0x40 || forced {
+            self.bytes.push(rex);
+        }
+    }
+
+    // An instruction with a ModRM byte: `reg` is either a register or the extension of the opcode,
+    // and `rm` the register or memory operand, followed by any immediate
+    fn modrm(&mut self, wide: bool, forced: bool, opcode: &[u8], reg: u8, rm: &Operand, immediate: &[u8]) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        match rm {
+            Operand::Register(register, _) => {
+                self.rex(wide, reg, 0, *register as u8, forced);
+                self.bytes.extend_from_slice(opcode);
+                self.bytes.push(0xc0 | (reg & 7) << 3 | (*register as u8 & 7));
+            }
+            Operand::Memory(Address::Relative(symbol)) => {
+                self.rex(wide, reg, 0, 0, forced);
+                self.bytes.extend_from_slice(opcode);
+                self.bytes.push((reg & 7) << 3 | 0x5);
+                self.reference(symbol, 4);
+            }
+            Operand::Memory(Address::Indexed { base, index, displacement }) => {
+                let base = *base as u8;
+                self.rex(wide, reg, index.map_or(0, |(index, _)| index as u8), base, forced);
+                self.bytes.extend_from_slice(opcode);
+                // %rbp and %r13 as a base always take a displacement, as the encoding without one
+                // means an address relative to the instruction
+                let mode = match *displacement {
+                    0 if base & 7 != 5 => 0x00,
+                    d if fits_in_byte(d as i64) => 0x40,
+                    _ => 0x80,
+                };
+                // %rsp and %r12 as a base, and any index, need a SIB byte
+                match index {
+                    None if base & 7 != 4 => self.bytes.push(mode | (reg & 7) << 3 | (base & 7)),
+                    _ => {
+                        self.bytes.push(mode | (reg & 7) << 3 | 0x4);
+                        let (index, scale) = index.map_or((0x4, 1), |(index, scale)| (index as u8 & 7, scale));
+                        let scale = match scale {
+                            1 => 0,
+                            2 => 1,
+                            4 => 2,
+                            _ => 3,
+                        };
+                        self.bytes.push(scale << 6 | index << 3 | (base & 7));
+                    }
+                }
+                match mode {
+                    0x40 => self.bytes.push(*displacement as u8),
+                    0x80 => self.bytes.extend_from_slice(&displacement.to_le_bytes()),
+                    _ => {}
+                }
+            }
+            unexpected => return Err(format!("Unable to address [{:?}] with a ModRM byte", unexpected).into()),
+        }
+        self.bytes.extend_from_slice(immediate);
+        Ok(())
+    }
+
+    // An instruction on one operand, with opcodes for a byte and for a wider operand
+    fn unary(&mut self, size: Size, opcodes: &[u8; 2], extension: u8, operand: &Operand) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        let opcode = if size == Size::Byte { opcodes[0] } else { opcodes[1] };
+        self.modrm(size == Size::QuadWord, forces_rex(&[operand]), &[opcode], extension, operand, &[])
+    }
+
+    fn register_in_opcode(&mut self, opcode: u8, register: Register) {
+        self.rex(false, 0, 0, register as u8, false);
+        self.bytes.push(opcode + (register as u8 & 7));
+    }
+
+    fn move_value(&mut self, size: Size, source: &Operand, destination: &Operand) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        let wide = size == Size::QuadWord;
+        let byte = size == Size::Byte;
+        let forced = forces_rex(&[source, destination]);
+        match (source, destination) {
+            (Operand::Register(register, _), Operand::Register(_, _)) | (Operand::Register(register, _), Operand::Memory(_)) =>
+                self.modrm(wide, forced, &[if byte { 0x88 } else { 0x89 }], *register as u8, destination, &[])?,
+            (Operand::Memory(_), Operand::Register(register, _)) =>
+                self.modrm(wide, forced, &[if byte { 0x8a } else { 0x8b }], *register as u8, source, &[])?,
+            (Operand::Immediate(value), _) => return self.move_immediate(size, *value, destination),
+            _ => return Err(format!("Unable to encode moving [{:?}] to [{:?}]", source, destination).into()),
+        }
+        Ok(())
+    }
+
+    fn move_immediate(&mut self, size: Size, value: i64, destination: &Operand) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        match (size, destination) {
+            (Size::Byte, Operand::Register(register, _)) => {
+                self.rex(false, 0, 0, *register as u8, forces_rex(&[destination]));
+                self.bytes.push(0xb0 + (*register as u8 & 7));
+                self.bytes.push(value as u8);
+            }
+            (Size::DoubleWord, Operand::Register(register, _)) => {
+                self.register_in_opcode(0xb8, *register);
+                self.bytes.extend_from_slice(&(value as i32).to_le_bytes());
+            }
+            // A 64 bit register takes a sign extended 32 bit constant, and only otherwise all 64 bits
+            (Size::QuadWord, Operand::Register(_, _)) if fits_in_double_word(value) =>
+                self.modrm(true, false, &[0xc7], 0, destination, &(value as i32).to_le_bytes())?,
+            (Size::QuadWord, Operand::Register(register, _)) => {
+                self.rex(true, 0, 0, *register as u8, false);
+                self.bytes.push(0xb8 + (*register as u8 & 7));
+                self.bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            (Size::Byte, Operand::Memory(_)) => self.modrm(false, false, &[0xc6], 0, destination, &[value as u8])?,
+            (_, Operand::Memory(_)) if fits_in_double_word(value) =>
+                self.modrm(size == Size::QuadWord, false, &[0xc7], 0, destination, &(value as i32).to_le_bytes())?,
+            _ => return Err(format!("Unable to encode moving {} to [{:?}]", value, destination).into()),
+        }
+        Ok(())
+    }
+
+    fn arithmetic(&mut self, operation: Arithmetic, size: Size, source: &Operand, destination: &Operand) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        let wide = size == Size::QuadWord;
+        let byte = size == Size::Byte;
+        let forced = forces_rex(&[source, destination]);
+        // The opcodes of each operation follow from the first, with the extension used with a constant
+        let (opcode, extension) = match operation {
+            // Multiplying only has the forms writing to a register, which with a constant also
+            // names the register as the operand
+            Arithmetic::Multiply => return match (source, destination) {
+                (Operand::Immediate(value), Operand::Register(register, _)) if !byte && fits_in_byte(*value) =>
+                    self.modrm(wide, false, &[0x6b], *register as u8, destination, &[*value as u8]),
+                (Operand::Immediate(value), Operand::Register(register, _)) if !byte && fits_in_double_word(*value) =>
+                    self.modrm(wide, false, &[0x69], *register as u8, destination, &(*value as i32).to_le_bytes()),
+                (Operand::Register(_, _) | Operand::Memory(_), Operand::Register(register, _)) if !byte =>
+                    self.modrm(wide, false, &[0x0f, 0xaf], *register as u8, source, &[]),
+                _ => Err(format!("Unable to encode multiplying [{:?}] by [{:?}]", destination, source).into()),
+            },
+            Arithmetic::Add => (0x00, 0),
+            Arithmetic::And => (0x20, 4),
+            Arithmetic::Subtract => (0x28, 5),
+            Arithmetic::Xor => (0x30, 6),
+            Arithmetic::Compare => (0x38, 7),
+            Arithmetic::Test => (0x84, 0),
+        };
+        let width = if byte { 0 } else { 1 };
+        match (source, destination) {
+            (Operand::Register(register, _), _) => self.modrm(wide, forced, &[opcode + width], *register as u8, destination, &[])?,
+            (Operand::Memory(_), Operand::Register(register, _)) if operation != Arithmetic::Test =>
+                self.modrm(wide, forced, &[opcode + 2 + width], *register as u8, source, &[])?,
+            (Operand::Immediate(value), _) if operation != Arithmetic::Test => {
+                let accumulator = matches!(destination, Operand::Register(Register::Rax, _));
+                if byte {
+                    if accumulator {
+                        self.bytes.extend_from_slice(&[opcode + 4, *value as u8]);
+                    } else {
+                        self.modrm(false, forced, &[0x80], extension, destination, &[*value as u8])?;
+                    }
+                } else if fits_in_byte(*value) {
+                    self.modrm(wide, false, &[0x83], extension, destination, &[*value as u8])?;
+                } else if !fits_in_double_word(*value) {
+                    return Err(format!("Unable to encode the constant {} in [{:?}]", value, operation).into());
+                } else if accumulator {
+                    self.rex(wide, 0, 0, 0, false);
+                    self.bytes.push(opcode + 5);
+                    self.bytes.extend_from_slice(&(*value as i32).to_le_bytes());
+                } else {
+                    self.modrm(wide, false, &[0x81], extension, destination, &(*value as i32).to_le_bytes())?;
+                }
+            }
+            _ => return Err(format!("Unable to encode [{:?}] of [{:?}] and [{:?}]", operation, source, destination).into()),
+        }
+        Ok(())
+    }
+
+    // An instruction taking the distance to a symbol
+    fn relative(&mut self, opcode: &[u8], symbol: &str, width: usize) {
+        self.bytes.extend_from_slice(opcode);
+        self.reference(symbol, width);
+    }
+
+    // Leaves room for the distance to the symbol, which calls to the C library find through the
+    // procedure linkage table
+    fn reference(&mut self, symbol: &str, width: usize) {
+        let (symbol, plt) = match symbol.strip_suffix("@PLT") {
+            Some(function) => (function, true),
+            None => (symbol, false),
+        };
+        self.fixup = Some(Fixup { offset: self.bytes.len(), width, symbol: symbol.to_string(), plt, addend: 0 });
+        self.bytes.extend(std::iter::repeat_n(0, width));
+    }
+
+    fn finish(mut self) -> Encoding {
+        let length = self.bytes.len() as i64;
+        if let Some(fixup) = &mut self.fixup {
+            fixup.addend = fixup.offset as i64 - length;
+        }
+        Encoding { bytes: self.bytes, fixup: self.fixup }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(instruction: Instruction) -> Vec<u8> {
+        encode(&instruction, false).unwrap().bytes
+    }
+
+    fn register(register: Register, size: Size) -> Operand {
+        Operand::Register(register, size)
+    }
+
+    fn memory(base: Register, displacement: i32) -> Operand {
+        Operand::Memory(Address::offset(base, displacement))
+    }
+
+    // Each as objdump shows the GNU assembler encoding it
+    #[test]
+    fn instructions_encode_to_known_bytes() {
+        let known = [
+            (Instruction::Move(Size::QuadWord, register(Register::Rax, Size::QuadWord), register(Register::Rbx, Size::QuadWord)), vec![0x48, 0x89, 0xc3]),
+            (Instruction::Move(Size::Byte, register(Register::Rsi, Size::Byte), memory(Register::Rdi, 0)), vec![0x40, 0x88, 0x37]),
+            (Instruction::Move(Size::DoubleWord, memory(Register::Rbp, -8), register(Register::R12, Size::DoubleWord)), vec![0x44, 0x8b, 0x65, 0xf8]),
+            (Instruction::Move(Size::QuadWord, memory(Register::R12, 0), register(Register::Rax, Size::QuadWord)), vec![0x49, 0x8b, 0x04, 0x24]),
+            (Instruction::Move(Size::QuadWord, memory(Register::R13, 0), register(Register::Rax, Size::QuadWord)), vec![0x49, 0x8b, 0x45, 0x00]),
+            (Instruction::LoadAddress(Address::Indexed { base: Register::Rsp, index: Some((Register::Rcx, 8)), displacement: 16 }, Register::Rdx),
+                vec![0x48, 0x8d, 0x54, 0xcc, 0x10]),
+            (Instruction::LoadConstant(u64::MAX, Register::Rax), vec![0x48, 0xc7, 0xc0, 0xff, 0xff, 0xff, 0xff]),
+            (Instruction::LoadConstant(4294967296, Register::R9), vec![0x49, 0xb9, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00]),
+            (Instruction::Arithmetic(Arithmetic::Add, Size::QuadWord, Operand::Immediate(100000), register(Register::Rax, Size::QuadWord)),
+                vec![0x48, 0x05, 0xa0, 0x86, 0x01, 0x00]),
+            (Instruction::Arithmetic(Arithmetic::Subtract, Size::DoubleWord, Operand::Immediate(1), register(Register::Rcx, Size::DoubleWord)), vec![0x83, 0xe9, 0x01]),
+            (Instruction::Arithmetic(Arithmetic::Compare, Size::Byte, Operand::Immediate(200), register(Register::Rax, Size::Byte)), vec![0x3c, 0xc8]),
+            (Instruction::Arithmetic(Arithmetic::Multiply, Size::QuadWord, register(Register::R8, Size::QuadWord), register(Register::Rcx, Size::QuadWord)),
+                vec![0x49, 0x0f, 0xaf, 0xc8]),
+            (Instruction::Arithmetic(Arithmetic::Multiply, Size::QuadWord, Operand::Immediate(8), register(Register::R10, Size::QuadWord)), vec![0x4d, 0x6b, 0xd2, 0x08]),
+            (Instruction::Shift(Shift::RightArithmetic, Size::QuadWord, register(Register::R11, Size::QuadWord)), vec![0x49, 0xd3, 0xfb]),
+            (Instruction::Set(Condition::Less, register(Register::Rdi, Size::Byte)), vec![0x40, 0x0f, 0x9c, 0xc7]),
+            (Instruction::ConditionalMove(Condition::Greater, Size::QuadWord, memory(Register::Rbp, 8), register(Register::Rax, Size::QuadWord)),
+                vec![0x48, 0x0f, 0x4f, 0x45, 0x08]),
+            (Instruction::SignedDivide(Size::QuadWord, register(Register::R10, Size::QuadWord)), vec![0x49, 0xf7, 0xfa]),
+            (Instruction::Push(Register::R12), vec![0x41, 0x54]),
+        ];
+        for (instruction, expected) in known {
+            assert_eq!(bytes(instruction.clone()), expected, "{:?}", instruction);
+        }
+    }
+
+    #[test]
+    fn reference_is_measured_from_the_end_of_the_instruction() {
+        let encoding = encode(&Instruction::Move(Size::DoubleWord, Operand::Immediate(5), Operand::Memory(Address::Relative("g".to_string()))), false).unwrap();
+
+        assert_eq!(encoding.bytes, vec![0xc7, 0x05, 0, 0, 0, 0, 0x05, 0, 0, 0]);
+        assert_eq!(encoding.fixup, Some(Fixup { offset: 2, width: 4, symbol: "g".to_string(), plt: false, addend: -8 }));
+    }
+
+    #[test]
+    fn calls_to_the_c_library_go_through_the_procedure_linkage_table() {
+        let encoding = encode(&Instruction::Call(Operand::Symbol("printf@PLT".to_string())), false).unwrap();
+
+        assert_eq!(encoding.bytes, vec![0xe8, 0, 0, 0, 0]);
+        assert_eq!(encoding.fixup, Some(Fixup { offset: 1, width: 4, symbol: "printf".to_string(), plt: true, addend: -4 }));
+    }
+
+    #[test]
+    fn short_jumps_take_one_byte() {
+        let label = Operand::Symbol("l".to_string());
+
+        let jump = encode(&Instruction::Jump(label.clone()), true).unwrap();
+        assert_eq!(jump.bytes, vec![0xeb, 0]);
+        assert_eq!(jump.fixup, Some(Fixup { offset: 1, width: 1, symbol: "l".to_string(), plt: false, addend: -1 }));
+
+        assert_eq!(encode(&Instruction::JumpIf(Condition::NotEqual, label.clone()), true).unwrap().bytes, vec![0x75, 0]);
+        assert_eq!(encode(&Instruction::JumpIf(Condition::NotEqual, label), false).unwrap().bytes, vec![0x0f, 0x85, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn instructions_without_an_encoding_are_errors() {
+        let unencodable = [
+            Instruction::Arithmetic(Arithmetic::Add, Size::QuadWord, Operand::Immediate(1 << 40), register(Register::Rax, Size::QuadWord)),
+            Instruction::Move(Size::QuadWord, Operand::Immediate(1 << 40), memory(Register::Rbp, -8)),
+            Instruction::Move(Size::QuadWord, memory(Register::Rbp, -8), memory(Register::Rbp, -16)),
+            Instruction::Arithmetic(Arithmetic::Multiply, Size::Byte, register(Register::Rcx, Size::Byte), register(Register::Rax, Size::Byte)),
+            Instruction::Arithmetic(Arithmetic::Test, Size::QuadWord, Operand::Immediate(1), register(Register::Rax, Size::QuadWord)),
+            Instruction::SignExtend(Size::Byte, Size::QuadWord, register(Register::Rax, Size::Byte), memory(Register::Rbp, -8)),
+        ];
+        for instruction in unencodable {
+            assert!(encode(&instruction, false).is_err(), "{:?}", instruction);
+        }
+    }
+}
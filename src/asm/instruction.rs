@@ -0,0 +1,223 @@
+/// The general purpose registers of x86-64, in the order the processor numbers them
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Register {
+    Rax,
+    Rcx,
+    Rdx,
+    Rbx,
+    Rsp,
+    Rbp,
+    Rsi,
+    Rdi,
+    R8,
+    R9,
+    R10,
+    R11,
+    R12,
+    R13,
+    R14,
+    R15,
+}
+
+const QUAD_WORD_NAMES: [&str; 16] = [
+    "rax", "rcx", "rdx", "rbx", "rsp", "rbp", "rsi", "rdi", "r8", "r9", "r10", "r11", "r12", "r13", "r14", "r15"
+];
+
+const DOUBLE_WORD_NAMES: [&str; 16] = [
+    "eax", "ecx", "edx", "ebx", "esp", "ebp", "esi", "edi", "r8d", "r9d", "r10d", "r11d", "r12d", "r13d", "r14d", "r15d"
+];
+
+const BYTE_NAMES: [&str; 16] = [
+    "al", "cl", "dl", "bl", "spl", "bpl", "sil", "dil", "r8b", "r9b", "r10b", "r11b", "r12b", "r13b", "r14b", "r15b"
+];
+
+impl Register {
+    /// The name of the low `size` bytes of the register, without the `%` AT&T syntax puts before it
+    pub fn name(&self, size: Size) -> &'static str {
+        let number = *self as usize;
+        match size {
+            Size::Byte => BYTE_NAMES[number],
+            Size::DoubleWord => DOUBLE_WORD_NAMES[number],
+            Size::QuadWord => QUAD_WORD_NAMES[number],
+        }
+    }
+}
+
+/// How many bytes an instruction works on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Size {
+    Byte,
+    DoubleWord,
+    QuadWord,
+}
+
+impl Size {
+    /// The size of a value `bytes` wide
+    pub fn of(bytes: u32) -> core::result::Result<Size, Box<dyn std::error::Error>> {
+        match bytes {
+            1 => Ok(Size::Byte),
+            4 => Ok(Size::DoubleWord),
+            8 => Ok(Size::QuadWord),
+            unhandled => Err(format!("Unable to move a value of {} bytes, no instruction works on it", unhandled).into()),
+        }
+    }
+}
+
+/// The flags left by a comparison that an instruction can test
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Condition {
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Below,
+    BelowEqual,
+    Above,
+    AboveEqual,
+}
+
+impl Condition {
+    /// The letters naming the condition at the end of `j`, `set` and `cmov`
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            Condition::Equal => "e",
+            Condition::NotEqual => "ne",
+            Condition::Less => "l",
+            Condition::LessEqual => "le",
+            Condition::Greater => "g",
+            Condition::GreaterEqual => "ge",
+            Condition::Below => "b",
+            Condition::BelowEqual => "be",
+            Condition::Above => "a",
+            Condition::AboveEqual => "ae",
+        }
+    }
+}
+
+/// Where in memory an operand is
+#[derive(Clone, Debug, PartialEq)]
+pub enum Address {
+    /// A symbol, found relative to the instruction pointer
+    Relative(String),
+    /// `base + index * scale + displacement`
+    Indexed { base: Register, index: Option<(Register, u8)>, displacement: i32 },
+}
+
+impl Address {
+    /// The address `displacement` bytes from the one held in `base`
+    pub fn offset(base: Register, displacement: i32) -> Address {
+        Address::Indexed { base, index: None, displacement }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operand {
+    Register(Register, Size),
+    Immediate(i64),
+    Memory(Address),
+    /// A label or symbol to jump to or call
+    Symbol(String),
+}
+
+impl Operand {
+    /// The whole of the register
+    pub fn register(register: Register) -> Operand {
+        Operand::Register(register, Size::QuadWord)
+    }
+}
+
+/// The operations on two operands that write their result, if any, to the second
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Arithmetic {
+    Add,
+    Subtract,
+    Multiply,
+    And,
+    Xor,
+    /// Subtracts only to set the flags
+    Compare,
+    /// Ands only to set the flags
+    Test,
+}
+
+/// The shifts by the count held in `%cl`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shift {
+    Left,
+    /// Shifts right, filling with copies of the sign bit
+    RightArithmetic,
+    /// Shifts right, filling with zeros
+    RightLogical,
+}
+
+/// The instructions the x86-64 code generator writes. Operands are in AT&T order, source first.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Instruction {
+    Move(Size, Operand, Operand),
+    /// Loads a 64 bit constant into a register, written as the unsigned number it is
+    LoadConstant(u64, Register),
+    /// Moves a value of the first size into a register of the second, sign extending it
+    SignExtend(Size, Size, Operand, Operand),
+    /// Moves a value of the first size into a register of the second, zero extending it
+    ZeroExtend(Size, Size, Operand, Operand),
+    LoadAddress(Address, Register),
+    Arithmetic(Arithmetic, Size, Operand, Operand),
+    Shift(Shift, Size, Operand),
+    Increment(Size, Operand),
+    /// Divides `%rdx:%rax` by the operand as unsigned numbers, leaving the quotient in `%rax`
+    Divide(Size, Operand),
+    /// Divides `%rdx:%rax` by the operand as signed numbers, leaving the quotient in `%rax`
+    SignedDivide(Size, Operand),
+    /// Sign extends `%rax` into `%rdx` ahead of a signed division
+    SignExtendAccumulator,
+    /// Sets the byte to 1 when the condition holds and 0 when it does not
+    Set(Condition, Operand),
+    ConditionalMove(Condition, Size, Operand, Operand),
+    /// Jumps to a label, or to the address held in a register
+    Jump(Operand),
+    JumpIf(Condition, Operand),
+    Call(Operand),
+    Push(Register),
+    Pop(Register),
+    Leave,
+    Return,
+    Nop,
+}
+
+/// A line of assembly
+#[derive(Clone, Debug, PartialEq)]
+pub enum Line {
+    Instruction(Instruction),
+    /// An instruction followed by a comment, with the spacing before the comment kept as it is
+    Commented(Instruction, &'static str),
+    /// An instruction written with no space after the commas between its operands
+    Compact(Instruction),
+    Label(String),
+    /// An assembler directive, such as `.text`, with anything it takes
+    Directive(String),
+    Comment(String),
+    Blank,
+}
+
+/// The lines of a function, held until it ends so that they can be looked over as a whole
+#[derive(Clone, Debug, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub lines: Vec<Line>,
+}
+
+impl Function {
+    pub fn new(name: &str) -> Function {
+        Function { name: name.to_string(), lines: Vec::new() }
+    }
+
+    /// The instructions of the function, leaving out its labels, directives and comments
+    pub fn instructions(&self) -> impl Iterator<Item = &Instruction> {
+        self.lines.iter().filter_map(|line| match line {
+            Line::Instruction(instruction) | Line::Commented(instruction, _) | Line::Compact(instruction) => Some(instruction),
+            Line::Label(_) | Line::Directive(_) | Line::Comment(_) | Line::Blank => None,
+        })
+    }
+}
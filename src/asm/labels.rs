@@ -0,0 +1,57 @@
+use std::fmt;
+
+use crate::asm::target::Target;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LabelIndex {
+    index: u32,
+    prefix: &'static str,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Labels {
+    next: u32,
+    prefix: &'static str,
+}
+
+impl Labels {
+    pub fn new() -> Labels {
+        Labels {
+            next: 1,
+            prefix: "L",
+        }
+    }
+
+    /// Labels the assembler for the target keeps to itself, so they can never clash with a name
+    /// from the program
+    pub fn for_target(target: Target) -> Labels {
+        Labels {
+            prefix: target.local_label_prefix(),
+            ..Labels::new()
+        }
+    }
+
+    pub fn allocate_label(&mut self) -> LabelIndex {
+        let label = LabelIndex { index: self.next, prefix: self.prefix };
+        self.next += 1;
+        label
+    }
+}
+
+impl Default for Labels {
+    fn default() -> Self {
+        Labels::new()
+    }
+}
+
+impl LabelIndex {
+    pub fn name(&self) -> String {
+        format!("{}{}", self.prefix, self.index)
+    }
+}
+
+impl fmt::Display for LabelIndex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
@@ -1,138 +1,568 @@
 use std::io::Write;
 
+use crate::asm::instruction::{Address, Arithmetic, Condition, Instruction, Line, Operand, Register, Shift, Size};
+use crate::asm::labels::{LabelIndex, Labels};
 use crate::asm::registers::{RegisterIndex, Registers};
+use crate::asm::strings::{StringIndex, Strings};
+use crate::asm::syntax::{Assembly, Syntax};
+use crate::asm::target::Target;
+use crate::types::Type;
 
+pub mod aarch64;
+pub mod backend;
+pub mod elf;
+pub mod encoder;
+pub mod instruction;
+pub mod labels;
 pub mod registers;
+pub mod riscv64;
+pub mod strings;
+pub mod syntax;
+pub mod target;
+pub mod x86_64;
 
-pub fn cgadd<W: Write>(r1: RegisterIndex, r2: RegisterIndex, registers: &mut Registers, mut out: W) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
-    writeln!(out, "\taddq\t{}, {}", r1.name(), r2.name())?;
+// The whole of the register an index names
+fn register(r: RegisterIndex) -> Operand {
+    Operand::register(r.register())
+}
+
+// The low `size` bytes of the register an index names
+fn register_for_size(r: RegisterIndex, size: Size) -> Operand {
+    Operand::Register(r.register(), size)
+}
+
+// The value at the address held in the register an index names
+fn pointed_to_by(r: RegisterIndex) -> Operand {
+    Operand::Memory(Address::offset(r.register(), 0))
+}
+
+pub fn cgadd<W: Write>(r1: RegisterIndex, r2: RegisterIndex, registers: &mut Registers, out: &mut Assembly<W>) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+    out.instruction(Instruction::Arithmetic(Arithmetic::Add, Size::QuadWord, register(r1), register(r2)))?;
     registers.free_register(r1);
-    return Ok(r2);
+    Ok(r2)
 }
 
-pub fn cgmul<W: Write>(r1: RegisterIndex, r2: RegisterIndex, registers: &mut Registers, mut out: W) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
-    writeln!(out, "\timulq\t{}, {}", r1.name(), r2.name())?;
+pub fn cgmul<W: Write>(r1: RegisterIndex, r2: RegisterIndex, registers: &mut Registers, out: &mut Assembly<W>) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+    out.instruction(Instruction::Arithmetic(Arithmetic::Multiply, Size::QuadWord, register(r1), register(r2)))?;
     registers.free_register(r1);
-    return Ok(r2);
+    Ok(r2)
 }
 
-pub fn cgsub<W: Write>(r1: RegisterIndex, r2: RegisterIndex, registers: &mut Registers, mut out: W) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
-    writeln!(out, "\tsubq\t{}, {}", r2.name(), r1.name())?;
+// Adds, subtracts or multiplies by a constant given to the instruction, which takes one that fits
+// in 32 bits once sign extended
+pub fn cgarithmeticconst<W: Write>(operation: Arithmetic, r: RegisterIndex, value: i64, out: &mut Assembly<W>) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+    out.instruction(Instruction::Arithmetic(operation, Size::QuadWord, Operand::Immediate(value), register(r)))?;
+    Ok(r)
+}
+
+pub fn cgsub<W: Write>(r1: RegisterIndex, r2: RegisterIndex, registers: &mut Registers, out: &mut Assembly<W>) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+    out.instruction(Instruction::Arithmetic(Arithmetic::Subtract, Size::QuadWord, register(r2), register(r1)))?;
     registers.free_register(r2);
-    return Ok(r1);
+    Ok(r1)
 }
 
-pub fn cgdiv<W: Write>(r1: RegisterIndex, r2: RegisterIndex, registers: &mut Registers, mut out: W) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
-    writeln!(out, "\tmovq\t{}, {}", r1.name(), "%rax")?;
-    writeln!(out, "\tcqo")?;
-    writeln!(out, "\tidivq\t{}", r2.name())?;
-    writeln!(out, "\tmovq\t{}, {}", "%rax", r1.name())?;
+pub fn cgdiv<W: Write>(r1: RegisterIndex, r2: RegisterIndex, ty: &Type, registers: &mut Registers, out: &mut Assembly<W>) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+    out.instruction(Instruction::Move(Size::QuadWord, register(r1), Operand::register(Register::Rax)))?;
+    if ty.is_signed() {
+        out.instruction(Instruction::SignExtendAccumulator)?;
+        out.instruction(Instruction::SignedDivide(Size::QuadWord, register(r2)))?;
+    } else {
+        out.instruction(Instruction::Arithmetic(Arithmetic::Xor, Size::QuadWord, Operand::register(Register::Rdx), Operand::register(Register::Rdx)))?;
+        out.instruction(Instruction::Divide(Size::QuadWord, register(r2)))?;
+    }
+    out.instruction(Instruction::Move(Size::QuadWord, Operand::register(Register::Rax), register(r1)))?;
     registers.free_register(r2);
-    return Ok(r1);
+    Ok(r1)
+}
+
+
+pub fn cgcomment<W: Write>(out: &mut Assembly<W>, comment: &str) -> core::result::Result<(), Box<dyn std::error::Error>> {
+    out.line(Line::Comment(comment.to_string()))
+}
+
+// printint and printuint pass the value in %rdi on to printf with the format for it
+fn cgprintroutine(name: &str, format: StringIndex, target: Target) -> Vec<Line> {
+    vec![
+        Line::Label(name.to_string()),
+        Line::Instruction(Instruction::Push(Register::Rbp)),
+        Line::Instruction(Instruction::Move(Size::QuadWord, Operand::register(Register::Rsp), Operand::register(Register::Rbp))),
+        Line::Instruction(Instruction::Arithmetic(Arithmetic::Subtract, Size::QuadWord, Operand::Immediate(16), Operand::register(Register::Rsp))),
+        Line::Instruction(Instruction::Move(Size::QuadWord, Operand::register(Register::Rdi), Operand::Memory(Address::offset(Register::Rbp, -8)))),
+        Line::Instruction(Instruction::Move(Size::QuadWord, Operand::Memory(Address::offset(Register::Rbp, -8)), Operand::register(Register::Rax))),
+        Line::Instruction(Instruction::Move(Size::QuadWord, Operand::register(Register::Rax), Operand::register(Register::Rsi))),
+        Line::Instruction(Instruction::LoadAddress(Address::Relative(format.to_string()), Register::Rdi)),
+        Line::Instruction(Instruction::Move(Size::DoubleWord, Operand::Immediate(0), Operand::Register(Register::Rax, Size::DoubleWord))),
+        Line::Instruction(Instruction::Call(Operand::Symbol(target.function("printf")))),
+        Line::Instruction(Instruction::Nop),
+        Line::Instruction(Instruction::Leave),
+        Line::Instruction(Instruction::Return),
+    ]
+}
+
+pub fn cgpreamble<W: Write>(target: Target, frame_size: u32, strings: &mut Strings, out: &mut Assembly<W>) -> core::result::Result<(), Box<dyn std::error::Error>> {
+    // The assembler reads AT&T syntax unless told otherwise
+    if out.syntax() == Syntax::Intel {
+        out.line(Line::Directive(".intel_syntax noprefix".to_string()))?;
+    }
+    out.line(Line::Comment("Start of preamble".to_string()))?;
+    out.line(Line::Directive(".text".to_string()))?;
+    // Each routine is held as a function of its own until the next begins
+    for (name, format) in [("printint", "%ld\n"), ("printuint", "%lu\n")] {
+        out.begin_function(&target.runtime(name))?;
+        out.lines(cgprintroutine(&target.runtime(name), strings.intern(format), target))?;
+    }
+    out.begin_function(&target.runtime("printstr"))?;
+    out.lines(vec![
+        Line::Label(target.runtime("printstr")),
+        Line::Instruction(Instruction::Push(Register::Rbp)),
+        Line::Instruction(Instruction::Move(Size::QuadWord, Operand::register(Register::Rsp), Operand::register(Register::Rbp))),
+        Line::Instruction(Instruction::Call(Operand::Symbol(target.function("puts")))),
+        Line::Instruction(Instruction::Nop),
+        Line::Instruction(Instruction::Leave),
+        Line::Instruction(Instruction::Return),
+    ])?;
+    // readint gives the scanf status in %rax and the value it read in %rdx
+    out.begin_function(&target.runtime("readint"))?;
+    out.lines(vec![
+        Line::Label(target.runtime("readint")),
+        Line::Instruction(Instruction::Push(Register::Rbp)),
+        Line::Instruction(Instruction::Move(Size::QuadWord, Operand::register(Register::Rsp), Operand::register(Register::Rbp))),
+        Line::Instruction(Instruction::Arithmetic(Arithmetic::Subtract, Size::QuadWord, Operand::Immediate(16), Operand::register(Register::Rsp))),
+        Line::Instruction(Instruction::LoadAddress(Address::offset(Register::Rbp, -8), Register::Rsi)),
+        Line::Instruction(Instruction::LoadAddress(Address::Relative(strings.intern("%ld").to_string()), Register::Rdi)),
+        Line::Instruction(Instruction::Move(Size::DoubleWord, Operand::Immediate(0), Operand::Register(Register::Rax, Size::DoubleWord))),
+        Line::Instruction(Instruction::Call(Operand::Symbol(target.function("scanf")))),
+        Line::Instruction(Instruction::SignExtend(Size::DoubleWord, Size::QuadWord, Operand::Register(Register::Rax, Size::DoubleWord), Operand::register(Register::Rax))),
+        Line::Instruction(Instruction::Move(Size::QuadWord, Operand::Memory(Address::offset(Register::Rbp, -8)), Operand::register(Register::Rdx))),
+        Line::Instruction(Instruction::Leave),
+        Line::Instruction(Instruction::Return),
+    ])?;
+    // readline takes the buffer in %rdi and its size in %rsi, and gives the length in %rax
+    let rbx_plus_r13 = Operand::Memory(Address::Indexed { base: Register::Rbx, index: Some((Register::R13, 1)), displacement: 0 });
+    out.begin_function(&target.runtime("readline"))?;
+    out.lines(vec![
+        Line::Label(target.runtime("readline")),
+        Line::Instruction(Instruction::Push(Register::Rbp)),
+        Line::Instruction(Instruction::Move(Size::QuadWord, Operand::register(Register::Rsp), Operand::register(Register::Rbp))),
+        Line::Instruction(Instruction::Push(Register::Rbx)),
+        Line::Instruction(Instruction::Push(Register::R12)),
+        Line::Instruction(Instruction::Push(Register::R13)),
+        Line::Instruction(Instruction::Arithmetic(Arithmetic::Subtract, Size::QuadWord, Operand::Immediate(8), Operand::register(Register::Rsp))),
+        Line::Instruction(Instruction::Move(Size::QuadWord, Operand::register(Register::Rdi), Operand::register(Register::Rbx))),
+        Line::Instruction(Instruction::LoadAddress(Address::offset(Register::Rsi, -1), Register::R12)),
+        Line::Instruction(Instruction::Arithmetic(Arithmetic::Xor, Size::QuadWord, Operand::register(Register::R13), Operand::register(Register::R13))),
+        Line::Label(target.runtime("readline_next")),
+        Line::Instruction(Instruction::Arithmetic(Arithmetic::Compare, Size::QuadWord, Operand::register(Register::R12), Operand::register(Register::R13))),
+        Line::Instruction(Instruction::JumpIf(Condition::GreaterEqual, Operand::Symbol(target.runtime("readline_done")))),
+        Line::Instruction(Instruction::Call(Operand::Symbol(target.function("getchar")))),
+        Line::Instruction(Instruction::Arithmetic(Arithmetic::Compare, Size::DoubleWord, Operand::Immediate(-1), Operand::Register(Register::Rax, Size::DoubleWord))),
+        Line::Instruction(Instruction::JumpIf(Condition::Equal, Operand::Symbol(target.runtime("readline_end")))),
+        Line::Instruction(Instruction::Arithmetic(Arithmetic::Compare, Size::DoubleWord, Operand::Immediate(10), Operand::Register(Register::Rax, Size::DoubleWord))),
+        Line::Instruction(Instruction::JumpIf(Condition::Equal, Operand::Symbol(target.runtime("readline_done")))),
+        Line::Instruction(Instruction::Move(Size::Byte, Operand::Register(Register::Rax, Size::Byte), rbx_plus_r13.clone())),
+        Line::Instruction(Instruction::Increment(Size::QuadWord, Operand::register(Register::R13))),
+        Line::Instruction(Instruction::Jump(Operand::Symbol(target.runtime("readline_next")))),
+        Line::Label(target.runtime("readline_end")),
+        Line::Instruction(Instruction::Move(Size::Byte, Operand::Immediate(0), rbx_plus_r13.clone())),
+        Line::Instruction(Instruction::Move(Size::QuadWord, Operand::Immediate(-1), Operand::register(Register::Rax))),
+        Line::Instruction(Instruction::Arithmetic(Arithmetic::Test, Size::QuadWord, Operand::register(Register::R13), Operand::register(Register::R13))),
+        Line::Instruction(Instruction::JumpIf(Condition::Equal, Operand::Symbol(target.runtime("readline_return")))),
+        Line::Label(target.runtime("readline_done")),
+        Line::Instruction(Instruction::Move(Size::Byte, Operand::Immediate(0), rbx_plus_r13)),
+        Line::Instruction(Instruction::Move(Size::QuadWord, Operand::register(Register::R13), Operand::register(Register::Rax))),
+        Line::Label(target.runtime("readline_return")),
+        Line::Instruction(Instruction::Arithmetic(Arithmetic::Add, Size::QuadWord, Operand::Immediate(8), Operand::register(Register::Rsp))),
+        Line::Instruction(Instruction::Pop(Register::R13)),
+        Line::Instruction(Instruction::Pop(Register::R12)),
+        Line::Instruction(Instruction::Pop(Register::Rbx)),
+        Line::Instruction(Instruction::Pop(Register::Rbp)),
+        Line::Instruction(Instruction::Return),
+    ])?;
+    // main is held until the epilogue, with the code of the program between
+    out.begin_function(&target.symbol("main"))?;
+    out.lines(vec![
+        Line::Directive(format!(".globl\t{}", target.symbol("main"))),
+        Line::Label(target.symbol("main")),
+        Line::Instruction(Instruction::Push(Register::Rbp)),
+        Line::Instruction(Instruction::Move(Size::QuadWord, Operand::register(Register::Rsp), Operand::register(Register::Rbp))),
+        Line::Instruction(Instruction::Arithmetic(Arithmetic::Subtract, Size::QuadWord, Operand::Immediate(frame_size as i64), Operand::register(Register::Rsp))),
+        Line::Instruction(Instruction::Move(Size::QuadWord, Operand::register(Register::Rdi), Operand::Memory(Address::offset(Register::Rbp, parameter_offset(0))))),
+        Line::Instruction(Instruction::Move(Size::QuadWord, Operand::register(Register::Rsi), Operand::Memory(Address::offset(Register::Rbp, parameter_offset(1))))),
+        Line::Comment("End of preamble".to_string()),
+    ])?;
+    Ok(())
+}
+
+/// Where the parameter at the given position is saved in the frame of `main`
+pub fn parameter_offset(index: u32) -> i32 {
+    -8 * (index as i32 + 1)
+}
+
+// Ends the file. An ELF object without a `.note.GNU-stack` section is linked with an executable stack.
+pub fn cgend<W: Write>(target: Target, out: &mut Assembly<W>) -> core::result::Result<(), Box<dyn std::error::Error>> {
+    if target.is_elf() {
+        out.line(Line::Directive(".section\t.note.GNU-stack,\"\",@progbits".to_string()))?;
+    }
+    Ok(())
+}
+
+pub fn cgpostamble<W: Write>(out: &mut Assembly<W>) -> core::result::Result<(), Box<dyn std::error::Error>> {
+    out.line(Line::Commented(Instruction::Move(Size::DoubleWord, Operand::Immediate(0), Operand::Register(Register::Rax, Size::DoubleWord)), "             # Start of postamble"))?;
+    out.instruction(Instruction::Leave)?;
+    out.line(Line::Commented(Instruction::Return, "                         # End of postamble"))?;
+    Ok(())
 }
 
+// Checks the index held in `r` is within an array of `length` elements, calling `boundsfail`
+// with the line of the access when it is not
+pub fn cgboundscheck<W: Write>(target: Target, r: RegisterIndex, length: u32, line: u32, labels: &mut Labels, out: &mut Assembly<W>) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+    let ok = labels.allocate_label();
+    out.instruction(Instruction::Arithmetic(Arithmetic::Compare, Size::QuadWord, Operand::Immediate(length as i64), register(r)))?;
+    out.instruction(Instruction::JumpIf(Condition::Below, Operand::Symbol(ok.to_string())))?;
+    out.instruction(Instruction::Move(Size::QuadWord, Operand::Immediate(line as i64), Operand::register(Register::Rdi)))?;
+    out.instruction(Instruction::Call(Operand::Symbol(target.runtime("boundsfail"))))?;
+    cglabel(ok, out)?;
+    Ok(r)
+}
 
-pub fn cgcomment<W: Write>(mut out: W, comment: &str) -> core::result::Result<(), Box<dyn std::error::Error>> {
-    writeln!(out, "\t# {}", comment)?;
+// Reports an out of bounds index on the line passed in %rdi and aborts
+pub fn cgboundsfail<W: Write>(target: Target, strings: &mut Strings, out: &mut Assembly<W>) -> core::result::Result<(), Box<dyn std::error::Error>> {
+    out.begin_function(&target.runtime("boundsfail"))?;
+    out.lines(vec![
+        Line::Label(target.runtime("boundsfail")),
+        Line::Instruction(Instruction::Push(Register::Rbp)),
+        Line::Instruction(Instruction::Move(Size::QuadWord, Operand::register(Register::Rsp), Operand::register(Register::Rbp))),
+        Line::Instruction(Instruction::Move(Size::QuadWord, Operand::register(Register::Rdi), Operand::register(Register::Rsi))),
+        Line::Instruction(Instruction::LoadAddress(Address::Relative(strings.intern("Array index out of bounds on line %ld\n").to_string()), Register::Rdi)),
+        Line::Instruction(Instruction::Move(Size::DoubleWord, Operand::Immediate(0), Operand::Register(Register::Rax, Size::DoubleWord))),
+        Line::Instruction(Instruction::Call(Operand::Symbol(target.function("printf")))),
+        Line::Instruction(Instruction::Move(Size::DoubleWord, Operand::Immediate(0), Operand::Register(Register::Rdi, Size::DoubleWord))),
+        Line::Instruction(Instruction::Call(Operand::Symbol(target.function("fflush")))),
+        Line::Instruction(Instruction::Call(Operand::Symbol(target.function("abort")))),
+    ])?;
     Ok(())
 }
 
-pub fn cgpreamble<W: Write>(mut out: W) -> core::result::Result<(), Box<dyn std::error::Error>> {
-    writeln!(out, "\t# Start of preamble")?;
-    writeln!(out, "\t.text")?;
-    writeln!(out, ".LC0:")?;
-    writeln!(out, "\t.string\t\"%d\\n\"\n")?;
-    writeln!(out, "printint:")?;
-    writeln!(out, "\tpushq\t%rbp")?;
-    writeln!(out, "\tmovq\t%rsp, %rbp")?;
-    writeln!(out, "\tsubq\t$16, %rsp")?;
-    writeln!(out, "\tmovl\t%edi, -4(%rbp)")?;
-    writeln!(out, "\tmovl\t-4(%rbp), %eax")?;
-    writeln!(out, "\tmovl\t%eax, %esi")?;
-    writeln!(out, "\tleaq\t.LC0(%rip), %rdi")?;
-    writeln!(out, "\tmovl\t$0, %eax")?;
-    writeln!(out, "\tcall\t_printf")?;
-    writeln!(out, "\tnop")?;
-    writeln!(out, "\tleave")?;
-    writeln!(out, "\tret")?;
-    writeln!(out, "\t.globl\t_main")?;
-    writeln!(out, "_main:")?;
-    writeln!(out, "\tpushq\t%rbp")?;
-    writeln!(out, "\tmovq\t%rsp, %rbp")?;
-    writeln!(out, "\t# End of preamble")?;
+pub fn cgload<W: Write>(value: u64, registers: &mut Registers, out: &mut Assembly<W>) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+    let r = registers.allocate_register()?;
+    out.instruction(Instruction::LoadConstant(value, r.register()))?;
+    Ok(r)
+}
+
+pub fn cgprintint<W: Write>(target: Target, r: RegisterIndex, out: &mut Assembly<W>) -> core::result::Result<(), Box<dyn std::error::Error>> {
+    out.line(Line::Compact(Instruction::Move(Size::QuadWord, register(r), Operand::register(Register::Rdi))))?;
+    out.instruction(Instruction::Call(Operand::Symbol(target.runtime("printint"))))?;
     Ok(())
 }
 
-pub fn cgpostamble<W: Write>(mut out: W) -> core::result::Result<(), Box<dyn std::error::Error>> {
-    writeln!(out, "\tmovl	$0, %eax             # Start of postamble")?;
-    writeln!(out, "\tpopq	%rbp")?;
-    writeln!(out, "\tret                         # End of postamble")?;
+pub fn cgprintstr<W: Write>(target: Target, r: RegisterIndex, out: &mut Assembly<W>) -> core::result::Result<(), Box<dyn std::error::Error>> {
+    out.line(Line::Compact(Instruction::Move(Size::QuadWord, register(r), Operand::register(Register::Rdi))))?;
+    out.instruction(Instruction::Call(Operand::Symbol(target.runtime("printstr"))))?;
     Ok(())
 }
 
-pub fn cgload<W: Write>(value: u32, registers: &mut Registers, mut out: W) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
-    let r = registers.allocate_register();
-    writeln!(out, "\tmovq\t${}, {}", value, r.name())?;
-    return Ok(r);
+// %r8 to %r11 belong to the caller, so the ones holding values are pushed around a call. The
+// stack is padded to keep it 16 byte aligned at the call.
+fn cgsave<W: Write>(registers: &Registers, out: &mut Assembly<W>) -> core::result::Result<Vec<RegisterIndex>, Box<dyn std::error::Error>> {
+    let saved = registers.allocated();
+    for r in &saved {
+        out.instruction(Instruction::Push(r.register()))?;
+    }
+    if saved.len() % 2 == 1 {
+        out.instruction(Instruction::Arithmetic(Arithmetic::Subtract, Size::QuadWord, Operand::Immediate(8), Operand::register(Register::Rsp)))?;
+    }
+    Ok(saved)
 }
 
-pub fn cgprintint<W: Write>(r: RegisterIndex, mut out: W) -> core::result::Result<(), Box<dyn std::error::Error>> {
-    writeln!(out, "\tmovq\t{},%rdi", r.name())?;
-    writeln!(out, "\tcall\tprintint")?;
+fn cgrestore<W: Write>(saved: &[RegisterIndex], out: &mut Assembly<W>) -> core::result::Result<(), Box<dyn std::error::Error>> {
+    if saved.len() % 2 == 1 {
+        out.instruction(Instruction::Arithmetic(Arithmetic::Add, Size::QuadWord, Operand::Immediate(8), Operand::register(Register::Rsp)))?;
+    }
+    for r in saved.iter().rev() {
+        out.instruction(Instruction::Pop(r.register()))?;
+    }
     Ok(())
 }
 
-pub fn cgglobsym<W: Write>(sym: &str, out: &mut W) -> core::result::Result<(), Box<dyn std::error::Error>> {
-    writeln!(out, "\t.comm\t{},8,8", sym)?;
+// Stores the integer readint read at the pointer in `r` only when scanf matched one
+pub fn cgreadint<W: Write>(target: Target, r: RegisterIndex, ty: &Type, registers: &mut Registers, labels: &mut Labels, out: &mut Assembly<W>) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+    let saved = cgsave(registers, out)?;
+    out.instruction(Instruction::Call(Operand::Symbol(target.runtime("readint"))))?;
+    cgrestore(&saved, out)?;
+
+    let skip = labels.allocate_label();
+    let size = match ty.size() {
+        1 => Size::Byte,
+        4 => Size::DoubleWord,
+        _ => Size::QuadWord,
+    };
+    out.instruction(Instruction::Arithmetic(Arithmetic::Compare, Size::QuadWord, Operand::Immediate(1), Operand::register(Register::Rax)))?;
+    out.instruction(Instruction::JumpIf(Condition::NotEqual, Operand::Symbol(skip.to_string())))?;
+    out.instruction(Instruction::Move(size, Operand::Register(Register::Rdx, size), pointed_to_by(r)))?;
+    cglabel(skip, out)?;
+    registers.free_register(r);
+
+    let status = registers.allocate_register()?;
+    out.instruction(Instruction::Move(Size::QuadWord, Operand::register(Register::Rax), register(status)))?;
+    Ok(status)
+}
+
+// Reads a line into the buffer pointed to by `r1` of the size in `r2`, giving its length
+pub fn cgreadline<W: Write>(target: Target, r1: RegisterIndex, r2: RegisterIndex, registers: &mut Registers, out: &mut Assembly<W>) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+    out.instruction(Instruction::Move(Size::QuadWord, register(r1), Operand::register(Register::Rdi)))?;
+    out.instruction(Instruction::Move(Size::QuadWord, register(r2), Operand::register(Register::Rsi)))?;
+    registers.free_register(r1);
+    registers.free_register(r2);
+
+    let saved = cgsave(registers, out)?;
+    out.instruction(Instruction::Call(Operand::Symbol(target.runtime("readline"))))?;
+    cgrestore(&saved, out)?;
+
+    let length = registers.allocate_register()?;
+    out.instruction(Instruction::Move(Size::QuadWord, Operand::register(Register::Rax), register(length)))?;
+    Ok(length)
+}
+
+// Ends the program through the C library so buffered output is written out first
+pub fn cgexit<W: Write>(target: Target, r: RegisterIndex, registers: &mut Registers, out: &mut Assembly<W>) -> core::result::Result<(), Box<dyn std::error::Error>> {
+    out.instruction(Instruction::Move(Size::QuadWord, register(r), Operand::register(Register::Rdi)))?;
+    registers.free_register(r);
+    // exit does not return so nothing is restored, saving only keeps the stack aligned
+    cgsave(registers, out)?;
+    out.instruction(Instruction::Call(Operand::Symbol(target.function("exit"))))?;
+    Ok(())
+}
+
+// Returns from main with the value of the register as the exit status
+pub fn cgreturn<W: Write>(r: RegisterIndex, registers: &mut Registers, out: &mut Assembly<W>) -> core::result::Result<(), Box<dyn std::error::Error>> {
+    out.instruction(Instruction::Move(Size::QuadWord, register(r), Operand::register(Register::Rax)))?;
+    out.instruction(Instruction::Leave)?;
+    out.instruction(Instruction::Return)?;
+    registers.free_register(r);
     Ok(())
 }
 
+// Loads the address of a string from the string pool
+pub fn cgloadstring<W: Write>(string: StringIndex, registers: &mut Registers, out: &mut Assembly<W>) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+    let r = registers.allocate_register()?;
+    out.instruction(Instruction::LoadAddress(Address::Relative(string.to_string()), r.register()))?;
+    Ok(r)
+}
+
+pub fn cgprintuint<W: Write>(target: Target, r: RegisterIndex, out: &mut Assembly<W>) -> core::result::Result<(), Box<dyn std::error::Error>> {
+    out.line(Line::Compact(Instruction::Move(Size::QuadWord, register(r), Operand::register(Register::Rdi))))?;
+    out.instruction(Instruction::Call(Operand::Symbol(target.runtime("printuint"))))?;
+    Ok(())
+}
+
+pub fn cgglobsym<W: Write>(target: Target, sym: &str, ty: &Type, out: &mut Assembly<W>) -> core::result::Result<(), Box<dyn std::error::Error>> {
+    let sym = out.syntax().symbol(sym);
+    out.line(Line::Directive(format!(".comm\t{},{},{}", sym, ty.size(), target.common_alignment(ty.alignment()))))
+}
+
 // Similarly, we need a function to save a register into a variable:
-pub fn cgstorglob<W: Write>(sym: &str, r: RegisterIndex, out: &mut W) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
-    writeln!(out, "\tmovq\t{}, {}(%rip)\n", r.name(), sym)?;
-    return Ok(r);
+pub fn cgstorglob<W: Write>(sym: &str, r: RegisterIndex, ty: &Type, out: &mut Assembly<W>) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+    cgstorto(Address::Relative(out.syntax().symbol(sym)), r, ty, out)
+}
+
+// Variables in the stack frame are found at an offset from %rbp
+pub fn cgstorlocal<W: Write>(offset: i32, r: RegisterIndex, ty: &Type, out: &mut Assembly<W>) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+    cgstorto(Address::offset(Register::Rbp, offset), r, ty, out)
+}
+
+fn cgstorto<W: Write>(address: Address, r: RegisterIndex, ty: &Type, out: &mut Assembly<W>) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+    let size = Size::of(ty.size())?;
+    out.instruction(Instruction::Move(size, register_for_size(r, size), Operand::Memory(address)))?;
+    out.line(Line::Blank)?;
+    Ok(r)
 }
 
 // You would have noticed that I changed the name of the old cgload() function to cgloadint().
 // This is more specific. We now have a function to load the value out of a global variable (in cg.c):
-pub fn cgloadglob<W: Write>(sym: &str, registers: &mut Registers, mut out: W) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+pub fn cgloadglob<W: Write>(sym: &str, ty: &Type, registers: &mut Registers, out: &mut Assembly<W>) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+    cgloadfrom(Operand::Memory(Address::Relative(out.syntax().symbol(sym))), ty, registers, out)
+}
+
+pub fn cgloadlocal<W: Write>(offset: i32, ty: &Type, registers: &mut Registers, out: &mut Assembly<W>) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+    cgloadfrom(Operand::Memory(Address::offset(Register::Rbp, offset)), ty, registers, out)
+}
+
+// Values narrower than a register are sign or zero extended so that every
+// register holds the full 64 bit value of whatever it was loaded from
+fn cgloadinto(source: Operand, r: RegisterIndex, ty: &Type) -> Instruction {
+    match (ty.size(), ty.is_signed()) {
+        (1, true) => Instruction::SignExtend(Size::Byte, Size::QuadWord, source, register(r)),
+        (1, false) => Instruction::ZeroExtend(Size::Byte, Size::QuadWord, source, register(r)),
+        (4, true) => Instruction::SignExtend(Size::DoubleWord, Size::QuadWord, source, register(r)),
+        // Writing the low 32 bits of a register clears the upper 32
+        (4, false) => Instruction::Move(Size::DoubleWord, source, register_for_size(r, Size::DoubleWord)),
+        _ => Instruction::Move(Size::QuadWord, source, register(r)),
+    }
+}
+
+fn cgloadfrom<W: Write>(source: Operand, ty: &Type, registers: &mut Registers, out: &mut Assembly<W>) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
     // Get a new register
-    let r = registers.allocate_register();
+    let r = registers.allocate_register()?;
 
     // Print out the code to initialise it
-    writeln!(out, "\tmovq\t{}(%rip), {}\n", sym, r.name())?;
-    return Ok(r);
+    out.instruction(cgloadinto(source, r, ty))?;
+    out.line(Line::Blank)?;
+    Ok(r)
 }
 
-pub fn cgcompare<W: Write>(how: &str, r1: RegisterIndex, r2: RegisterIndex, registers: &mut Registers, mut out: W) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
-    writeln!(out, "\tcmpq\t{}, {}", r2, r1)?;
-    writeln!(out, "\t{}\t{}b", how, r2)?;
-    writeln!(out, "\tandq\t$255,{}", r2)?;
+pub fn cgaddress<W: Write>(sym: &str, registers: &mut Registers, out: &mut Assembly<W>) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+    let r = registers.allocate_register()?;
+    out.instruction(Instruction::LoadAddress(Address::Relative(out.syntax().symbol(sym)), r.register()))?;
+    Ok(r)
+}
+
+pub fn cgaddresslocal<W: Write>(offset: i32, registers: &mut Registers, out: &mut Assembly<W>) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+    let r = registers.allocate_register()?;
+    out.instruction(Instruction::LoadAddress(Address::offset(Register::Rbp, offset), r.register()))?;
+    Ok(r)
+}
+
+// Replace the address held in a register with the value it points at
+pub fn cgloadderef<W: Write>(r: RegisterIndex, ty: &Type, out: &mut Assembly<W>) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+    out.instruction(cgloadinto(pointed_to_by(r), r, ty))?;
+    Ok(r)
+}
+
+pub fn cgstorderef<W: Write>(r1: RegisterIndex, r2: RegisterIndex, ty: &Type, registers: &mut Registers, out: &mut Assembly<W>) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+    let size = Size::of(ty.size())?;
+    out.instruction(Instruction::Move(size, register_for_size(r1, size), pointed_to_by(r2)))?;
+    registers.free_register(r2);
+    Ok(r1)
+}
+
+// Extend the low bytes of a register holding a value of the given type to the full
+// register, discarding anything above them left by a wider computation
+pub fn cgextend<W: Write>(r: RegisterIndex, ty: &Type, out: &mut Assembly<W>) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+    if let 1 | 4 = ty.size() {
+        out.instruction(cgloadinto(register_for_size(r, Size::of(ty.size())?), r, ty))?;
+    }
+    Ok(r)
+}
+
+pub fn cgshl<W: Write>(r1: RegisterIndex, r2: RegisterIndex, registers: &mut Registers, out: &mut Assembly<W>) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+    out.instruction(Instruction::Move(Size::Byte, register_for_size(r2, Size::Byte), Operand::Register(Register::Rcx, Size::Byte)))?;
+    out.instruction(Instruction::Shift(Shift::Left, Size::QuadWord, register(r1)))?;
+    registers.free_register(r2);
+    Ok(r1)
+}
+
+pub fn cgshr<W: Write>(r1: RegisterIndex, r2: RegisterIndex, ty: &Type, registers: &mut Registers, out: &mut Assembly<W>) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+    let shift = if ty.is_signed() { Shift::RightArithmetic } else { Shift::RightLogical };
+    out.instruction(Instruction::Move(Size::Byte, register_for_size(r2, Size::Byte), Operand::Register(Register::Rcx, Size::Byte)))?;
+    out.instruction(Instruction::Shift(shift, Size::QuadWord, register(r1)))?;
+    registers.free_register(r2);
+    Ok(r1)
+}
+
+// Sets `r2` to 1 when `r1` compared with `r2` meets the condition and to 0 when it does not
+pub fn cgcompare<W: Write>(condition: Condition, r1: RegisterIndex, r2: RegisterIndex, registers: &mut Registers, out: &mut Assembly<W>) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+    out.instruction(Instruction::Arithmetic(Arithmetic::Compare, Size::QuadWord, register(r2), register(r1)))?;
+    out.instruction(Instruction::Set(condition, register_for_size(r2, Size::Byte)))?;
+    out.line(Line::Compact(Instruction::Arithmetic(Arithmetic::And, Size::QuadWord, Operand::Immediate(255), register(r2))))?;
     registers.free_register(r1);
 
-    return Ok(r2);
+    Ok(r2)
+}
+
+pub fn cgequal<W: Write>(r1: RegisterIndex, r2: RegisterIndex, registers: &mut Registers, out: &mut Assembly<W>) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+    cgcompare(Condition::Equal, r1, r2, registers, out)
+}
+
+pub fn cgnotequal<W: Write>(r1: RegisterIndex, r2: RegisterIndex, registers: &mut Registers, out: &mut Assembly<W>) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+    cgcompare(Condition::NotEqual, r1, r2, registers, out)
+}
+
+// The ordered comparisons use the below/above condition codes when the
+// operands are unsigned and less/greater when they are signed
+pub fn cglessthan<W: Write>(r1: RegisterIndex, r2: RegisterIndex, ty: &Type, registers: &mut Registers, out: &mut Assembly<W>) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+    cgcompare(if ty.is_signed() { Condition::Less } else { Condition::Below }, r1, r2, registers, out)
 }
 
-pub fn cgequal<W: Write>(r1: RegisterIndex, r2: RegisterIndex, registers: &mut Registers, out: W) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
-    return cgcompare("sete", r1, r2, registers, out);
+pub fn cggreaterthan<W: Write>(r1: RegisterIndex, r2: RegisterIndex, ty: &Type, registers: &mut Registers, out: &mut Assembly<W>) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+    cgcompare(if ty.is_signed() { Condition::Greater } else { Condition::Above }, r1, r2, registers, out)
 }
 
-pub fn cgnotequal<W: Write>(r1: RegisterIndex, r2: RegisterIndex, registers: &mut Registers, out: W) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
-    return cgcompare("setne", r1, r2, registers, out);
+pub fn cglessequal<W: Write>(r1: RegisterIndex, r2: RegisterIndex, ty: &Type, registers: &mut Registers, out: &mut Assembly<W>) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+    cgcompare(if ty.is_signed() { Condition::LessEqual } else { Condition::BelowEqual }, r1, r2, registers, out)
 }
 
-pub fn cglessthan<W: Write>(r1: RegisterIndex, r2: RegisterIndex, registers: &mut Registers, out: W) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
-    return cgcompare("setl", r1, r2, registers, out);
+pub fn cggreaterequal<W: Write>(r1: RegisterIndex, r2: RegisterIndex, ty: &Type, registers: &mut Registers, out: &mut Assembly<W>) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+    cgcompare(if ty.is_signed() { Condition::GreaterEqual } else { Condition::AboveEqual }, r1, r2, registers, out)
 }
 
-pub fn cggreaterthan<W: Write>(r1: RegisterIndex, r2: RegisterIndex, registers: &mut Registers, out: W) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
-    return cgcompare("setg", r1, r2, registers, out);
+pub fn cglabel<W: Write>(label: LabelIndex, out: &mut Assembly<W>) -> core::result::Result<(), Box<dyn std::error::Error>> {
+    out.line(Line::Label(label.to_string()))
+}
+
+pub fn cgjump<W: Write>(label: LabelIndex, out: &mut Assembly<W>) -> core::result::Result<(), Box<dyn std::error::Error>> {
+    out.instruction(Instruction::Jump(Operand::Symbol(label.to_string())))
+}
+
+// Jump to the label when the register holds the constant. Constants that do not fit in a sign
+// extended 32 bit immediate are loaded into %rax to compare against.
+pub fn cgjumpifequal<W: Write>(r: RegisterIndex, value: u64, label: LabelIndex, out: &mut Assembly<W>) -> core::result::Result<(), Box<dyn std::error::Error>> {
+    let value = value as i64;
+    if value >= i32::MIN as i64 && value <= i32::MAX as i64 {
+        out.instruction(Instruction::Arithmetic(Arithmetic::Compare, Size::QuadWord, Operand::Immediate(value), register(r)))?;
+    } else {
+        out.instruction(Instruction::Move(Size::QuadWord, Operand::Immediate(value), Operand::register(Register::Rax)))?;
+        out.instruction(Instruction::Arithmetic(Arithmetic::Compare, Size::QuadWord, Operand::register(Register::Rax), register(r)))?;
+    }
+    out.instruction(Instruction::JumpIf(Condition::Equal, Operand::Symbol(label.to_string())))?;
+    Ok(())
+}
+
+// Jump to the label found at index `r - low` of a table in .rodata, or to `default` when that is
+// past the end of the table. The table holds offsets from itself rather than addresses so that
+// it needs no relocations.
+pub fn cgjumptable<W: Write>(target: Target, r: RegisterIndex, low: u64, targets: &[LabelIndex], default: LabelIndex, labels: &mut Labels, out: &mut Assembly<W>) -> core::result::Result<(), Box<dyn std::error::Error>> {
+    let table = labels.allocate_label();
+    if low != 0 {
+        out.instruction(Instruction::Move(Size::QuadWord, Operand::Immediate(low as i64), Operand::register(Register::Rax)))?;
+        out.instruction(Instruction::Arithmetic(Arithmetic::Subtract, Size::QuadWord, Operand::register(Register::Rax), register(r)))?;
+    }
+    let entry = Address::Indexed { base: Register::Rax, index: Some((r.register(), 4)), displacement: 0 };
+    out.instruction(Instruction::Arithmetic(Arithmetic::Compare, Size::QuadWord, Operand::Immediate(targets.len() as i64), register(r)))?;
+    out.instruction(Instruction::JumpIf(Condition::AboveEqual, Operand::Symbol(default.to_string())))?;
+    out.instruction(Instruction::LoadAddress(Address::Relative(table.to_string()), Register::Rax))?;
+    out.instruction(Instruction::SignExtend(Size::DoubleWord, Size::QuadWord, Operand::Memory(entry), register(r)))?;
+    out.instruction(Instruction::Arithmetic(Arithmetic::Add, Size::QuadWord, Operand::register(Register::Rax), register(r)))?;
+    out.instruction(Instruction::Jump(register(r)))?;
+    out.line(Line::Directive(format!(".section\t{}", target.read_only_section())))?;
+    out.line(Line::Directive(".p2align\t2".to_string()))?;
+    cglabel(table, out)?;
+    for target in targets {
+        out.line(Line::Directive(format!(".long\t{}-{}", target, table)))?;
+    }
+    out.line(Line::Directive(".text".to_string()))?;
+    Ok(())
+}
+
+// Jump to the label when the register holds zero, i.e. the condition was false
+pub fn cgjumpifzero<W: Write>(r: RegisterIndex, label: LabelIndex, registers: &mut Registers, out: &mut Assembly<W>) -> core::result::Result<(), Box<dyn std::error::Error>> {
+    out.instruction(Instruction::Arithmetic(Arithmetic::Compare, Size::QuadWord, Operand::Immediate(0), register(r)))?;
+    out.instruction(Instruction::JumpIf(Condition::Equal, Operand::Symbol(label.to_string())))?;
+    registers.free_register(r);
+    Ok(())
 }
 
-pub fn cglessequal<W: Write>(r1: RegisterIndex, r2: RegisterIndex, registers: &mut Registers, out: W) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
-    return cgcompare("setle", r1, r2, registers, out);
+pub fn cgmove<W: Write>(from: RegisterIndex, to: RegisterIndex, registers: &mut Registers, out: &mut Assembly<W>) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+    out.instruction(Instruction::Move(Size::QuadWord, register(from), register(to)))?;
+    registers.free_register(from);
+    Ok(to)
 }
 
-pub fn cggreaterequal<W: Write>(r1: RegisterIndex, r2: RegisterIndex, registers: &mut Registers, out: W) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
-    return cgcompare("setge", r1, r2, registers, out);
+// Select between two already evaluated values without branching: the result is
+// `when_true` unless the condition register holds zero, in which case it is `when_false`
+pub fn cgselect<W: Write>(condition: RegisterIndex, when_true: RegisterIndex, when_false: RegisterIndex, registers: &mut Registers, out: &mut Assembly<W>) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+    out.instruction(Instruction::Arithmetic(Arithmetic::Compare, Size::QuadWord, Operand::Immediate(0), register(condition)))?;
+    out.instruction(Instruction::ConditionalMove(Condition::Equal, Size::QuadWord, register(when_false), register(when_true)))?;
+    registers.free_register(condition);
+    registers.free_register(when_false);
+    Ok(when_true)
 }
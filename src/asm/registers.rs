@@ -1,34 +1,62 @@
 use std::fmt;
 
+use crate::asm::instruction::Register;
+
 #[derive(Clone, Copy, Debug)]
 pub struct RegisterIndex(pub u32);
 
 const REGISTER_COUNT: usize = 4;
 
+const X86_64_REGISTERS: [Register; REGISTER_COUNT] = [
+    Register::R8, Register::R9, Register::R10, Register::R11
+];
+
 const REGISTERS: [&str; REGISTER_COUNT] = [
     "%r8", "%r9", "%r10", "%r11"
 ];
 
+const DOUBLE_WORD_REGISTERS: [&str; REGISTER_COUNT] = [
+    "%r8d", "%r9d", "%r10d", "%r11d"
+];
+
+const BYTE_REGISTERS: [&str; REGISTER_COUNT] = [
+    "%r8b", "%r9b", "%r10b", "%r11b"
+];
+
+/// The most registers any backend hands out
+const MAX_REGISTER_COUNT: usize = 8;
+
 #[derive(Clone, Copy, Debug)]
 pub struct Registers {
-    freereg: [bool; REGISTER_COUNT]
+    freereg: [bool; MAX_REGISTER_COUNT],
+    count: usize,
 }
 
 impl Registers {
+    /// Hands out the four registers x86-64 names
     pub fn new() -> Registers {
         Registers {
-            freereg: [true; REGISTER_COUNT]
+            freereg: [true; MAX_REGISTER_COUNT],
+            count: REGISTER_COUNT,
         }
     }
 
-    pub fn allocate_register(&mut self) -> RegisterIndex {
-        for i in 0..self.freereg.len() {
+    /// Hands out the first `count` registers instead, for a backend naming more of them
+    pub fn with_count(self, count: usize) -> Registers {
+        assert!(count <= MAX_REGISTER_COUNT, "Unable to hand out {} registers", count);
+        Registers { count, ..self }
+    }
+
+    /// A free register, or an error when an expression needs more values at once than there are
+    /// registers, as nothing is spilled to memory
+    pub fn allocate_register(&mut self) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        for i in 0..self.count {
             if self.freereg[i] {
                 self.freereg[i] = false;
-                return RegisterIndex(i as u32);
+                return Ok(RegisterIndex(i as u32));
             }
         }
-        panic!("Out of registers")
+        Err(format!("Unable to compute an expression needing more than {} registers at once", self.count).into())
     }
 
     pub fn free_register(&mut self, register: RegisterIndex) {
@@ -39,17 +67,43 @@ impl Registers {
         self.freereg[register.0 as usize] = true;
     }
 
+    /// The registers currently holding values, which a call would clobber
+    pub fn allocated(&self) -> Vec<RegisterIndex> {
+        (0..self.count).filter(|i| !self.freereg[*i]).map(|i| RegisterIndex(i as u32)).collect()
+    }
+
     pub fn free_all(&mut self) {
-        for i in 0..REGISTER_COUNT {
+        for i in 0..self.count {
             self.freereg[i] = true;
         }
     }
 }
 
+impl Default for Registers {
+    fn default() -> Self {
+        Registers::new()
+    }
+}
+
 impl RegisterIndex {
+    /// The x86-64 register this index names
+    pub fn register(&self) -> Register {
+        X86_64_REGISTERS[self.0 as usize]
+    }
+
     pub fn name(&self) -> &str {
         REGISTERS[self.0 as usize]
     }
+
+    /// The name of the low `size` bytes of this register
+    pub fn name_for_size(&self, size: u32) -> &str {
+        match size {
+            1 => BYTE_REGISTERS[self.0 as usize],
+            4 => DOUBLE_WORD_REGISTERS[self.0 as usize],
+            8 => REGISTERS[self.0 as usize],
+            unhandled => panic!("No register is {} bytes wide", unhandled),
+        }
+    }
 }
 
 impl fmt::Display for RegisterIndex {
@@ -0,0 +1,522 @@
+use std::io::Write;
+
+use crate::asm::backend::{Backend, BinaryOperation, Comparison, Function};
+use crate::asm::labels::{LabelIndex, Labels};
+use crate::asm::parameter_offset;
+use crate::asm::registers::{RegisterIndex, Registers};
+use crate::asm::strings::Strings;
+use crate::asm::target::Target;
+use crate::compiler::Program;
+use crate::symbols::{Storage, Symbol};
+use crate::types::Type;
+
+// The allocator hands out four of the temporaries. t4 is kept as a scratch register for
+// addresses and t5 for constants.
+const REGISTERS: [&str; 4] = [
+    "t0", "t1", "t2", "t3"
+];
+
+fn name(r: RegisterIndex) -> &'static str {
+    REGISTERS[r.0 as usize]
+}
+
+/// The RISC-V backend, writing GNU assembler syntax for RV64IM and the standard calling convention
+pub struct RiscV64<W: Write> {
+    out: W,
+    target: Target,
+    registers: Registers,
+    labels: Labels,
+    strings: Strings,
+    bounds_checked: bool,
+    frame_size: u32,
+}
+
+impl<W: Write> RiscV64<W> {
+    fn address_of_storage(&mut self, register: &str, symbol: &Symbol) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        match symbol.storage {
+            Storage::Global => writeln!(self.out, "\tla\t{}, {}", register, symbol.name)?,
+            // The offset only fits in the instruction when it is small
+            Storage::Frame(offset) if offset >= -2048 => writeln!(self.out, "\taddi\t{}, s0, {}", register, offset)?,
+            Storage::Frame(offset) => {
+                writeln!(self.out, "\tli\t{}, {}", register, offset)?;
+                writeln!(self.out, "\tadd\t{}, s0, {}", register, register)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn load_from(&mut self, r: RegisterIndex, address: &str, ty: &Type) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        let instruction = match (ty.size(), ty.is_signed()) {
+            (1, true) => "lb",
+            (1, false) => "lbu",
+            (4, true) => "lw",
+            (4, false) => "lwu",
+            _ => "ld",
+        };
+        writeln!(self.out, "\t{}\t{}, 0({})", instruction, name(r), address)?;
+        Ok(())
+    }
+
+    fn store_to(&mut self, r: RegisterIndex, address: &str, ty: &Type) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        let instruction = match ty.size() {
+            1 => "sb",
+            4 => "sw",
+            _ => "sd",
+        };
+        writeln!(self.out, "\t{}\t{}, 0({})", instruction, name(r), address)?;
+        Ok(())
+    }
+
+    // The temporaries belong to the caller, so the ones holding values are pushed around a call.
+    // Each takes a 16 byte slot to keep the stack pointer aligned.
+    fn save(&mut self) -> core::result::Result<Vec<RegisterIndex>, Box<dyn std::error::Error>> {
+        let saved = self.registers.allocated();
+        for r in &saved {
+            writeln!(self.out, "\taddi\tsp, sp, -16")?;
+            writeln!(self.out, "\tsd\t{}, 0(sp)", name(*r))?;
+        }
+        Ok(saved)
+    }
+
+    fn restore(&mut self, saved: &[RegisterIndex]) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        for r in saved.iter().rev() {
+            writeln!(self.out, "\tld\t{}, 0(sp)", name(*r))?;
+            writeln!(self.out, "\taddi\tsp, sp, 16")?;
+        }
+        Ok(())
+    }
+
+    fn call_symbol(&mut self, symbol: &str, r: RegisterIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        writeln!(self.out, "\tmv\ta0, {}", name(r))?;
+        self.registers.free_register(r);
+        let saved = self.save()?;
+        writeln!(self.out, "\tcall\t{}", symbol)?;
+        self.restore(&saved)
+    }
+
+    fn read_int(&mut self, r: RegisterIndex, ty: &Type) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        let saved = self.save()?;
+        writeln!(self.out, "\tcall\t{}", self.target.runtime("readint"))?;
+        self.restore(&saved)?;
+
+        let skip = self.labels.allocate_label();
+        let instruction = match ty.size() {
+            1 => "sb",
+            4 => "sw",
+            _ => "sd",
+        };
+        writeln!(self.out, "\tli\tt5, 1")?;
+        writeln!(self.out, "\tbne\ta0, t5, {}", skip)?;
+        writeln!(self.out, "\t{}\ta1, 0({})", instruction, name(r))?;
+        self.label(skip)?;
+        self.registers.free_register(r);
+
+        let status = self.registers.allocate_register()?;
+        writeln!(self.out, "\tmv\t{}, a0", name(status))?;
+        Ok(status)
+    }
+
+    fn read_line(&mut self, buffer: RegisterIndex, size: RegisterIndex) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        writeln!(self.out, "\tmv\ta0, {}", name(buffer))?;
+        writeln!(self.out, "\tmv\ta1, {}", name(size))?;
+        self.registers.free_register(buffer);
+        self.registers.free_register(size);
+
+        let saved = self.save()?;
+        writeln!(self.out, "\tcall\t{}", self.target.runtime("readline"))?;
+        self.restore(&saved)?;
+
+        let length = self.registers.allocate_register()?;
+        writeln!(self.out, "\tmv\t{}, a0", name(length))?;
+        Ok(length)
+    }
+
+    // Reports an out of bounds index on the line passed in a0 and aborts
+    fn bounds_fail(&mut self) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        let message = self.strings.intern("Array index out of bounds on line %ld\n");
+        writeln!(self.out, "{}:", self.target.runtime("boundsfail"))?;
+        writeln!(self.out, "\taddi\tsp, sp, -16")?;
+        writeln!(self.out, "\tsd\tra, 8(sp)")?;
+        writeln!(self.out, "\tmv\ta1, a0")?;
+        writeln!(self.out, "\tla\ta0, {}", message)?;
+        writeln!(self.out, "\tcall\t{}", self.target.function("printf"))?;
+        writeln!(self.out, "\tli\ta0, 0")?;
+        writeln!(self.out, "\tcall\t{}", self.target.function("fflush"))?;
+        writeln!(self.out, "\tcall\t{}", self.target.function("abort"))?;
+        Ok(())
+    }
+
+    // Restores the frame of `main` and returns from it with the exit status already in a0
+    fn leave(&mut self) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        writeln!(self.out, "\tmv\tsp, s0")?;
+        writeln!(self.out, "\tld\tra, 8(sp)")?;
+        writeln!(self.out, "\tld\ts0, 0(sp)")?;
+        writeln!(self.out, "\taddi\tsp, sp, 16")?;
+        writeln!(self.out, "\tret")?;
+        Ok(())
+    }
+}
+
+impl<W: Write> Backend<W> for RiscV64<W> {
+    type Register = RegisterIndex;
+
+    fn new(target: Target, out: W) -> Self {
+        RiscV64 {
+            out,
+            target,
+            registers: Registers::new(),
+            labels: Labels::for_target(target),
+            strings: Strings::new(),
+            bounds_checked: false,
+            frame_size: 0,
+        }
+    }
+
+    fn survey(&mut self, program: &Program) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        self.frame_size = program.symbols.frame_size();
+        Ok(())
+    }
+
+    fn prologue(&mut self) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        let signed = self.strings.intern("%ld\n");
+        let unsigned = self.strings.intern("%lu\n");
+        let read = self.strings.intern("%ld");
+        let printf = self.target.function("printf");
+
+        writeln!(self.out, "\t# Start of preamble")?;
+        writeln!(self.out, "\t.text")?;
+        for (routine, format) in &[("printint", signed), ("printuint", unsigned)] {
+            writeln!(self.out, "{}:", self.target.runtime(routine))?;
+            writeln!(self.out, "\taddi\tsp, sp, -16")?;
+            writeln!(self.out, "\tsd\tra, 8(sp)")?;
+            writeln!(self.out, "\tmv\ta1, a0")?;
+            writeln!(self.out, "\tla\ta0, {}", format)?;
+            writeln!(self.out, "\tcall\t{}", printf)?;
+            writeln!(self.out, "\tld\tra, 8(sp)")?;
+            writeln!(self.out, "\taddi\tsp, sp, 16")?;
+            writeln!(self.out, "\tret")?;
+        }
+        writeln!(self.out, "{}:", self.target.runtime("printstr"))?;
+        writeln!(self.out, "\taddi\tsp, sp, -16")?;
+        writeln!(self.out, "\tsd\tra, 8(sp)")?;
+        writeln!(self.out, "\tcall\t{}", self.target.function("puts"))?;
+        writeln!(self.out, "\tld\tra, 8(sp)")?;
+        writeln!(self.out, "\taddi\tsp, sp, 16")?;
+        writeln!(self.out, "\tret")?;
+        // readint gives the scanf status in a0 and the value it read in a1
+        writeln!(self.out, "{}:", self.target.runtime("readint"))?;
+        writeln!(self.out, "\taddi\tsp, sp, -16")?;
+        writeln!(self.out, "\tsd\tra, 8(sp)")?;
+        writeln!(self.out, "\tmv\ta1, sp")?;
+        writeln!(self.out, "\tla\ta0, {}", read)?;
+        writeln!(self.out, "\tcall\t{}", self.target.function("scanf"))?;
+        writeln!(self.out, "\tsext.w\ta0, a0")?;
+        writeln!(self.out, "\tld\ta1, 0(sp)")?;
+        writeln!(self.out, "\tld\tra, 8(sp)")?;
+        writeln!(self.out, "\taddi\tsp, sp, 16")?;
+        writeln!(self.out, "\tret")?;
+        // readline takes the buffer in a0 and its size in a1, and gives the length in a0
+        writeln!(self.out, "{}:", self.target.runtime("readline"))?;
+        writeln!(self.out, "\taddi\tsp, sp, -32")?;
+        writeln!(self.out, "\tsd\tra, 24(sp)")?;
+        writeln!(self.out, "\tsd\ts1, 16(sp)")?;
+        writeln!(self.out, "\tsd\ts2, 8(sp)")?;
+        writeln!(self.out, "\tsd\ts3, 0(sp)")?;
+        writeln!(self.out, "\tmv\ts1, a0")?;
+        writeln!(self.out, "\taddi\ts2, a1, -1")?;
+        writeln!(self.out, "\tli\ts3, 0")?;
+        writeln!(self.out, "{}:", self.target.runtime("readline_next"))?;
+        writeln!(self.out, "\tbge\ts3, s2, {}", self.target.runtime("readline_done"))?;
+        writeln!(self.out, "\tcall\t{}", self.target.function("getchar"))?;
+        writeln!(self.out, "\tli\tt5, -1")?;
+        writeln!(self.out, "\tbeq\ta0, t5, {}", self.target.runtime("readline_end"))?;
+        writeln!(self.out, "\tli\tt5, 10")?;
+        writeln!(self.out, "\tbeq\ta0, t5, {}", self.target.runtime("readline_done"))?;
+        writeln!(self.out, "\tadd\tt4, s1, s3")?;
+        writeln!(self.out, "\tsb\ta0, 0(t4)")?;
+        writeln!(self.out, "\taddi\ts3, s3, 1")?;
+        writeln!(self.out, "\tj\t{}", self.target.runtime("readline_next"))?;
+        writeln!(self.out, "{}:", self.target.runtime("readline_end"))?;
+        writeln!(self.out, "\tadd\tt4, s1, s3")?;
+        writeln!(self.out, "\tsb\tzero, 0(t4)")?;
+        writeln!(self.out, "\tli\ta0, -1")?;
+        writeln!(self.out, "\tbeqz\ts3, {}", self.target.runtime("readline_return"))?;
+        writeln!(self.out, "{}:", self.target.runtime("readline_done"))?;
+        writeln!(self.out, "\tadd\tt4, s1, s3")?;
+        writeln!(self.out, "\tsb\tzero, 0(t4)")?;
+        writeln!(self.out, "\tmv\ta0, s3")?;
+        writeln!(self.out, "{}:", self.target.runtime("readline_return"))?;
+        writeln!(self.out, "\tld\tra, 24(sp)")?;
+        writeln!(self.out, "\tld\ts1, 16(sp)")?;
+        writeln!(self.out, "\tld\ts2, 8(sp)")?;
+        writeln!(self.out, "\tld\ts3, 0(sp)")?;
+        writeln!(self.out, "\taddi\tsp, sp, 32")?;
+        writeln!(self.out, "\tret")?;
+        writeln!(self.out, "\t.globl\t{}", self.target.symbol("main"))?;
+        writeln!(self.out, "{}:", self.target.symbol("main"))?;
+        writeln!(self.out, "\taddi\tsp, sp, -16")?;
+        writeln!(self.out, "\tsd\tra, 8(sp)")?;
+        writeln!(self.out, "\tsd\ts0, 0(sp)")?;
+        writeln!(self.out, "\tmv\ts0, sp")?;
+        // addi only takes a 12 bit signed constant, so a larger frame is sized through t5
+        if self.frame_size <= 2048 {
+            writeln!(self.out, "\taddi\tsp, sp, -{}", self.frame_size)?;
+        } else {
+            writeln!(self.out, "\tli\tt5, {}", self.frame_size)?;
+            writeln!(self.out, "\tsub\tsp, sp, t5")?;
+        }
+        writeln!(self.out, "\tsd\ta0, {}(s0)", parameter_offset(0))?;
+        writeln!(self.out, "\tsd\ta1, {}(s0)", parameter_offset(1))?;
+        writeln!(self.out, "\t# End of preamble")?;
+        Ok(())
+    }
+
+    fn epilogue(&mut self) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        writeln!(self.out, "\tli\ta0, 0")?;
+        self.leave()?;
+        if self.bounds_checked {
+            self.bounds_fail()?;
+        }
+        self.strings.write(self.target, self.out.by_ref())?;
+        writeln!(self.out, "\t.section\t.note.GNU-stack,\"\",@progbits")?;
+        Ok(())
+    }
+
+    fn comment(&mut self, comment: &str) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        writeln!(self.out, "\t# {}", comment)?;
+        Ok(())
+    }
+
+    fn load(&mut self, value: u64, _ty: &Type) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        let r = self.registers.allocate_register()?;
+        writeln!(self.out, "\tli\t{}, {}", name(r), value as i64)?;
+        Ok(r)
+    }
+
+    fn load_string(&mut self, string: &str) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        let string = self.strings.intern(string);
+        let r = self.registers.allocate_register()?;
+        writeln!(self.out, "\tla\t{}, {}", name(r), string)?;
+        Ok(r)
+    }
+
+    fn binary(&mut self, operation: BinaryOperation, r1: RegisterIndex, r2: RegisterIndex, ty: &Type) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        let instruction = match operation {
+            BinaryOperation::Add => "add",
+            BinaryOperation::Subtract => "sub",
+            BinaryOperation::Multiply => "mul",
+            BinaryOperation::Divide => if ty.is_signed() { "div" } else { "divu" },
+            BinaryOperation::LeftShift => "sll",
+            BinaryOperation::RightShift => if ty.is_signed() { "sra" } else { "srl" },
+        };
+        writeln!(self.out, "\t{}\t{}, {}, {}", instruction, name(r1), name(r1), name(r2))?;
+        self.registers.free_register(r2);
+        Ok(r1)
+    }
+
+    // addi takes a 12 bit signed constant, and multiplying by a power of two is a shift
+    fn binary_constant(&mut self, operation: BinaryOperation, r: RegisterIndex, value: u64, ty: &Type) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        let value = value as i64;
+        match operation {
+            BinaryOperation::Add if (-2048..2048).contains(&value) => writeln!(self.out, "\taddi\t{}, {}, {}", name(r), name(r), value)?,
+            BinaryOperation::Subtract if (-2047..=2048).contains(&value) => writeln!(self.out, "\taddi\t{}, {}, {}", name(r), name(r), -value)?,
+            BinaryOperation::Multiply if value > 0 && (value as u64).is_power_of_two() =>
+                writeln!(self.out, "\tslli\t{}, {}, {}", name(r), name(r), value.trailing_zeros())?,
+            _ => {
+                let constant = self.load(value as u64, ty)?;
+                return self.binary(operation, r, constant, ty);
+            }
+        }
+        Ok(r)
+    }
+
+    // There is only a set if less than, so the other orderings swap its operands or invert
+    // its result. Equality tests whether the difference is zero.
+    fn compare(&mut self, comparison: Comparison, r1: RegisterIndex, r2: RegisterIndex, ty: &Type) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        let less = if ty.is_signed() { "slt" } else { "sltu" };
+        let (a, b) = (name(r1), name(r2));
+        match comparison {
+            Comparison::Equal => {
+                writeln!(self.out, "\tsub\t{}, {}, {}", b, a, b)?;
+                writeln!(self.out, "\tseqz\t{}, {}", b, b)?;
+            }
+            Comparison::NotEqual => {
+                writeln!(self.out, "\tsub\t{}, {}, {}", b, a, b)?;
+                writeln!(self.out, "\tsnez\t{}, {}", b, b)?;
+            }
+            Comparison::LessThan => writeln!(self.out, "\t{}\t{}, {}, {}", less, b, a, b)?,
+            Comparison::GreaterThan => writeln!(self.out, "\t{}\t{}, {}, {}", less, b, b, a)?,
+            Comparison::LessThanEqual => {
+                writeln!(self.out, "\t{}\t{}, {}, {}", less, b, b, a)?;
+                writeln!(self.out, "\txori\t{}, {}, 1", b, b)?;
+            }
+            Comparison::GreaterThanEqual => {
+                writeln!(self.out, "\t{}\t{}, {}, {}", less, b, a, b)?;
+                writeln!(self.out, "\txori\t{}, {}, 1", b, b)?;
+            }
+        }
+        self.registers.free_register(r1);
+        Ok(r2)
+    }
+
+    fn extend(&mut self, r: RegisterIndex, ty: &Type) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        let r_name = name(r);
+        match (ty.size(), ty.is_signed()) {
+            (1, true) => {
+                writeln!(self.out, "\tslli\t{}, {}, 56", r_name, r_name)?;
+                writeln!(self.out, "\tsrai\t{}, {}, 56", r_name, r_name)?;
+            }
+            (1, false) => writeln!(self.out, "\tandi\t{}, {}, 255", r_name, r_name)?,
+            (4, true) => writeln!(self.out, "\tsext.w\t{}, {}", r_name, r_name)?,
+            (4, false) => {
+                writeln!(self.out, "\tslli\t{}, {}, 32", r_name, r_name)?;
+                writeln!(self.out, "\tsrli\t{}, {}, 32", r_name, r_name)?;
+            }
+            _ => {}
+        }
+        Ok(r)
+    }
+
+    fn declare_global(&mut self, symbol: &Symbol) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        writeln!(self.out, "\t.comm\t{},{},{}", symbol.name, symbol.ty.size(), self.target.common_alignment(symbol.ty.alignment()))?;
+        Ok(())
+    }
+
+    fn load_symbol(&mut self, symbol: &Symbol, ty: &Type) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        let r = self.registers.allocate_register()?;
+        self.address_of_storage("t4", symbol)?;
+        self.load_from(r, "t4", ty)?;
+        Ok(r)
+    }
+
+    fn store_symbol(&mut self, symbol: &Symbol, r: RegisterIndex, ty: &Type) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        self.address_of_storage("t4", symbol)?;
+        self.store_to(r, "t4", ty)?;
+        Ok(r)
+    }
+
+    fn address_of(&mut self, symbol: &Symbol, _ty: &Type) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        let r = self.registers.allocate_register()?;
+        self.address_of_storage(name(r), symbol)?;
+        Ok(r)
+    }
+
+    fn load_dereference(&mut self, r: RegisterIndex, ty: &Type) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        self.load_from(r, name(r), ty)?;
+        Ok(r)
+    }
+
+    fn store_dereference(&mut self, value: RegisterIndex, pointer: RegisterIndex, ty: &Type) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        self.store_to(value, name(pointer), ty)?;
+        self.registers.free_register(pointer);
+        Ok(value)
+    }
+
+    fn bounds_check(&mut self, r: RegisterIndex, length: u32, line: u32) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        self.bounds_checked = true;
+        let ok = self.labels.allocate_label();
+        writeln!(self.out, "\tli\tt5, {}", length)?;
+        writeln!(self.out, "\tbltu\t{}, t5, {}", name(r), ok)?;
+        writeln!(self.out, "\tli\ta0, {}", line)?;
+        writeln!(self.out, "\tcall\t{}", self.target.runtime("boundsfail"))?;
+        self.label(ok)?;
+        Ok(r)
+    }
+
+    fn call(&mut self, function: Function, arguments: &[RegisterIndex]) -> core::result::Result<Option<RegisterIndex>, Box<dyn std::error::Error>> {
+        match (function, arguments) {
+            (Function::PrintInt, [r]) => self.call_symbol(&self.target.runtime("printint"), *r).map(|_| None),
+            (Function::PrintUnsignedInt, [r]) => self.call_symbol(&self.target.runtime("printuint"), *r).map(|_| None),
+            (Function::PrintString, [r]) => self.call_symbol(&self.target.runtime("printstr"), *r).map(|_| None),
+            (Function::ReadInt(ty), [r]) => self.read_int(*r, &ty).map(Some),
+            (Function::ReadLine, [buffer, size]) => self.read_line(*buffer, *size).map(Some),
+            (Function::Exit, [r]) => self.call_symbol(&self.target.function("exit"), *r).map(|_| None),
+            (function, arguments) => panic!("Unable to call {:?} with {} arguments", function, arguments.len()),
+        }
+    }
+
+    fn return_value(&mut self, r: RegisterIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        writeln!(self.out, "\tmv\ta0, {}", name(r))?;
+        self.leave()?;
+        self.registers.free_register(r);
+        Ok(())
+    }
+
+    fn allocate_label(&mut self) -> LabelIndex {
+        self.labels.allocate_label()
+    }
+
+    fn label(&mut self, label: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        writeln!(self.out, "{}:", label)?;
+        Ok(())
+    }
+
+    fn jump(&mut self, label: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        writeln!(self.out, "\tj\t{}", label)?;
+        Ok(())
+    }
+
+    fn jump_if_zero(&mut self, r: RegisterIndex, label: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        writeln!(self.out, "\tbeqz\t{}, {}", name(r), label)?;
+        self.registers.free_register(r);
+        Ok(())
+    }
+
+    fn jump_if_equal(&mut self, r: RegisterIndex, value: u64, label: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        writeln!(self.out, "\tli\tt5, {}", value as i64)?;
+        writeln!(self.out, "\tbeq\t{}, t5, {}", name(r), label)?;
+        Ok(())
+    }
+
+    // The table holds offsets from itself rather than addresses so that it works wherever the program
+    // is loaded, which the linker resolves from a pair of relocations for each entry
+    fn jump_table(&mut self, r: RegisterIndex, low: u64, targets: &[LabelIndex], default: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        let table = self.labels.allocate_label();
+        let r = name(r);
+        if low != 0 {
+            writeln!(self.out, "\tli\tt5, {}", low as i64)?;
+            writeln!(self.out, "\tsub\t{}, {}, t5", r, r)?;
+        }
+        writeln!(self.out, "\tli\tt5, {}", targets.len())?;
+        writeln!(self.out, "\tbgeu\t{}, t5, {}", r, default)?;
+        writeln!(self.out, "\tla\tt4, {}", table)?;
+        writeln!(self.out, "\tslli\t{}, {}, 2", r, r)?;
+        writeln!(self.out, "\tadd\t{}, t4, {}", r, r)?;
+        writeln!(self.out, "\tlw\t{}, 0({})", r, r)?;
+        writeln!(self.out, "\tadd\tt4, t4, {}", r)?;
+        writeln!(self.out, "\tjr\tt4")?;
+        writeln!(self.out, "\t.section\t{}", self.target.read_only_section())?;
+        writeln!(self.out, "\t.p2align\t2")?;
+        writeln!(self.out, "{}:", table)?;
+        for target in targets {
+            writeln!(self.out, "\t.word\t{}-{}", target, table)?;
+        }
+        writeln!(self.out, "\t.text")?;
+        Ok(())
+    }
+
+    fn move_register(&mut self, from: RegisterIndex, to: RegisterIndex) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        writeln!(self.out, "\tmv\t{}, {}", name(to), name(from))?;
+        self.registers.free_register(from);
+        Ok(to)
+    }
+
+    // RV64IM has no conditional move so the false value is copied over when the condition is zero
+    fn select(&mut self, condition: RegisterIndex, when_true: RegisterIndex, when_false: RegisterIndex) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        let keep = self.labels.allocate_label();
+        writeln!(self.out, "\tbnez\t{}, {}", name(condition), keep)?;
+        writeln!(self.out, "\tmv\t{}, {}", name(when_true), name(when_false))?;
+        self.label(keep)?;
+        self.registers.free_register(condition);
+        self.registers.free_register(when_false);
+        Ok(when_true)
+    }
+
+    fn free_register(&mut self, r: RegisterIndex) {
+        self.registers.free_register(r);
+    }
+
+    fn free_all_registers(&mut self) {
+        self.registers.free_all();
+    }
+}
@@ -0,0 +1,81 @@
+use std::fmt;
+use std::io::Write;
+
+use crate::asm::target::Target;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StringIndex(pub u32);
+
+/// Every string in the program, each stored once however many times it is used. The strings are
+/// written out together at the end of the assembly, once all of them are known.
+#[derive(Clone, Debug)]
+pub struct Strings {
+    strings: Vec<String>,
+}
+
+impl Strings {
+    pub fn new() -> Strings {
+        Strings {
+            strings: Vec::new()
+        }
+    }
+
+    pub fn intern(&mut self, string: &str) -> StringIndex {
+        if let Some(index) = self.strings.iter().position(|s| s == string) {
+            return StringIndex(index as u32);
+        }
+        self.strings.push(string.to_string());
+        StringIndex(self.strings.len() as u32 - 1)
+    }
+
+    /// Every string with the index naming it
+    pub fn iter(&self) -> impl Iterator<Item = (StringIndex, &str)> {
+        self.strings.iter().enumerate().map(|(index, string)| (StringIndex(index as u32), string.as_str()))
+    }
+
+    pub fn write<W: Write>(&self, target: Target, mut out: W) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        if self.strings.is_empty() {
+            return Ok(());
+        }
+        writeln!(out, "\t.section\t{}", target.read_only_section())?;
+        for (index, string) in self.strings.iter().enumerate() {
+            writeln!(out, "{}:", StringIndex(index as u32))?;
+            writeln!(out, "\t.string\t\"{}\"", escape(string))?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for Strings {
+    fn default() -> Self {
+        Strings::new()
+    }
+}
+
+impl StringIndex {
+    pub fn name(&self) -> String {
+        format!(".LC{}", self.0)
+    }
+}
+
+impl fmt::Display for StringIndex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+// Writes the string the way the assembler reads it back, with anything unprintable as an octal escape
+fn escape(string: &str) -> String {
+    let mut escaped = String::new();
+    for byte in string.bytes() {
+        match byte {
+            b'"' => escaped.push_str("\\\""),
+            b'\\' => escaped.push_str("\\\\"),
+            b'\n' => escaped.push_str("\\n"),
+            b'\t' => escaped.push_str("\\t"),
+            b' '..=b'~' => escaped.push(byte as char),
+            other => escaped.push_str(&format!("\\{:03o}", other)),
+        }
+    }
+    escaped
+}
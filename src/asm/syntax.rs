@@ -0,0 +1,363 @@
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+
+use log::debug;
+
+use crate::asm::elf;
+use crate::asm::instruction::{Address, Arithmetic, Function, Instruction, Line, Operand, Register, Shift, Size};
+use crate::asm::strings::Strings;
+
+/// How x86-64 instructions are written
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Syntax {
+    /// Sources before destinations, with `%` before registers, `$` before constants and the size
+    /// of the operands on the end of the instruction. The only syntax of the other instruction sets.
+    Att,
+    /// Destinations before sources, with the size of a memory operand given by `PTR`
+    Intel,
+}
+
+impl fmt::Display for Syntax {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Syntax::Att => write!(f, "att"),
+            Syntax::Intel => write!(f, "intel"),
+        }
+    }
+}
+
+impl FromStr for Syntax {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Syntax, String> {
+        match s {
+            "att" => Ok(Syntax::Att),
+            "intel" => Ok(Syntax::Intel),
+            unknown => Err(format!("Unknown assembly syntax [{}], expected one of att or intel", unknown)),
+        }
+    }
+}
+
+impl Syntax {
+    /// The name a global of the program is written by. A name the assembler would read as
+    /// something else is given a trailing underscore, which no name from the program can have,
+    /// as the GNU assembler reads a register even when its name is quoted.
+    pub fn symbol(&self, name: &str) -> String {
+        if self.reserves(name) {
+            return format!("{}_", name);
+        }
+        name.to_string()
+    }
+
+    /// Whether the assembler would read the name as a register or an operator rather than a
+    /// symbol, as it does in Intel syntax, where registers are written without a `%`
+    fn reserves(&self, name: &str) -> bool {
+        if *self == Syntax::Att {
+            return false;
+        }
+        let name = name.to_lowercase();
+        let name = name.as_str();
+        let number = |digits: &str| !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit());
+        // r8 to r15 with the suffixes naming their low bytes
+        let extended = name.strip_prefix('r').is_some_and(|rest| number(rest.trim_end_matches(['d', 'w', 'b'])));
+        let legacy = ["ax", "bx", "cx", "dx", "si", "di", "sp", "bp", "ip"].iter()
+            .any(|r| name == *r || name == format!("e{}", r) || name == format!("r{}", r));
+        let other = [
+            "al", "bl", "cl", "dl", "ah", "bh", "ch", "dh", "sil", "dil", "spl", "bpl",
+            "cs", "ds", "es", "fs", "gs", "ss", "st",
+        ].contains(&name);
+        // The sizes of memory operands and the operators of expressions
+        let keyword = [
+            "byte", "word", "dword", "fword", "qword", "tbyte", "oword", "xmmword", "ymmword", "zmmword",
+            "offset", "short", "near", "far", "flat",
+            "and", "or", "xor", "not", "mod", "shl", "shr", "eq", "ne", "lt", "le", "gt", "ge",
+        ].contains(&name);
+        extended || legacy || other || keyword || ["xmm", "ymm", "zmm", "mm", "st", "cr", "dr", "tr", "k", "bnd"].iter()
+            .any(|prefix| name.strip_prefix(prefix).is_some_and(number))
+    }
+
+    /// The line as the assembler reads it in this syntax
+    pub fn line(&self, line: &Line) -> String {
+        match line {
+            Line::Instruction(instruction) => self.instruction(instruction, ", "),
+            Line::Commented(instruction, comment) => format!("{}{}", self.instruction(instruction, ", "), comment),
+            Line::Compact(instruction) => self.instruction(instruction, ","),
+            Line::Label(label) => format!("{}:", label),
+            Line::Directive(directive) => format!("\t{}", directive),
+            Line::Comment(comment) => format!("\t# {}", comment),
+            Line::Blank => String::new(),
+        }
+    }
+
+    fn instruction(&self, instruction: &Instruction, separator: &str) -> String {
+        let (mnemonic, mut operands) = match self {
+            Syntax::Att => att(instruction),
+            Syntax::Intel => intel(instruction),
+        };
+        if operands.is_empty() {
+            return format!("\t{}", mnemonic);
+        }
+        if *self == Syntax::Intel {
+            operands.reverse();
+        }
+        format!("\t{}\t{}", mnemonic, operands.join(separator))
+    }
+}
+
+fn suffix(size: Size) -> &'static str {
+    match size {
+        Size::Byte => "b",
+        Size::DoubleWord => "l",
+        Size::QuadWord => "q",
+    }
+}
+
+fn arithmetic(operation: Arithmetic) -> &'static str {
+    match operation {
+        Arithmetic::Add => "add",
+        Arithmetic::Subtract => "sub",
+        Arithmetic::Multiply => "imul",
+        Arithmetic::And => "and",
+        Arithmetic::Xor => "xor",
+        Arithmetic::Compare => "cmp",
+        Arithmetic::Test => "test",
+    }
+}
+
+fn shift(shift: Shift) -> &'static str {
+    match shift {
+        Shift::Left => "shl",
+        Shift::RightArithmetic => "sar",
+        Shift::RightLogical => "shr",
+    }
+}
+
+// The mnemonic and operands of the instruction in AT&T syntax
+fn att(instruction: &Instruction) -> (String, Vec<String>) {
+    let register = |r: &Register| format!("%{}", r.name(Size::QuadWord));
+    match instruction {
+        Instruction::Move(size, source, destination) =>
+            (format!("mov{}", suffix(*size)), vec![att_operand(source), att_operand(destination)]),
+        Instruction::LoadConstant(value, destination) =>
+            ("movq".to_string(), vec![format!("${}", value), register(destination)]),
+        Instruction::SignExtend(from, to, source, destination) =>
+            (format!("movs{}{}", suffix(*from), suffix(*to)), vec![att_operand(source), att_operand(destination)]),
+        Instruction::ZeroExtend(from, to, source, destination) =>
+            (format!("movz{}{}", suffix(*from), suffix(*to)), vec![att_operand(source), att_operand(destination)]),
+        Instruction::LoadAddress(address, destination) =>
+            ("leaq".to_string(), vec![att_address(address), register(destination)]),
+        Instruction::Arithmetic(operation, size, source, destination) =>
+            (format!("{}{}", arithmetic(*operation), suffix(*size)), vec![att_operand(source), att_operand(destination)]),
+        Instruction::Shift(operation, size, destination) =>
+            (format!("{}{}", shift(*operation), suffix(*size)), vec!["%cl".to_string(), att_operand(destination)]),
+        Instruction::Increment(size, destination) => (format!("inc{}", suffix(*size)), vec![att_operand(destination)]),
+        Instruction::Divide(size, divisor) => (format!("div{}", suffix(*size)), vec![att_operand(divisor)]),
+        Instruction::SignedDivide(size, divisor) => (format!("idiv{}", suffix(*size)), vec![att_operand(divisor)]),
+        Instruction::SignExtendAccumulator => ("cqo".to_string(), vec![]),
+        Instruction::Set(condition, destination) => (format!("set{}", condition.suffix()), vec![att_operand(destination)]),
+        Instruction::ConditionalMove(condition, size, source, destination) =>
+            (format!("cmov{}{}", condition.suffix(), suffix(*size)), vec![att_operand(source), att_operand(destination)]),
+        // Jumping to an address held in a register is marked with a `*`
+        Instruction::Jump(target @ Operand::Register(_, _)) => ("jmp".to_string(), vec![format!("*{}", att_operand(target))]),
+        Instruction::Jump(target) => ("jmp".to_string(), vec![att_operand(target)]),
+        Instruction::JumpIf(condition, target) => (format!("j{}", condition.suffix()), vec![att_operand(target)]),
+        Instruction::Call(target) => ("call".to_string(), vec![att_operand(target)]),
+        Instruction::Push(r) => ("pushq".to_string(), vec![register(r)]),
+        Instruction::Pop(r) => ("popq".to_string(), vec![register(r)]),
+        Instruction::Leave => ("leave".to_string(), vec![]),
+        Instruction::Return => ("ret".to_string(), vec![]),
+        Instruction::Nop => ("nop".to_string(), vec![]),
+    }
+}
+
+fn att_operand(operand: &Operand) -> String {
+    match operand {
+        Operand::Register(register, size) => format!("%{}", register.name(*size)),
+        Operand::Immediate(value) => format!("${}", value),
+        Operand::Memory(address) => att_address(address),
+        Operand::Symbol(symbol) => symbol.clone(),
+    }
+}
+
+fn att_address(address: &Address) -> String {
+    match address {
+        Address::Relative(symbol) => format!("{}(%rip)", symbol),
+        Address::Indexed { base, index, displacement } => {
+            let displacement = if *displacement == 0 { String::new() } else { displacement.to_string() };
+            let base = base.name(Size::QuadWord);
+            match index {
+                None => format!("{}(%{})", displacement, base),
+                Some((index, 1)) => format!("{}(%{},%{})", displacement, base, index.name(Size::QuadWord)),
+                Some((index, scale)) => format!("{}(%{},%{},{})", displacement, base, index.name(Size::QuadWord), scale),
+            }
+        }
+    }
+}
+
+// The mnemonic and operands of the instruction in Intel syntax, still in AT&T order. Memory
+// operands say how wide they are, as an operand they are moved from or to might not.
+fn intel(instruction: &Instruction) -> (String, Vec<String>) {
+    let register = |r: &Register| r.name(Size::QuadWord).to_string();
+    match instruction {
+        Instruction::Move(size, source, destination) =>
+            ("mov".to_string(), vec![intel_operand(source, *size), intel_operand(destination, *size)]),
+        Instruction::LoadConstant(value, destination) => ("mov".to_string(), vec![value.to_string(), register(destination)]),
+        // Sign extending a double word has a mnemonic of its own
+        Instruction::SignExtend(Size::DoubleWord, to, source, destination) =>
+            ("movsxd".to_string(), vec![intel_operand(source, Size::DoubleWord), intel_operand(destination, *to)]),
+        Instruction::SignExtend(from, to, source, destination) =>
+            ("movsx".to_string(), vec![intel_operand(source, *from), intel_operand(destination, *to)]),
+        Instruction::ZeroExtend(from, to, source, destination) =>
+            ("movzx".to_string(), vec![intel_operand(source, *from), intel_operand(destination, *to)]),
+        Instruction::LoadAddress(address, destination) => ("lea".to_string(), vec![intel_address(address), register(destination)]),
+        Instruction::Arithmetic(operation, size, source, destination) =>
+            (arithmetic(*operation).to_string(), vec![intel_operand(source, *size), intel_operand(destination, *size)]),
+        Instruction::Shift(operation, size, destination) =>
+            (shift(*operation).to_string(), vec!["cl".to_string(), intel_operand(destination, *size)]),
+        Instruction::Increment(size, destination) => ("inc".to_string(), vec![intel_operand(destination, *size)]),
+        Instruction::Divide(size, divisor) => ("div".to_string(), vec![intel_operand(divisor, *size)]),
+        Instruction::SignedDivide(size, divisor) => ("idiv".to_string(), vec![intel_operand(divisor, *size)]),
+        Instruction::SignExtendAccumulator => ("cqo".to_string(), vec![]),
+        Instruction::Set(condition, destination) => (format!("set{}", condition.suffix()), vec![intel_operand(destination, Size::Byte)]),
+        Instruction::ConditionalMove(condition, size, source, destination) =>
+            (format!("cmov{}", condition.suffix()), vec![intel_operand(source, *size), intel_operand(destination, *size)]),
+        Instruction::Jump(target) => ("jmp".to_string(), vec![intel_operand(target, Size::QuadWord)]),
+        Instruction::JumpIf(condition, target) => (format!("j{}", condition.suffix()), vec![intel_operand(target, Size::QuadWord)]),
+        Instruction::Call(target) => ("call".to_string(), vec![intel_operand(target, Size::QuadWord)]),
+        Instruction::Push(r) => ("push".to_string(), vec![register(r)]),
+        Instruction::Pop(r) => ("pop".to_string(), vec![register(r)]),
+        Instruction::Leave => ("leave".to_string(), vec![]),
+        Instruction::Return => ("ret".to_string(), vec![]),
+        Instruction::Nop => ("nop".to_string(), vec![]),
+    }
+}
+
+fn intel_operand(operand: &Operand, size: Size) -> String {
+    match operand {
+        Operand::Register(register, size) => register.name(*size).to_string(),
+        Operand::Immediate(value) => value.to_string(),
+        Operand::Memory(address) => {
+            let width = match size {
+                Size::Byte => "BYTE",
+                Size::DoubleWord => "DWORD",
+                Size::QuadWord => "QWORD",
+            };
+            format!("{} PTR {}", width, intel_address(address))
+        }
+        Operand::Symbol(symbol) => symbol.clone(),
+    }
+}
+
+fn intel_address(address: &Address) -> String {
+    match address {
+        Address::Relative(symbol) => format!("[rip+{}]", symbol),
+        Address::Indexed { base, index, displacement } => {
+            let mut address = format!("[{}", base.name(Size::QuadWord));
+            match index {
+                None => {}
+                Some((index, 1)) => address.push_str(&format!("+{}", index.name(Size::QuadWord))),
+                Some((index, scale)) => address.push_str(&format!("+{}*{}", index.name(Size::QuadWord), scale)),
+            }
+            match displacement {
+                0 => {}
+                d if *d < 0 => address.push_str(&d.to_string()),
+                d => address.push_str(&format!("+{}", d)),
+            }
+            address.push(']');
+            address
+        }
+    }
+}
+
+/// Writes assembly in the syntax it was made for. The lines of a function are held until it
+/// ends, and lines between functions are written as they come.
+pub struct Assembly<W: Write> {
+    out: W,
+    syntax: Syntax,
+    /// The function being written, if any
+    function: Option<Function>,
+    /// Every line of the program, when it is assembled into an object rather than written out
+    object: Option<Vec<Line>>,
+}
+
+impl<W: Write> Assembly<W> {
+    pub fn new(syntax: Syntax, out: W) -> Assembly<W> {
+        Assembly { out, syntax, function: None, object: None }
+    }
+
+    pub fn syntax(&self) -> Syntax {
+        self.syntax
+    }
+
+    pub fn set_syntax(&mut self, syntax: Syntax) {
+        self.syntax = syntax;
+    }
+
+    /// Ends the function being written, if there is one, and starts holding the lines of another
+    pub fn begin_function(&mut self, name: &str) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        self.end_function()?;
+        self.function = Some(Function::new(name));
+        Ok(())
+    }
+
+    /// Writes out the lines of the function being written, if there is one
+    pub fn end_function(&mut self) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        if let Some(function) = self.function.take() {
+            debug!("Function {} has {} instructions", function.name, function.instructions().count());
+            for line in function.lines {
+                self.write(line)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn line(&mut self, line: Line) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        match &mut self.function {
+            Some(function) => function.lines.push(line),
+            None => self.write(line)?,
+        }
+        Ok(())
+    }
+
+    pub fn lines(&mut self, lines: Vec<Line>) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        for line in lines {
+            self.line(line)?;
+        }
+        Ok(())
+    }
+
+    pub fn instruction(&mut self, instruction: Instruction) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        self.line(Line::Instruction(instruction))
+    }
+
+    fn write(&mut self, line: Line) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        match &mut self.object {
+            Some(lines) => lines.push(line),
+            None => writeln!(self.out, "{}", self.syntax.line(&line))?,
+        }
+        Ok(())
+    }
+
+    /// Keeps every line to be assembled into an object once the program ends, instead of writing it out
+    pub fn keep_for_object(&mut self) {
+        self.object = Some(Vec::new());
+    }
+
+    pub fn is_object(&self) -> bool {
+        self.object.is_some()
+    }
+
+    /// Assembles the lines kept, with the strings they use, into a relocatable ELF object and writes it out
+    pub fn write_object(&mut self, strings: &Strings) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        self.end_function()?;
+        let lines = self.object.take().expect("Expected the lines of the program to have been kept");
+        elf::write(&lines, strings, &mut self.out)
+    }
+
+    /// The writer underneath, for what is written the same way in either syntax. It is written to
+    /// straight away, ahead of the lines of a function that has not yet ended.
+    pub fn writer(&mut self) -> &mut W {
+        &mut self.out
+    }
+}
@@ -0,0 +1,134 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// The instruction sets there is a backend for
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Architecture {
+    X86_64,
+    AArch64,
+    RiscV64,
+}
+
+/// The platform the assembly is written for. Besides the instruction set this decides how
+/// symbols are named, how the C library is called and which sections and directives the
+/// assembler understands.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Target {
+    /// x86-64 Linux, assembled to ELF and linked as a position independent executable
+    X86_64Linux,
+    /// x86-64 macOS, assembled to Mach-O
+    X86_64Darwin,
+    /// 64 bit ARM Linux, assembled to ELF
+    AArch64Linux,
+    /// 64 bit RISC-V Linux with the integer multiply and divide extension, assembled to ELF
+    RiscV64Linux,
+}
+
+impl Target {
+    /// The target of the machine the compiler is running on
+    pub fn host() -> Target {
+        if cfg!(target_os = "macos") {
+            return Target::X86_64Darwin;
+        }
+        if cfg!(target_arch = "aarch64") {
+            return Target::AArch64Linux;
+        }
+        if cfg!(target_arch = "riscv64") {
+            return Target::RiscV64Linux;
+        }
+        Target::X86_64Linux
+    }
+
+    pub fn architecture(&self) -> Architecture {
+        match self {
+            Target::X86_64Linux | Target::X86_64Darwin => Architecture::X86_64,
+            Target::AArch64Linux => Architecture::AArch64,
+            Target::RiscV64Linux => Architecture::RiscV64,
+        }
+    }
+
+    /// Whether the assembler writes ELF objects, which need a `.note.GNU-stack` section to
+    /// be linked without an executable stack
+    pub fn is_elf(&self) -> bool {
+        match self {
+            Target::X86_64Linux | Target::AArch64Linux | Target::RiscV64Linux => true,
+            Target::X86_64Darwin => false,
+        }
+    }
+
+    /// The name the linker knows a symbol defined by the program by. Mach-O puts an underscore
+    /// in front of every C name.
+    pub fn symbol(&self, name: &str) -> String {
+        match self {
+            Target::X86_64Linux | Target::AArch64Linux | Target::RiscV64Linux => name.to_string(),
+            Target::X86_64Darwin => format!("_{}", name),
+        }
+    }
+
+    /// The prefix of a label the assembler leaves out of the symbol table. Anything else is an
+    /// ordinary symbol on ELF, which a global of the program could share the name of.
+    pub fn local_label_prefix(&self) -> &'static str {
+        match self {
+            Target::X86_64Linux | Target::AArch64Linux | Target::RiscV64Linux => ".L",
+            Target::X86_64Darwin => "L",
+        }
+    }
+
+    /// The name of a routine of the runtime, kept out of the symbol table like a label so a
+    /// global of the program can have the same name
+    pub fn runtime(&self, name: &str) -> String {
+        format!("{}{}", self.local_label_prefix(), name)
+    }
+
+    /// The operand of a call to a function in the C library. On x86-64 Linux the call goes
+    /// through the procedure linkage table so it works wherever the library is loaded, which
+    /// the AArch64 and RISC-V linkers arrange without being asked.
+    pub fn function(&self, name: &str) -> String {
+        match self {
+            Target::X86_64Linux => format!("{}@PLT", name),
+            Target::X86_64Darwin | Target::AArch64Linux | Target::RiscV64Linux => self.symbol(name),
+        }
+    }
+
+    /// The section read only data such as strings and jump tables are placed in
+    pub fn read_only_section(&self) -> &'static str {
+        match self {
+            Target::X86_64Linux | Target::AArch64Linux | Target::RiscV64Linux => ".rodata",
+            Target::X86_64Darwin => "__TEXT,__const",
+        }
+    }
+
+    /// The alignment operand of `.comm`, which Mach-O gives as a power of two
+    pub fn common_alignment(&self, alignment: u32) -> u32 {
+        match self {
+            Target::X86_64Linux | Target::AArch64Linux | Target::RiscV64Linux => alignment,
+            Target::X86_64Darwin => alignment.trailing_zeros(),
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Target::X86_64Linux => write!(f, "x86_64-linux"),
+            Target::X86_64Darwin => write!(f, "x86_64-darwin"),
+            Target::AArch64Linux => write!(f, "aarch64-linux"),
+            Target::RiscV64Linux => write!(f, "riscv64-linux"),
+        }
+    }
+}
+
+impl FromStr for Target {
+    type Err = String;
+
+    // Accepts the short names shown by `Display` as well as the full target triples
+    fn from_str(s: &str) -> Result<Target, String> {
+        match s {
+            "x86_64-linux" | "x86_64-unknown-linux-gnu" | "x86_64-pc-linux-gnu" => Ok(Target::X86_64Linux),
+            "x86_64-darwin" | "x86_64-macos" | "x86_64-apple-darwin" => Ok(Target::X86_64Darwin),
+            "aarch64-linux" | "aarch64-unknown-linux-gnu" | "arm64-linux" => Ok(Target::AArch64Linux),
+            "riscv64-linux" | "riscv64-unknown-linux-gnu" | "riscv64gc-unknown-linux-gnu" => Ok(Target::RiscV64Linux),
+            unknown => Err(format!("Unknown target [{}], expected one of x86_64-linux, x86_64-darwin, aarch64-linux or riscv64-linux", unknown)),
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use std::io::Write;
+
+use crate::asm::{cgadd, cgaddress, cgaddresslocal, cgarithmeticconst, cgboundscheck, cgboundsfail, cgcomment, cgdiv, cgend, cgequal, cgexit, cgextend, cgglobsym, cggreaterequal, cggreaterthan, cgjump, cgjumpifequal, cgjumpifzero, cgjumptable, cglabel, cglessequal, cglessthan, cgload, cgloadderef, cgloadglob, cgloadlocal, cgloadstring, cgmove, cgmul, cgnotequal, cgpostamble, cgpreamble, cgprintint, cgprintstr, cgprintuint, cgreadint, cgreadline, cgreturn, cgselect, cgshl, cgshr, cgstorderef, cgstorglob, cgstorlocal, cgsub};
+use crate::asm::backend::{Backend, BinaryOperation, Comparison, Function};
+use crate::asm::instruction::Arithmetic;
+use crate::asm::labels::{LabelIndex, Labels};
+use crate::asm::registers::{RegisterIndex, Registers};
+use crate::asm::strings::Strings;
+use crate::asm::syntax::{Assembly, Syntax};
+use crate::asm::target::Target;
+use crate::compiler::Program;
+use crate::symbols::{Storage, Symbol};
+use crate::types::Type;
+
+/// The x86-64 backend, building instructions with the `cg` functions and writing each function
+/// out in AT&T or Intel syntax once it ends
+pub struct X86_64<W: Write> {
+    out: Assembly<W>,
+    target: Target,
+    registers: Registers,
+    labels: Labels,
+    strings: Strings,
+    bounds_checked: bool,
+    frame_size: u32,
+}
+
+impl<W: Write> Backend<W> for X86_64<W> {
+    type Register = RegisterIndex;
+
+    fn new(target: Target, out: W) -> Self {
+        X86_64 {
+            out: Assembly::new(Syntax::Att, out),
+            target,
+            registers: Registers::new(),
+            labels: Labels::for_target(target),
+            strings: Strings::new(),
+            bounds_checked: false,
+            frame_size: 0,
+        }
+    }
+
+    fn set_syntax(&mut self, syntax: Syntax) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        self.out.set_syntax(syntax);
+        Ok(())
+    }
+
+    fn set_object_output(&mut self) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        if self.target != Target::X86_64Linux {
+            return Err(format!("Unable to write an object file for [{}], they are only written in ELF for x86_64-linux", self.target).into());
+        }
+        self.out.keep_for_object();
+        Ok(())
+    }
+
+    fn survey(&mut self, program: &Program) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        self.frame_size = program.symbols.frame_size();
+        Ok(())
+    }
+
+    fn prologue(&mut self) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        cgpreamble(self.target, self.frame_size, &mut self.strings, &mut self.out)
+    }
+
+    fn epilogue(&mut self) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        cgpostamble(&mut self.out)?;
+        if self.bounds_checked {
+            cgboundsfail(self.target, &mut self.strings, &mut self.out)?;
+        }
+        self.out.end_function()?;
+        if self.out.is_object() {
+            cgend(self.target, &mut self.out)?;
+            return self.out.write_object(&self.strings);
+        }
+        self.strings.write(self.target, self.out.writer())?;
+        cgend(self.target, &mut self.out)
+    }
+
+    fn comment(&mut self, comment: &str) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        cgcomment(&mut self.out, comment)
+    }
+
+    fn load(&mut self, value: u64, _ty: &Type) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        cgload(value, &mut self.registers, &mut self.out)
+    }
+
+    fn load_string(&mut self, string: &str) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        cgloadstring(self.strings.intern(string), &mut self.registers, &mut self.out)
+    }
+
+    fn binary(&mut self, operation: BinaryOperation, r1: RegisterIndex, r2: RegisterIndex, ty: &Type) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        let registers = &mut self.registers;
+        let out = &mut self.out;
+        match operation {
+            BinaryOperation::Add => cgadd(r1, r2, registers, out),
+            BinaryOperation::Subtract => cgsub(r1, r2, registers, out),
+            BinaryOperation::Multiply => cgmul(r1, r2, registers, out),
+            BinaryOperation::Divide => cgdiv(r1, r2, ty, registers, out),
+            BinaryOperation::LeftShift => cgshl(r1, r2, registers, out),
+            BinaryOperation::RightShift => cgshr(r1, r2, ty, registers, out),
+        }
+    }
+
+    fn binary_constant(&mut self, operation: BinaryOperation, r: RegisterIndex, value: u64, ty: &Type) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        let arithmetic = match operation {
+            BinaryOperation::Add => Some(Arithmetic::Add),
+            BinaryOperation::Subtract => Some(Arithmetic::Subtract),
+            BinaryOperation::Multiply => Some(Arithmetic::Multiply),
+            _ => None,
+        };
+        match arithmetic {
+            Some(arithmetic) if value as i64 >= i32::MIN as i64 && value as i64 <= i32::MAX as i64 =>
+                cgarithmeticconst(arithmetic, r, value as i64, &mut self.out),
+            _ => {
+                let constant = self.load(value, ty)?;
+                self.binary(operation, r, constant, ty)
+            }
+        }
+    }
+
+    fn compare(&mut self, comparison: Comparison, r1: RegisterIndex, r2: RegisterIndex, ty: &Type) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        let registers = &mut self.registers;
+        let out = &mut self.out;
+        match comparison {
+            Comparison::Equal => cgequal(r1, r2, registers, out),
+            Comparison::NotEqual => cgnotequal(r1, r2, registers, out),
+            Comparison::LessThan => cglessthan(r1, r2, ty, registers, out),
+            Comparison::GreaterThan => cggreaterthan(r1, r2, ty, registers, out),
+            Comparison::LessThanEqual => cglessequal(r1, r2, ty, registers, out),
+            Comparison::GreaterThanEqual => cggreaterequal(r1, r2, ty, registers, out),
+        }
+    }
+
+    fn extend(&mut self, r: RegisterIndex, ty: &Type) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        cgextend(r, ty, &mut self.out)
+    }
+
+    fn declare_global(&mut self, symbol: &Symbol) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        cgglobsym(self.target, &symbol.name, &symbol.ty, &mut self.out)
+    }
+
+    fn load_symbol(&mut self, symbol: &Symbol, ty: &Type) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        match symbol.storage {
+            Storage::Global => cgloadglob(&symbol.name, ty, &mut self.registers, &mut self.out),
+            Storage::Frame(offset) => cgloadlocal(offset, ty, &mut self.registers, &mut self.out),
+        }
+    }
+
+    fn store_symbol(&mut self, symbol: &Symbol, r: RegisterIndex, ty: &Type) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        match symbol.storage {
+            Storage::Global => cgstorglob(&symbol.name, r, ty, &mut self.out),
+            Storage::Frame(offset) => cgstorlocal(offset, r, ty, &mut self.out),
+        }
+    }
+
+    fn address_of(&mut self, symbol: &Symbol, _ty: &Type) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        match symbol.storage {
+            Storage::Global => cgaddress(&symbol.name, &mut self.registers, &mut self.out),
+            Storage::Frame(offset) => cgaddresslocal(offset, &mut self.registers, &mut self.out),
+        }
+    }
+
+    fn load_dereference(&mut self, r: RegisterIndex, ty: &Type) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        cgloadderef(r, ty, &mut self.out)
+    }
+
+    fn store_dereference(&mut self, value: RegisterIndex, pointer: RegisterIndex, ty: &Type) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        cgstorderef(value, pointer, ty, &mut self.registers, &mut self.out)
+    }
+
+    fn bounds_check(&mut self, r: RegisterIndex, length: u32, line: u32) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        self.bounds_checked = true;
+        cgboundscheck(self.target, r, length, line, &mut self.labels, &mut self.out)
+    }
+
+    fn call(&mut self, function: Function, arguments: &[RegisterIndex]) -> core::result::Result<Option<RegisterIndex>, Box<dyn std::error::Error>> {
+        let registers = &mut self.registers;
+        let out = &mut self.out;
+        match (function, arguments) {
+            (Function::PrintInt, [r]) => cgprintint(self.target, *r, out).map(|_| None),
+            (Function::PrintUnsignedInt, [r]) => cgprintuint(self.target, *r, out).map(|_| None),
+            (Function::PrintString, [r]) => cgprintstr(self.target, *r, out).map(|_| None),
+            (Function::ReadInt(ty), [r]) => cgreadint(self.target, *r, &ty, registers, &mut self.labels, out).map(Some),
+            (Function::ReadLine, [buffer, size]) => cgreadline(self.target, *buffer, *size, registers, out).map(Some),
+            (Function::Exit, [r]) => cgexit(self.target, *r, registers, out).map(|_| None),
+            (function, arguments) => panic!("Unable to call {:?} with {} arguments", function, arguments.len()),
+        }
+    }
+
+    fn return_value(&mut self, r: RegisterIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        cgreturn(r, &mut self.registers, &mut self.out)
+    }
+
+    fn allocate_label(&mut self) -> LabelIndex {
+        self.labels.allocate_label()
+    }
+
+    fn label(&mut self, label: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        cglabel(label, &mut self.out)
+    }
+
+    fn jump(&mut self, label: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        cgjump(label, &mut self.out)
+    }
+
+    fn jump_if_zero(&mut self, r: RegisterIndex, label: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        cgjumpifzero(r, label, &mut self.registers, &mut self.out)
+    }
+
+    fn jump_if_equal(&mut self, r: RegisterIndex, value: u64, label: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        cgjumpifequal(r, value, label, &mut self.out)
+    }
+
+    fn jump_table(&mut self, r: RegisterIndex, low: u64, targets: &[LabelIndex], default: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        cgjumptable(self.target, r, low, targets, default, &mut self.labels, &mut self.out)
+    }
+
+    fn move_register(&mut self, from: RegisterIndex, to: RegisterIndex) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        cgmove(from, to, &mut self.registers, &mut self.out)
+    }
+
+    fn select(&mut self, condition: RegisterIndex, when_true: RegisterIndex, when_false: RegisterIndex) -> core::result::Result<RegisterIndex, Box<dyn std::error::Error>> {
+        cgselect(condition, when_true, when_false, &mut self.registers, &mut self.out)
+    }
+
+    fn free_register(&mut self, r: RegisterIndex) {
+        self.registers.free_register(r);
+    }
+
+    fn free_all_registers(&mut self) {
+        self.registers.free_all();
+    }
+}
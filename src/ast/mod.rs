@@ -1,16 +1,32 @@
+use std::fmt;
+
 use crate::scanner::{Token, KeywordToken};
+use crate::symbols::SymbolId;
+use crate::types::Type;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum AbstractSyntaxTreeNode {
     Construct(AbstractSyntaxTreeConstructNodeType, Box<AbstractSyntaxTreeNode>),
-    Expression(AbstractSyntaxTreeExpressionNodeType, Box<AbstractSyntaxTreeNode>, Box<AbstractSyntaxTreeNode>),
-    Leaf(AbstractSyntaxTreeLeafNodeType),
+    Expression(AbstractSyntaxTreeExpressionNodeType, Type, Box<AbstractSyntaxTreeNode>, Box<AbstractSyntaxTreeNode>),
+    Unary(AbstractSyntaxTreeUnaryNodeType, Type, Box<AbstractSyntaxTreeNode>),
+    Ternary(AbstractSyntaxTreeTernaryNodeType, Type, Box<AbstractSyntaxTreeNode>, Box<AbstractSyntaxTreeNode>, Box<AbstractSyntaxTreeNode>),
+    Leaf(AbstractSyntaxTreeLeafNodeType, Type),
+    /// A call to one of the functions built in to the language
+    Call(AbstractSyntaxTreeCallNodeType, Type, Vec<AbstractSyntaxTreeNode>),
+    /// A construct controlled by an expression that contains a list of statements
+    Compound(AbstractSyntaxTreeCompoundNodeType, Box<AbstractSyntaxTreeNode>, Vec<AbstractSyntaxTreeNode>),
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum AbstractSyntaxTreeConstructNodeType {
     Print,
     Declaration,
+    /// Declares its child as a variable of `main`, kept in its stack frame
+    Local,
+    /// Ends the program with its child as the exit status
+    Return,
+    /// Declares its child as the parameter of `main` at the given position
+    Parameter(u32),
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -26,21 +42,105 @@ pub enum AbstractSyntaxTreeExpressionNodeType {
     GreaterThanEqual,
     NotEqual,
     Equality,
+    LeftShift,
+    RightShift,
+    /// `array[index]`, replaced by pointer arithmetic and a dereference when type checked
+    Index,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum AbstractSyntaxTreeUnaryNodeType {
+    /// Converts the value of its child to the type of this node
+    Convert,
+    AddressOf,
+    Dereference,
+    /// Checks its child is a valid index into an array of the given length
+    BoundsCheck(u32, Location),
+    /// `value.member`, replaced by an offset from the address of the struct when type checked
+    Member(String),
+    /// A conversion written in the source, replaced by a `Convert` when type checked
+    Cast,
+    /// The size of the type of its child, replaced by a literal when type checked
+    Sizeof,
+    /// A `case` label of the enclosing switch with the constant expression it matches, replaced
+    /// by a `Case` leaf holding its value when type checked
+    Case(Location),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum AbstractSyntaxTreeTernaryNodeType {
+    Conditional,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum AbstractSyntaxTreeCallNodeType {
+    /// `read(&x)` reads an integer from standard input into `x`, giving 1 when one was read, 0
+    /// when the input is not an integer and -1 at the end of the input
+    Read,
+    /// `readline(buffer, size)` reads a line from standard input into the buffer without its
+    /// newline, giving the length of the line or -1 at the end of the input
+    ReadLine,
+    /// `exit(status)` ends the program with the given exit status once output has been flushed
+    Exit,
+}
+
+impl AbstractSyntaxTreeCallNodeType {
+    pub fn from_name(name: &str) -> Option<AbstractSyntaxTreeCallNodeType> {
+        match name {
+            "read" => Some(AbstractSyntaxTreeCallNodeType::Read),
+            "readline" => Some(AbstractSyntaxTreeCallNodeType::ReadLine),
+            "exit" => Some(AbstractSyntaxTreeCallNodeType::Exit),
+            _ => None,
+        }
+    }
+
+    /// The number of arguments the function takes
+    pub fn arity(&self) -> usize {
+        match self {
+            AbstractSyntaxTreeCallNodeType::Read => 1,
+            AbstractSyntaxTreeCallNodeType::ReadLine => 2,
+            AbstractSyntaxTreeCallNodeType::Exit => 1,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum AbstractSyntaxTreeCompoundNodeType {
+    /// Jumps to the `Case` among its statements matching the value of the expression
+    Switch,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum AbstractSyntaxTreeLeafNodeType {
-    U32(u32),
+    U64(u64),
     SemiColon,
-    Identifier(String),
+    Identifier(String, Location),
+    Symbol(SymbolId, Location),
+    /// A string literal, a `char *` to where the string is stored
+    String(String),
+    /// A `case` label of the enclosing switch, holding the constant it matches
+    Case(u64),
+    Default,
+    Break,
+}
+
+/// Where in the source file a node was parsed from
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Location {
+    pub line: u32,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}", self.line)
+    }
 }
 
 
 impl From<Token> for AbstractSyntaxTreeLeafNodeType {
     fn from(token: Token) -> Self {
         match token {
-            Token::U32(v) => AbstractSyntaxTreeLeafNodeType::U32(v),
-            Token::Identifier(v) => AbstractSyntaxTreeLeafNodeType::Identifier(v),
+            Token::U64(v) => AbstractSyntaxTreeLeafNodeType::U64(v),
             unhandled => panic!("Unable to convert {:?} to an [AbstractSyntaxTreeLeafNodeType]", unhandled)
         }
     }
@@ -69,14 +169,34 @@ impl From<Token> for AbstractSyntaxTreeExpressionNodeType {
             Token::GreaterThanEqual => AbstractSyntaxTreeExpressionNodeType::GreaterThanEqual,
             Token::NotEqual => AbstractSyntaxTreeExpressionNodeType::NotEqual,
             Token::Equality => AbstractSyntaxTreeExpressionNodeType::Equality,
+            Token::LeftShift => AbstractSyntaxTreeExpressionNodeType::LeftShift,
+            Token::RightShift => AbstractSyntaxTreeExpressionNodeType::RightShift,
             unhandled => panic!("Unable to convert {:?} to an [AbstractSyntaxTreeInteriorNodeType]", unhandled)
         }
     }
 }
 
+impl AbstractSyntaxTreeExpressionNodeType {
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            AbstractSyntaxTreeExpressionNodeType::LessThan |
+            AbstractSyntaxTreeExpressionNodeType::LessThanEqual |
+            AbstractSyntaxTreeExpressionNodeType::GreaterThan |
+            AbstractSyntaxTreeExpressionNodeType::GreaterThanEqual |
+            AbstractSyntaxTreeExpressionNodeType::NotEqual |
+            AbstractSyntaxTreeExpressionNodeType::Equality
+        )
+    }
+}
+
 impl AbstractSyntaxTreeNode {
     pub fn new_leaf_node(op: AbstractSyntaxTreeLeafNodeType) -> AbstractSyntaxTreeNode {
-        AbstractSyntaxTreeNode::Leaf(op)
+        AbstractSyntaxTreeNode::Leaf(op, Type::None)
+    }
+
+    pub fn new_typed_leaf_node(op: AbstractSyntaxTreeLeafNodeType, ty: Type) -> AbstractSyntaxTreeNode {
+        AbstractSyntaxTreeNode::Leaf(op, ty)
     }
 
     pub fn new_interior(
@@ -84,7 +204,66 @@ impl AbstractSyntaxTreeNode {
         left: AbstractSyntaxTreeNode,
         right: AbstractSyntaxTreeNode,
     ) -> AbstractSyntaxTreeNode {
-        AbstractSyntaxTreeNode::Expression(op, Box::new(left), Box::new(right))
+        AbstractSyntaxTreeNode::new_typed_interior(op, Type::None, left, right)
+    }
+
+    pub fn new_typed_interior(
+        op: AbstractSyntaxTreeExpressionNodeType,
+        ty: Type,
+        left: AbstractSyntaxTreeNode,
+        right: AbstractSyntaxTreeNode,
+    ) -> AbstractSyntaxTreeNode {
+        AbstractSyntaxTreeNode::Expression(op, ty, Box::new(left), Box::new(right))
+    }
+
+    pub fn new_unary(
+        op: AbstractSyntaxTreeUnaryNodeType,
+        ty: Type,
+        left: AbstractSyntaxTreeNode,
+    ) -> AbstractSyntaxTreeNode {
+        AbstractSyntaxTreeNode::Unary(op, ty, Box::new(left))
+    }
+
+    pub fn new_ternary(
+        op: AbstractSyntaxTreeTernaryNodeType,
+        left: AbstractSyntaxTreeNode,
+        middle: AbstractSyntaxTreeNode,
+        right: AbstractSyntaxTreeNode,
+    ) -> AbstractSyntaxTreeNode {
+        AbstractSyntaxTreeNode::new_typed_ternary(op, Type::None, left, middle, right)
+    }
+
+    pub fn new_typed_ternary(
+        op: AbstractSyntaxTreeTernaryNodeType,
+        ty: Type,
+        left: AbstractSyntaxTreeNode,
+        middle: AbstractSyntaxTreeNode,
+        right: AbstractSyntaxTreeNode,
+    ) -> AbstractSyntaxTreeNode {
+        AbstractSyntaxTreeNode::Ternary(op, ty, Box::new(left), Box::new(middle), Box::new(right))
+    }
+
+    pub fn new_compound(
+        op: AbstractSyntaxTreeCompoundNodeType,
+        left: AbstractSyntaxTreeNode,
+        statements: Vec<AbstractSyntaxTreeNode>,
+    ) -> AbstractSyntaxTreeNode {
+        AbstractSyntaxTreeNode::Compound(op, Box::new(left), statements)
+    }
+
+    pub fn new_call(
+        op: AbstractSyntaxTreeCallNodeType,
+        arguments: Vec<AbstractSyntaxTreeNode>,
+    ) -> AbstractSyntaxTreeNode {
+        AbstractSyntaxTreeNode::new_typed_call(op, Type::None, arguments)
+    }
+
+    pub fn new_typed_call(
+        op: AbstractSyntaxTreeCallNodeType,
+        ty: Type,
+        arguments: Vec<AbstractSyntaxTreeNode>,
+    ) -> AbstractSyntaxTreeNode {
+        AbstractSyntaxTreeNode::Call(op, ty, arguments)
     }
 
     pub fn new_construct(
@@ -93,4 +272,39 @@ impl AbstractSyntaxTreeNode {
     ) -> AbstractSyntaxTreeNode {
         AbstractSyntaxTreeNode::Construct(op, Box::new(left))
     }
-}
\ No newline at end of file
+
+    /// The type of the value this node produces, `Type::None` for constructs
+    /// and for expressions that have not been type checked
+    pub fn ty(&self) -> &Type {
+        match self {
+            AbstractSyntaxTreeNode::Construct(_, _) => &Type::None,
+            AbstractSyntaxTreeNode::Compound(_, _, _) => &Type::None,
+            AbstractSyntaxTreeNode::Call(_, ty, _) => ty,
+            AbstractSyntaxTreeNode::Expression(_, ty, _, _) => ty,
+            AbstractSyntaxTreeNode::Unary(_, ty, _) => ty,
+            AbstractSyntaxTreeNode::Ternary(_, ty, _, _, _) => ty,
+            AbstractSyntaxTreeNode::Leaf(_, ty) => ty,
+        }
+    }
+
+    /// Whether evaluating this node could be observed beyond the value it produces, either by
+    /// writing to a variable or by trapping (division by zero, dereferencing a bad pointer, an
+    /// out of bounds index).
+    /// Nodes without side effects may be evaluated speculatively.
+    pub fn has_side_effects(&self) -> bool {
+        match self {
+            AbstractSyntaxTreeNode::Construct(_, _) => true,
+            AbstractSyntaxTreeNode::Compound(_, _, _) => true,
+            AbstractSyntaxTreeNode::Call(_, _, _) => true,
+            AbstractSyntaxTreeNode::Expression(AbstractSyntaxTreeExpressionNodeType::Assignment, _, _, _) => true,
+            AbstractSyntaxTreeNode::Expression(AbstractSyntaxTreeExpressionNodeType::Divide, _, _, _) => true,
+            AbstractSyntaxTreeNode::Unary(AbstractSyntaxTreeUnaryNodeType::Dereference, _, _) => true,
+            AbstractSyntaxTreeNode::Unary(AbstractSyntaxTreeUnaryNodeType::BoundsCheck(_, _), _, _) => true,
+            AbstractSyntaxTreeNode::Expression(_, _, left, right) => left.has_side_effects() || right.has_side_effects(),
+            AbstractSyntaxTreeNode::Unary(_, _, left) => left.has_side_effects(),
+            AbstractSyntaxTreeNode::Ternary(_, _, left, middle, right) =>
+                left.has_side_effects() || middle.has_side_effects() || right.has_side_effects(),
+            AbstractSyntaxTreeNode::Leaf(_, _) => false,
+        }
+    }
+}
@@ -3,10 +3,19 @@ use std::fs::File;
 use std::io::Write;
 
 use clap::Clap;
-use log::debug;
+use log::{debug, error};
 use log::LevelFilter;
 
+use learn_to_write_a_compiler::asm::aarch64::AArch64;
+use learn_to_write_a_compiler::asm::riscv64::RiscV64;
+use learn_to_write_a_compiler::asm::syntax::Syntax;
+use learn_to_write_a_compiler::asm::target::{Architecture, Target};
+use learn_to_write_a_compiler::asm::x86_64::X86_64;
 use learn_to_write_a_compiler::compiler::Compiler;
+use learn_to_write_a_compiler::emit::Emit;
+use learn_to_write_a_compiler::emit::c::CEmitter;
+use learn_to_write_a_compiler::emit::llvm::LlvmEmitter;
+use learn_to_write_a_compiler::emit::wat::WatEmitter;
 use learn_to_write_a_compiler::scanner::{Token, TokenIterator};
 use learn_to_write_a_compiler::compiler::code_generator::CodeGenerator;
 
@@ -35,6 +44,18 @@ pub struct Compile {
     /// The main file to compile
     #[clap(short, long)]
     file: String,
+    /// Abort with an error when an array is indexed out of bounds
+    #[clap(long)]
+    bounds_check: bool,
+    /// The platform to write assembly for, x86_64-linux, x86_64-darwin, aarch64-linux or riscv64-linux. Defaults to the host.
+    #[clap(long)]
+    target: Option<Target>,
+    /// What to write for the program, asm for assembly, obj for an object file encoded without an assembler, wat for a WebAssembly text module, llvm for LLVM IR, which needs LLVM 15 or later, or c for C source. Assembly and objects for the host are then linked into an executable by the system linker through cc, so even obj needs cc and a linker installed.
+    #[clap(long, default_value = "asm")]
+    emit: Emit,
+    /// How x86-64 instructions are written, att or intel
+    #[clap(long, default_value = "att")]
+    asm_syntax: Syntax,
 }
 
 #[derive(Clap)]
@@ -54,7 +75,7 @@ fn main() -> core::result::Result<(), Box<dyn std::error::Error>> {
         logger.filter_level(match opts.verbose {
             0 => LevelFilter::Info,
             1 => LevelFilter::Debug,
-            2 | _ => LevelFilter::Trace,
+            _ => LevelFilter::Trace,
         });
     }
 
@@ -63,8 +84,8 @@ fn main() -> core::result::Result<(), Box<dyn std::error::Error>> {
     // You can handle information about subcommands by requesting their matches by name
     // (as below), requesting just the name used, or both at the same time
     match opts.subcmd {
-        SubCommand::Compile(t) => return compile(t),
-        SubCommand::Print(t) => return print(t),
+        SubCommand::Compile(t) => compile(t),
+        SubCommand::Print(t) => print(t),
     }
 }
 
@@ -76,7 +97,7 @@ fn print(p: Print) -> core::result::Result<(), Box<dyn std::error::Error>> {
     let chars = content.chars();
 
     let tokens = TokenIterator::new_iterator(chars)
-        .filter(|x| (*x != Token::Space) && (*x != Token::NewLine));
+        .filter(|x| *x != Token::Space);
 
     let code_generator = CodeGenerator::new(tokens);
 
@@ -90,9 +111,48 @@ fn print(p: Print) -> core::result::Result<(), Box<dyn std::error::Error>> {
 }
 
 fn compile(c: Compile) -> core::result::Result<(), Box<dyn std::error::Error>> {
-    let compiler = Compiler::new();
+    let target = c.target.unwrap_or_else(Target::host);
 
     let file = c.file;
 
-    return compiler.compile(file);
-}
\ No newline at end of file
+    // WebAssembly, LLVM IR and C are written by backends of their own, and assembly by the backend for the instruction set
+    let result = match (c.emit, target.architecture()) {
+        (Emit::Wat, _) => Compiler::<WatEmitter<File>>::new()
+            .with_bounds_checking(c.bounds_check)
+            .with_emit(c.emit)
+            .compile(file),
+        (Emit::Llvm, _) => Compiler::<LlvmEmitter<File>>::new()
+            .with_bounds_checking(c.bounds_check)
+            .with_emit(c.emit)
+            .compile(file),
+        (Emit::C, _) => Compiler::<CEmitter<File>>::new()
+            .with_bounds_checking(c.bounds_check)
+            .with_emit(c.emit)
+            .compile(file),
+        (Emit::Assembly | Emit::Object, Architecture::X86_64) => Compiler::<X86_64<File>>::new()
+            .with_bounds_checking(c.bounds_check)
+            .with_target(target)
+            .with_syntax(c.asm_syntax)
+            .with_emit(c.emit)
+            .compile(file),
+        (Emit::Assembly | Emit::Object, Architecture::AArch64) => Compiler::<AArch64<File>>::new()
+            .with_bounds_checking(c.bounds_check)
+            .with_target(target)
+            .with_syntax(c.asm_syntax)
+            .with_emit(c.emit)
+            .compile(file),
+        (Emit::Assembly | Emit::Object, Architecture::RiscV64) => Compiler::<RiscV64<File>>::new()
+            .with_bounds_checking(c.bounds_check)
+            .with_target(target)
+            .with_syntax(c.asm_syntax)
+            .with_emit(c.emit)
+            .compile(file),
+    };
+
+    if let Err(e) = result {
+        error!("{}", e);
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
@@ -1,4 +1,7 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::convert::TryFrom;
 use std::iter::Peekable;
+use std::rc::Rc;
 
 use log::debug;
 
@@ -6,52 +9,481 @@ use crate::ast::*;
 use crate::ast::AbstractSyntaxTreeNode;
 use crate::scanner::{KeywordToken, Token};
 use crate::scanner::Precedence;
+use crate::types::{Struct, StructKind, Type};
 
 pub struct CodeGenerator<T: Iterator<Item=Token>> {
     inner: Peekable<T>,
+    pending: VecDeque<AbstractSyntaxTreeNode>,
+    line: u32,
+    /// Structs and unions defined so far, by tag. They can only be used once their definition is complete.
+    structs: HashMap<String, Rc<Struct>>,
+    enums: HashSet<String>,
+    /// The values of enumerators, which are replaced by literals as they are parsed
+    constants: HashMap<String, u64>,
+    /// Names declared with `typedef`. Knowing these is what lets `T * x;` be parsed as a
+    /// declaration rather than a multiplication.
+    typedefs: HashMap<String, Type>,
+    /// Set while the statements of `main` are being parsed, so its closing brace can be matched
+    in_main: bool,
+    main_defined: bool,
 }
 
 impl<T: Iterator<Item=Token>> CodeGenerator<T> {
     pub fn new(tokens: T) -> Self {
         CodeGenerator {
-            inner: tokens.peekable()
+            inner: tokens.peekable(),
+            pending: VecDeque::new(),
+            line: 1,
+            structs: HashMap::new(),
+            enums: HashSet::new(),
+            constants: HashMap::new(),
+            typedefs: HashMap::new(),
+            in_main: false,
+            main_defined: false,
         }
     }
 
-    fn compile_int_keyword(&mut self) -> AbstractSyntaxTreeNode {
-        let _int_keyword = self.inner.next().expect("Expected int_keyword but received nothing");
-        let identifier = self.inner.next().expect("Expected identifier but received nothing");
-        let assignment_or_semi_colon = self.inner.next().expect("Expected assignment but received nothing");
+    // Newlines only matter for working out where a node came from, so they
+    // are counted and dropped here rather than handled by every caller
+    fn peek_token(&mut self) -> Option<&Token> {
+        while self.inner.peek() == Some(&Token::NewLine) {
+            self.inner.next();
+            self.line += 1;
+        }
+        self.inner.peek()
+    }
 
-        return match assignment_or_semi_colon {
-            Token::SemiColon => {
-                AbstractSyntaxTreeNode::new_construct(
-                    AbstractSyntaxTreeConstructNodeType::Declaration,
-                    AbstractSyntaxTreeNode::new_leaf_node(
-                        AbstractSyntaxTreeLeafNodeType::from(identifier)
-                    ),
-                )
+    fn next_token(&mut self) -> Option<Token> {
+        self.peek_token();
+        self.inner.next()
+    }
+
+    fn location(&self) -> Location {
+        Location { line: self.line }
+    }
+
+    fn compile_type(&mut self) -> Type {
+        match self.peek_token() {
+            Some(Token::Keyword(KeywordToken::Struct)) => return self.compile_struct(StructKind::Struct),
+            Some(Token::Keyword(KeywordToken::Union)) => return self.compile_struct(StructKind::Union),
+            Some(Token::Keyword(KeywordToken::Enum)) => return self.compile_enum(),
+            _ => {}
+        }
+        if let Some(ty) = self.peek_typedef() {
+            self.skip();
+            return ty;
+        }
+
+        let signedness = match self.peek_token() {
+            Some(Token::Keyword(keyword @ KeywordToken::Signed)) |
+            Some(Token::Keyword(keyword @ KeywordToken::Unsigned)) => {
+                let keyword = *keyword;
+                self.skip();
+                Some(keyword)
             }
-            Token::Assignment => {
-                let expression = self.compile_expression(0);
+            _ => None
+        };
+
+        // `signed` and `unsigned` on their own are short for `signed int` and `unsigned int`
+        let ty = match (self.peek_token(), signedness) {
+            (Some(Token::Keyword(keyword)), _) if Type::try_from(*keyword).is_ok() => {
+                let ty = Type::try_from(*keyword).unwrap();
+                self.skip();
+                ty
+            }
+            (_, Some(_)) => Type::Int,
+            (unhandled, None) => panic!("Error - Expected a type but got: [{:?}]", unhandled)
+        };
+
+        match signedness {
+            Some(KeywordToken::Unsigned) => ty.to_unsigned().unwrap_or_else(|e| panic!("Error - {}", e)),
+            Some(_) if !ty.is_integer() => panic!("Error - Unable to make [{}] signed", ty),
+            _ => ty
+        }
+    }
+
+    // `struct Name` refers to a struct defined earlier, `struct Name { ... }` defines it and
+    // `struct { ... }` defines one that can only be used where it is defined. Unions are the same.
+    fn compile_struct(&mut self, kind: StructKind) -> Type {
+        self.skip();
+        let name = self.compile_tag();
+        let location = self.location();
+
+        if self.peek_token() != Some(&Token::LeftBrace) {
+            let name = name.unwrap_or_else(|| panic!("Error - Expected a {} name on {}", kind, location));
+            return match self.structs.get(&name) {
+                Some(layout) if layout.kind == kind => Type::Struct(layout.clone()),
+                Some(layout) => panic!("Error - [{}] was defined as a {} on {}", name, layout.kind, location),
+                None => panic!("Error - Use of undefined [{} {}] on {}", kind, name, location)
+            };
+        }
+        self.skip();
+
+        let mut members = Vec::new();
+        while self.peek_token() != Some(&Token::RightBrace) {
+            let base_type = self.compile_type();
+            loop {
+                let ty = self.compile_pointers(base_type.clone());
+                let member = match self.next_token() {
+                    Some(Token::Identifier(member)) => member,
+                    unhandled => panic!("Error - Expected a member name but got: [{:?}]", unhandled)
+                };
+                let dimensions = self.compile_dimensions();
+                members.push((member, array_of(ty, &dimensions)));
+
+                match self.next_token() {
+                    Some(Token::Comma) => continue,
+                    Some(Token::SemiColon) => break,
+                    unhandled => panic!("Error - Expected [,] or [;] but got: [{:?}]", unhandled)
+                }
+            }
+        }
+        self.skip();
+
+        let tag = name.clone().unwrap_or_else(|| "<anonymous>".to_string());
+        let layout = Rc::new(Struct::new(kind, tag, members).unwrap_or_else(|e| panic!("Error - {} on {}", e, location)));
+        debug!("Defined {} {:?}", kind, layout);
+        if let Some(name) = name {
+            if self.structs.contains_key(&name) || self.enums.contains(&name) {
+                panic!("Error - Redefinition of [{}] on {}", name, location)
+            }
+            self.structs.insert(name, layout.clone());
+        }
+        Type::Struct(layout)
+    }
+
+    // Enumerators count up from zero or from the value they were last given. Variables of an
+    // enum type are ints.
+    fn compile_enum(&mut self) -> Type {
+        self.skip();
+        let name = self.compile_tag();
+        let location = self.location();
+
+        if self.peek_token() != Some(&Token::LeftBrace) {
+            match name {
+                Some(name) if self.enums.contains(&name) => return Type::Int,
+                Some(name) => panic!("Error - Use of undefined [enum {}] on {}", name, location),
+                None => panic!("Error - Expected an enum name on {}", location),
+            }
+        }
+        self.skip();
+
+        let mut value = 0;
+        while self.peek_token() != Some(&Token::RightBrace) {
+            let enumerator = match self.next_token() {
+                Some(Token::Identifier(enumerator)) => enumerator,
+                unhandled => panic!("Error - Expected an enumerator but got: [{:?}]", unhandled)
+            };
+            if self.peek_token() == Some(&Token::Assignment) {
+                self.skip();
+                value = match self.next_token() {
+                    Some(Token::U64(value)) => value,
+                    unhandled => panic!("Error - Expected the value of [{}] but got: [{:?}]", enumerator, unhandled)
+                };
+            }
+            if value > i32::MAX as u64 {
+                panic!("Error - The value of [{}] does not fit in an int on {}", enumerator, self.location())
+            }
+            self.declare_name(&enumerator);
+            self.constants.insert(enumerator, value);
+            value += 1;
+
+            match self.peek_token() {
+                Some(Token::Comma) => self.skip(),
+                Some(Token::RightBrace) => {}
+                unhandled => panic!("Error - Expected [,] or [}}] but got: [{:?}]", unhandled)
+            }
+        }
+        self.skip();
+
+        if let Some(name) = name {
+            if self.structs.contains_key(&name) || !self.enums.insert(name.clone()) {
+                panic!("Error - Redefinition of [{}] on {}", name, location)
+            }
+        }
+        Type::Int
+    }
+
+    // The type named by the next token if it is a typedef name
+    fn peek_typedef(&mut self) -> Option<Type> {
+        self.peek_token();
+        match self.inner.peek() {
+            Some(Token::Identifier(name)) => self.typedefs.get(name).cloned(),
+            _ => None
+        }
+    }
+
+    fn compile_tag(&mut self) -> Option<String> {
+        match self.peek_token() {
+            Some(Token::Identifier(_)) => match self.next_token() {
+                Some(Token::Identifier(name)) => Some(name),
+                _ => unreachable!(),
+            },
+            _ => None
+        }
+    }
+
+    // Typedef names and enumerators share their names with variables
+    fn declare_name(&mut self, name: &str) {
+        if self.typedefs.contains_key(name) || self.constants.contains_key(name) {
+            panic!("Error - Redeclaration of [{}] on {}", name, self.location())
+        }
+    }
 
-                AbstractSyntaxTreeNode::new_construct(
-                    AbstractSyntaxTreeConstructNodeType::Declaration,
+    fn compile_typedef(&mut self, base_type: Type) {
+        let ty = self.compile_pointers(base_type);
+        let name = match self.next_token() {
+            Some(Token::Identifier(name)) => name,
+            unhandled => panic!("Error - Expected a type name but got: [{:?}]", unhandled)
+        };
+        let ty = array_of(ty, &self.compile_dimensions());
+
+        self.declare_name(&name);
+        debug!("Declared type {:?} as {}", name, ty);
+        self.typedefs.insert(name, ty);
+    }
+
+    fn compile_pointers(&mut self, base_type: Type) -> Type {
+        let mut ty = base_type;
+        while self.peek_token() == Some(&Token::Star) {
+            self.skip();
+            ty = ty.pointer_to();
+        }
+        ty
+    }
+
+    // The length of the outermost dimension can be left out, to be filled in by an initialiser list
+    fn compile_dimensions(&mut self) -> Vec<Option<u32>> {
+        let mut dimensions = Vec::new();
+        while self.peek_token() == Some(&Token::LeftBracket) {
+            self.skip();
+            let length = match self.next_token() {
+                Some(Token::U64(length)) => {
+                    match self.next_token() {
+                        Some(Token::RightBracket) => Some(length as u32),
+                        unhandled => panic!("Error - Expected []] but got: [{:?}]", unhandled)
+                    }
+                }
+                Some(Token::RightBracket) if dimensions.is_empty() => None,
+                unhandled => panic!("Error - Expected an array length but got: [{:?}]", unhandled)
+            };
+            dimensions.push(length);
+        }
+        dimensions
+    }
+
+    fn compile_declaration(&mut self) {
+        let typedef = self.peek_token() == Some(&Token::Keyword(KeywordToken::Typedef));
+        if typedef {
+            self.skip();
+        }
+        let base_type = self.compile_type();
+
+        // A struct, union or enum definition on its own declares no variables
+        if self.peek_token() == Some(&Token::SemiColon) {
+            self.skip();
+            return;
+        }
+
+        loop {
+            if typedef {
+                self.compile_typedef(base_type.clone());
+            } else if self.compile_declarator(base_type.clone()) {
+                // A function definition ends with its body rather than a `;`
+                return;
+            }
+
+            match self.next_token() {
+                Some(Token::Comma) => continue,
+                Some(Token::SemiColon) => return,
+                unhandled => panic!("Error - Expected [,] or [;] but got: [{:?}]", unhandled)
+            }
+        }
+    }
+
+    // Returns whether the declarator was the start of a function definition
+    fn compile_declarator(&mut self, base_type: Type) -> bool {
+        let ty = self.compile_pointers(base_type);
+
+        let identifier = match self.next_token() {
+            Some(Token::Identifier(identifier)) => identifier,
+            unhandled => panic!("Error - Expected identifier but got: [{:?}]", unhandled)
+        };
+        let location = self.location();
+        self.declare_name(&identifier);
+
+        if self.peek_token() == Some(&Token::LeftParen) {
+            self.compile_main(&identifier, ty, location);
+            return true;
+        }
+
+        let mut dimensions = self.compile_dimensions();
+
+        if !dimensions.is_empty() && self.peek_token() == Some(&Token::Assignment) {
+            self.skip();
+            let initialisers = self.compile_initialiser_list();
+            let length = dimensions[0].unwrap_or(initialisers.len() as u32);
+            if initialisers.len() > length as usize {
+                panic!("Error - Too many initialisers for [{}] on {}", identifier, location)
+            }
+            dimensions[0] = Some(length);
+
+            self.pending.push_back(AbstractSyntaxTreeNode::new_construct(
+                self.declaration(),
+                AbstractSyntaxTreeNode::new_typed_leaf_node(
+                    AbstractSyntaxTreeLeafNodeType::Identifier(identifier.clone(), location),
+                    array_of(ty, &dimensions),
+                ),
+            ));
+
+            // Each initialiser becomes an assignment to its element
+            for (index, initialiser) in initialisers.into_iter().enumerate() {
+                self.pending.push_back(AbstractSyntaxTreeNode::new_interior(
+                    AbstractSyntaxTreeExpressionNodeType::Assignment,
                     AbstractSyntaxTreeNode::new_interior(
-                        AbstractSyntaxTreeExpressionNodeType::Assignment,
-                        AbstractSyntaxTreeNode::new_leaf_node(
-                            AbstractSyntaxTreeLeafNodeType::from(identifier)
-                        ),
-                        expression,
+                        AbstractSyntaxTreeExpressionNodeType::Index,
+                        AbstractSyntaxTreeNode::new_leaf_node(AbstractSyntaxTreeLeafNodeType::Identifier(identifier.clone(), location)),
+                        AbstractSyntaxTreeNode::new_leaf_node(AbstractSyntaxTreeLeafNodeType::U64(index as u64)),
                     ),
-                )
+                    initialiser,
+                ));
             }
-            unhandled => panic!("Error - Expected [=] or [;] but got: [{:?}]", unhandled)
-        };
+            return false;
+        }
+
+        let identifier = AbstractSyntaxTreeNode::new_typed_leaf_node(
+            AbstractSyntaxTreeLeafNodeType::Identifier(identifier, location),
+            array_of(ty, &dimensions),
+        );
+
+        if self.peek_token() != Some(&Token::Assignment) {
+            self.pending.push_back(AbstractSyntaxTreeNode::new_construct(
+                self.declaration(),
+                identifier,
+            ));
+            return false;
+        }
+
+        let _assignment = self.next_token();
+        let expression = self.compile_expression(0);
+
+        self.pending.push_back(AbstractSyntaxTreeNode::new_construct(
+            self.declaration(),
+            AbstractSyntaxTreeNode::new_interior(
+                AbstractSyntaxTreeExpressionNodeType::Assignment,
+                identifier,
+                expression,
+            ),
+        ));
+        false
+    }
+
+    // Variables declared in `main` are its own, on the stack, and those before it are globals
+    fn declaration(&self) -> AbstractSyntaxTreeConstructNodeType {
+        if self.in_main {
+            AbstractSyntaxTreeConstructNodeType::Local
+        } else {
+            AbstractSyntaxTreeConstructNodeType::Declaration
+        }
+    }
+
+    // The only function a program can define is `main`, taking either no parameters or the
+    // `argc` and `argv` handed to it at startup. Its statements are parsed one at a time like
+    // those at the top level, with the closing brace picked up in `compile_statement`.
+    fn compile_main(&mut self, name: &str, ty: Type, location: Location) {
+        if name != "main" {
+            panic!("Error - Unable to define function [{}] on {}, only [main] can be defined", name, location)
+        }
+        if self.main_defined {
+            panic!("Error - Redefinition of [main] on {}", location)
+        }
+        if ty != Type::Int {
+            panic!("Error - [main] must return [int] on {}", location)
+        }
+        self.skip();
+
+        let parameters = self.compile_parameters();
+        match parameters.iter().map(|p| p.ty()).collect::<Vec<_>>().as_slice() {
+            [] => {}
+            [argc, argv] if **argc == Type::Int && **argv == Type::Char.pointer_to().pointer_to() => {}
+            _ => panic!("Error - The parameters of [main] must be () or (int, char **) on {}", location)
+        }
+
+        match self.next_token() {
+            Some(Token::LeftBrace) => {}
+            unhandled => panic!("Error - Expected [{{] but got: [{:?}]", unhandled)
+        }
+        for (index, parameter) in parameters.into_iter().enumerate() {
+            self.pending.push_back(AbstractSyntaxTreeNode::new_construct(
+                AbstractSyntaxTreeConstructNodeType::Parameter(index as u32),
+                parameter,
+            ));
+        }
+        self.in_main = true;
+        self.main_defined = true;
+    }
+
+    // Parses a parameter list up to and including the closing parenthesis. A parameter
+    // declared as an array is a pointer to its first element, as it is in C.
+    fn compile_parameters(&mut self) -> Vec<AbstractSyntaxTreeNode> {
+        let mut parameters = Vec::new();
+        if self.peek_token() == Some(&Token::Keyword(KeywordToken::Void)) {
+            self.skip();
+            self.compile_right_paren();
+            return parameters;
+        }
+        if self.peek_token() == Some(&Token::RightParen) {
+            self.skip();
+            return parameters;
+        }
+
+        loop {
+            let base_type = self.compile_type();
+            let ty = self.compile_pointers(base_type);
+            let identifier = match self.next_token() {
+                Some(Token::Identifier(identifier)) => identifier,
+                unhandled => panic!("Error - Expected identifier but got: [{:?}]", unhandled)
+            };
+            let location = self.location();
+            self.declare_name(&identifier);
+
+            let dimensions = self.compile_dimensions();
+            let ty = match dimensions.split_first() {
+                Some((_, inner)) => array_of(ty, inner).pointer_to(),
+                None => ty,
+            };
+            parameters.push(AbstractSyntaxTreeNode::new_typed_leaf_node(
+                AbstractSyntaxTreeLeafNodeType::Identifier(identifier, location),
+                ty,
+            ));
+
+            match self.next_token() {
+                Some(Token::Comma) => continue,
+                Some(Token::RightParen) => return parameters,
+                unhandled => panic!("Error - Expected [,] or [)] but got: [{:?}]", unhandled)
+            }
+        }
+    }
+
+    fn compile_initialiser_list(&mut self) -> Vec<AbstractSyntaxTreeNode> {
+        match self.next_token() {
+            Some(Token::LeftBrace) => {}
+            unhandled => panic!("Error - Expected [{{] but got: [{:?}]", unhandled)
+        }
+
+        let mut initialisers = Vec::new();
+        loop {
+            initialisers.push(self.compile_expression(0));
+            match self.next_token() {
+                Some(Token::Comma) => continue,
+                Some(Token::RightBrace) => return initialisers,
+                unhandled => panic!("Error - Expected [,] or [}}] but got: [{:?}]", unhandled)
+            }
+        }
     }
 
     fn compile_print_keyword(&mut self) -> AbstractSyntaxTreeNode {
-        match self.inner.next() {
+        match self.next_token() {
             Some(Token::Keyword(KeywordToken::Print)) => AbstractSyntaxTreeNode::new_construct(
                 AbstractSyntaxTreeConstructNodeType::Print,
                 self.compile_expression(0),
@@ -60,30 +492,204 @@ impl<T: Iterator<Item=Token>> CodeGenerator<T> {
         }
     }
 
-    fn compile_identifier(&mut self) -> AbstractSyntaxTreeNode {
-        let identifier = self.inner.next().expect("Expected identifier but received nothing");
-        let _assignment = self.inner.next().expect("Expected assignment but received nothing");
+    fn compile_assignment(&mut self) -> AbstractSyntaxTreeNode {
+        let target = self.compile_prefix();
+
+        // A call can be made for its effect alone
+        if let AbstractSyntaxTreeNode::Call(_, _, _) = target {
+            if self.peek_token() != Some(&Token::Assignment) {
+                return target;
+            }
+        }
+
+        match self.next_token() {
+            Some(Token::Assignment) => {}
+            unhandled => panic!("Error - Expected [=] but got: [{:?}]", unhandled)
+        }
         let expression = self.compile_expression(0);
 
         AbstractSyntaxTreeNode::new_interior(
             AbstractSyntaxTreeExpressionNodeType::Assignment,
-            AbstractSyntaxTreeNode::new_leaf_node(
-                AbstractSyntaxTreeLeafNodeType::from(identifier)
-            ),
+            target,
             expression,
         )
     }
 
-    fn compile_expression(&mut self, ptp: u32) -> AbstractSyntaxTreeNode {
-        let mut left = match self.inner.next() {
-            Some(Token::U32(v)) => AbstractSyntaxTreeNode::new_leaf_node(AbstractSyntaxTreeLeafNodeType::U32(v)),
-            Some(Token::Identifier(identifier)) => AbstractSyntaxTreeNode::new_leaf_node(AbstractSyntaxTreeLeafNodeType::Identifier(identifier)),
+    fn compile_prefix(&mut self) -> AbstractSyntaxTreeNode {
+        match self.peek_token() {
+            Some(Token::Ampersand) => {
+                self.skip();
+                AbstractSyntaxTreeNode::new_unary(AbstractSyntaxTreeUnaryNodeType::AddressOf, Type::None, self.compile_prefix())
+            }
+            Some(Token::Star) => {
+                self.skip();
+                AbstractSyntaxTreeNode::new_unary(AbstractSyntaxTreeUnaryNodeType::Dereference, Type::None, self.compile_prefix())
+            }
+            Some(Token::Keyword(KeywordToken::Sizeof)) => self.compile_sizeof(),
+            Some(Token::LeftParen) => {
+                self.skip();
+                // `(type) value` converts the value to the type
+                if self.peek_type_start() {
+                    let ty = self.compile_type_name();
+                    self.compile_right_paren();
+                    return AbstractSyntaxTreeNode::new_unary(AbstractSyntaxTreeUnaryNodeType::Cast, ty, self.compile_prefix());
+                }
+                let expression = self.compile_expression(0);
+                self.compile_right_paren();
+                self.compile_postfix(expression)
+            }
+            _ => {
+                let primary = self.compile_primary();
+                self.compile_postfix(primary)
+            }
+        }
+    }
+
+    fn compile_postfix(&mut self, primary: AbstractSyntaxTreeNode) -> AbstractSyntaxTreeNode {
+        let mut left = primary;
+
+        loop {
+            match self.peek_token() {
+                Some(Token::LeftBracket) => {
+                    self.skip();
+                    let index = self.compile_expression(0);
+                    match self.next_token() {
+                        Some(Token::RightBracket) => {}
+                        unhandled => panic!("Error - Expected []] but got: [{:?}]", unhandled)
+                    }
+                    left = AbstractSyntaxTreeNode::new_interior(AbstractSyntaxTreeExpressionNodeType::Index, left, index);
+                }
+                Some(Token::Dot) => {
+                    self.skip();
+                    let member = self.compile_member_name();
+                    left = AbstractSyntaxTreeNode::new_unary(AbstractSyntaxTreeUnaryNodeType::Member(member), Type::None, left);
+                }
+                // `p->a` is `(*p).a`
+                Some(Token::Arrow) => {
+                    self.skip();
+                    let member = self.compile_member_name();
+                    let value = AbstractSyntaxTreeNode::new_unary(AbstractSyntaxTreeUnaryNodeType::Dereference, Type::None, left);
+                    left = AbstractSyntaxTreeNode::new_unary(AbstractSyntaxTreeUnaryNodeType::Member(member), Type::None, value);
+                }
+                _ => return left,
+            }
+        }
+    }
+
+    fn compile_member_name(&mut self) -> String {
+        match self.next_token() {
+            Some(Token::Identifier(member)) => member,
+            unhandled => panic!("Error - Expected a member name but got: [{:?}]", unhandled)
+        }
+    }
+
+    fn compile_call(&mut self, name: String) -> AbstractSyntaxTreeNode {
+        let location = self.location();
+        let call = AbstractSyntaxTreeCallNodeType::from_name(&name)
+            .unwrap_or_else(|| panic!("Error - Call to unknown function [{}] on {}", name, location));
+        self.skip();
+
+        let mut arguments = Vec::new();
+        if self.peek_token() != Some(&Token::RightParen) {
+            loop {
+                arguments.push(self.compile_expression(0));
+                if self.peek_token() != Some(&Token::Comma) {
+                    break;
+                }
+                self.skip();
+            }
+        }
+        self.compile_right_paren();
+
+        if arguments.len() != call.arity() {
+            panic!("Error - [{}] takes {} arguments but was given {} on {}", name, call.arity(), arguments.len(), location)
+        }
+        AbstractSyntaxTreeNode::new_call(call, arguments)
+    }
+
+    // `sizeof` gives the size of a type, or of the type of an expression without evaluating it.
+    // The size of a type is known while parsing so becomes an `unsigned long` literal straight
+    // away, the size of an expression once it has been type checked.
+    fn compile_sizeof(&mut self) -> AbstractSyntaxTreeNode {
+        self.skip();
+        if self.peek_token() != Some(&Token::LeftParen) {
+            return AbstractSyntaxTreeNode::new_unary(AbstractSyntaxTreeUnaryNodeType::Sizeof, Type::None, self.compile_prefix());
+        }
+        self.skip();
+
+        if self.peek_type_start() {
+            let ty = self.compile_type_name();
+            self.compile_right_paren();
+            if ty.size() == 0 {
+                panic!("Error - Unable to take the size of [{}] on {}", ty, self.location())
+            }
+            return AbstractSyntaxTreeNode::new_typed_leaf_node(AbstractSyntaxTreeLeafNodeType::U64(ty.size() as u64), Type::UnsignedLong);
+        }
+
+        let expression = self.compile_expression(0);
+        self.compile_right_paren();
+        let expression = self.compile_postfix(expression);
+        AbstractSyntaxTreeNode::new_unary(AbstractSyntaxTreeUnaryNodeType::Sizeof, Type::None, expression)
+    }
+
+    // A type without a name being declared, as written in casts and `sizeof`
+    fn compile_type_name(&mut self) -> Type {
+        let base_type = self.compile_type();
+        let ty = self.compile_pointers(base_type);
+        array_of(ty, &self.compile_dimensions())
+    }
+
+    // Whether the next token starts a type rather than an expression
+    fn peek_type_start(&mut self) -> bool {
+        if self.peek_typedef().is_some() {
+            return true;
+        }
+        matches!(
+            self.peek_token(),
+            Some(Token::Keyword(KeywordToken::Int)) |
+            Some(Token::Keyword(KeywordToken::Char)) |
+            Some(Token::Keyword(KeywordToken::Long)) |
+            Some(Token::Keyword(KeywordToken::Void)) |
+            Some(Token::Keyword(KeywordToken::Signed)) |
+            Some(Token::Keyword(KeywordToken::Unsigned)) |
+            Some(Token::Keyword(KeywordToken::Struct)) |
+            Some(Token::Keyword(KeywordToken::Union)) |
+            Some(Token::Keyword(KeywordToken::Enum))
+        )
+    }
+
+    fn compile_right_paren(&mut self) {
+        match self.next_token() {
+            Some(Token::RightParen) => {}
+            unhandled => panic!("Error - Expected [)] but got: [{:?}]", unhandled)
+        }
+    }
+
+    fn compile_primary(&mut self) -> AbstractSyntaxTreeNode {
+        match self.next_token() {
+            Some(Token::U64(v)) => AbstractSyntaxTreeNode::new_leaf_node(AbstractSyntaxTreeLeafNodeType::U64(v)),
+            Some(Token::StringLiteral(string)) => AbstractSyntaxTreeNode::new_leaf_node(AbstractSyntaxTreeLeafNodeType::String(string)),
+            Some(Token::Identifier(identifier)) => match self.constants.get(&identifier).copied() {
+                Some(value) => AbstractSyntaxTreeNode::new_leaf_node(AbstractSyntaxTreeLeafNodeType::U64(value)),
+                None if self.peek_token() == Some(&Token::LeftParen) => self.compile_call(identifier),
+                None => AbstractSyntaxTreeNode::new_leaf_node(AbstractSyntaxTreeLeafNodeType::Identifier(identifier, self.location())),
+            },
             unhandled => panic!("Error - Expected to compile u32 but instead got: {:?}", unhandled)
-        };
+        }
+    }
 
-        while let Some(peeked_token) = self.inner.peek() {
+    fn compile_expression(&mut self, ptp: u32) -> AbstractSyntaxTreeNode {
+        let mut left = self.compile_prefix();
+
+        while let Some(peeked_token) = self.peek_token() {
             if peeked_token.precedence() > ptp {
-                let token = self.inner.next().expect("Need another token to get");
+                let token = self.next_token().expect("Need another token to get");
+
+                if token == Token::QuestionMark {
+                    left = self.compile_conditional(left, token.precedence());
+                    continue;
+                }
+
                 // Recursively call binexpr() with the
                 // precedence of our token to build a sub-tree
                 let right = self.compile_expression(token.precedence());
@@ -95,34 +701,184 @@ impl<T: Iterator<Item=Token>> CodeGenerator<T> {
                 return left;
             }
         }
-        return left;
+        left
+    }
+
+    fn compile_conditional(&mut self, condition: AbstractSyntaxTreeNode, precedence: u32) -> AbstractSyntaxTreeNode {
+        let when_true = self.compile_expression(0);
+
+        match self.next_token() {
+            Some(Token::Colon) => {}
+            unhandled => panic!("Error - Expected [:] but got: [{:?}]", unhandled)
+        }
+
+        // Parse the false arm one level below our own precedence so that
+        // `a ? b : c ? d : e` groups as `a ? b : (c ? d : e)`
+        let when_false = self.compile_expression(precedence - 1);
+
+        AbstractSyntaxTreeNode::new_ternary(
+            AbstractSyntaxTreeTernaryNodeType::Conditional,
+            condition,
+            when_true,
+            when_false,
+        )
+    }
+
+    // Parses the next statement, returning its first node with any others left in `pending`.
+    // Statements such as a lone `;` produce nothing.
+    fn compile_statement(&mut self) -> Option<AbstractSyntaxTreeNode> {
+        if self.in_main && self.peek_token() == Some(&Token::RightBrace) {
+            self.skip();
+            self.in_main = false;
+            return None;
+        }
+        if self.peek_typedef().is_some() {
+            self.compile_declaration();
+            return self.pending.pop_front();
+        }
+
+        let token = self.peek_token().expect("Expected a statement");
+        debug!("Peeked a token: {:?}", token);
+
+        match token {
+            Token::Keyword(KeywordToken::Print) => return Some(self.compile_print_keyword()),
+            Token::Keyword(KeywordToken::Switch) => return Some(self.compile_switch()),
+            Token::Keyword(KeywordToken::Return) => return Some(self.compile_return()),
+            Token::Keyword(KeywordToken::Int) |
+            Token::Keyword(KeywordToken::Char) |
+            Token::Keyword(KeywordToken::Long) |
+            Token::Keyword(KeywordToken::Void) |
+            Token::Keyword(KeywordToken::Signed) |
+            Token::Keyword(KeywordToken::Unsigned) |
+            Token::Keyword(KeywordToken::Struct) |
+            Token::Keyword(KeywordToken::Union) |
+            Token::Keyword(KeywordToken::Enum) |
+            Token::Keyword(KeywordToken::Typedef) => {
+                self.compile_declaration();
+                return self.pending.pop_front();
+            }
+            Token::Identifier(_) | Token::Star | Token::LeftParen => {
+                debug!("Compiling assignment to: {:?}", token);
+                return Some(self.compile_assignment())
+            },
+            Token::Keyword(keyword @ KeywordToken::Case) |
+            Token::Keyword(keyword @ KeywordToken::Default) |
+            Token::Keyword(keyword @ KeywordToken::Break) => {
+                let keyword = *keyword;
+                panic!("Error - [{:?}] outside of a switch on {}", keyword, self.location())
+            }
+            Token::SemiColon => { self.skip() }
+            Token::Assignment => { self.skip() }
+            unhandled => panic!("Unhandled token: [{:?}]", unhandled)
+        };
+        None
+    }
+
+    // `return;` on its own exits successfully
+    fn compile_return(&mut self) -> AbstractSyntaxTreeNode {
+        self.skip();
+        let status = if self.peek_token() == Some(&Token::SemiColon) {
+            AbstractSyntaxTreeNode::new_leaf_node(AbstractSyntaxTreeLeafNodeType::U64(0))
+        } else {
+            self.compile_expression(0)
+        };
+        AbstractSyntaxTreeNode::new_construct(AbstractSyntaxTreeConstructNodeType::Return, status)
+    }
+
+    // The body of a switch is a list of statements with `case` and `default` labels marking
+    // where to start and `break` leaving the switch. Without a `break` control falls through
+    // into the statements of the next label.
+    fn compile_switch(&mut self) -> AbstractSyntaxTreeNode {
+        self.skip();
+        match self.next_token() {
+            Some(Token::LeftParen) => {}
+            unhandled => panic!("Error - Expected [(] but got: [{:?}]", unhandled)
+        }
+        let condition = self.compile_expression(0);
+        match self.next_token() {
+            Some(Token::RightParen) => {}
+            unhandled => panic!("Error - Expected [)] but got: [{:?}]", unhandled)
+        }
+        match self.next_token() {
+            Some(Token::LeftBrace) => {}
+            unhandled => panic!("Error - Expected [{{] but got: [{:?}]", unhandled)
+        }
+
+        let mut statements = Vec::new();
+        let mut default = false;
+        loop {
+            match self.peek_token() {
+                Some(Token::RightBrace) => {
+                    self.skip();
+                    break;
+                }
+                Some(Token::Keyword(KeywordToken::Case)) => {
+                    self.skip();
+                    let location = self.location();
+                    let value = self.compile_expression(0);
+                    self.compile_label_colon();
+                    statements.push(AbstractSyntaxTreeNode::new_unary(AbstractSyntaxTreeUnaryNodeType::Case(location), Type::None, value));
+                }
+                Some(Token::Keyword(KeywordToken::Default)) => {
+                    self.skip();
+                    if default {
+                        panic!("Error - Multiple default labels in one switch on {}", self.location())
+                    }
+                    default = true;
+                    self.compile_label_colon();
+                    statements.push(AbstractSyntaxTreeNode::new_leaf_node(AbstractSyntaxTreeLeafNodeType::Default));
+                }
+                Some(Token::Keyword(KeywordToken::Break)) => {
+                    self.skip();
+                    statements.push(AbstractSyntaxTreeNode::new_leaf_node(AbstractSyntaxTreeLeafNodeType::Break));
+                }
+                Some(_) => {
+                    statements.extend(self.compile_statement());
+                    statements.extend(self.pending.drain(..));
+                }
+                None => panic!("Error - Expected [{}] to end the switch but reached the end of the file", "}")
+            }
+        }
+
+        AbstractSyntaxTreeNode::new_compound(AbstractSyntaxTreeCompoundNodeType::Switch, condition, statements)
+    }
+
+    fn compile_label_colon(&mut self) {
+        match self.next_token() {
+            Some(Token::Colon) => {}
+            unhandled => panic!("Error - Expected [:] but got: [{:?}]", unhandled)
+        }
     }
 
     fn skip(&mut self) {
-        let token = self.inner.next();
+        let token = self.next_token();
         debug!("Skipping token: {:?}", token)
     }
 }
 
+// `int a[2][3]` is an array of two arrays of three ints
+fn array_of(element: Type, dimensions: &[Option<u32>]) -> Type {
+    dimensions.iter().rev().fold(element, |ty, length| {
+        Type::Array(Box::new(ty), length.expect("Error - Only the first array dimension can be left empty"))
+    })
+}
+
 impl<T: Iterator<Item=Token>> Iterator for CodeGenerator<T> {
     type Item = AbstractSyntaxTreeNode;
     fn next(&mut self) -> Option<AbstractSyntaxTreeNode> {
-        while let Some(token) = self.inner.peek() {
-            debug!("Peeked a token: {:?}", token);
-
-            match token {
-                Token::Keyword(KeywordToken::Print) => return Some(self.compile_print_keyword()),
-                Token::Keyword(KeywordToken::Int) => return Some(self.compile_int_keyword()),
-                Token::Identifier(identifier) => {
-                    debug!("Compiling identifier: {:?}", identifier);
-                    return Some(self.compile_identifier())
-                },
-                Token::SemiColon => { self.skip() }
-                Token::NewLine => { self.skip() }
-                Token::Assignment => { self.skip() }
-                unhandled => panic!("Unhandled token: [{:?}]", unhandled)
-            };
+        // A single statement such as `int a, b;` can produce several nodes
+        if let Some(node) = self.pending.pop_front() {
+            return Some(node);
+        }
+
+        while self.peek_token().is_some() {
+            if let Some(node) = self.compile_statement() {
+                return Some(node);
+            }
+        }
+        if self.in_main {
+            panic!("Error - Expected [}}] to end [main] on {}", self.location())
         }
-        return None;
+        None
     }
-}
\ No newline at end of file
+}
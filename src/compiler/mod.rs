@@ -1,16 +1,21 @@
 use std::fs;
 use std::fs::File;
-use std::io::Write;
+use std::marker::PhantomData;
 use std::str::Chars;
 
 use log::debug;
 
-use crate::asm::{cgadd, cgcomment, cgdiv, cgglobsym, cgload, cgloadglob, cgmul, cgpostamble, cgpreamble, cgprintint, cgstorglob, cgsub, cglessthan};
-use crate::asm::registers::{RegisterIndex, Registers};
+use crate::asm::backend::{Backend, BinaryOperation, Comparison, Function};
+use crate::asm::labels::LabelIndex;
+use crate::asm::syntax::Syntax;
+use crate::asm::target::Target;
 use crate::ast::*;
 use crate::ast::AbstractSyntaxTreeNode;
 use crate::compiler::code_generator::CodeGenerator;
+use crate::emit::Emit;
 use crate::scanner::{TokenIterator, Token};
+use crate::symbols::{Storage, SymbolId, SymbolTable};
+use crate::types::{Type, TypeChecker};
 use std::path::Path;
 
 pub mod code_generator;
@@ -21,18 +26,19 @@ pub enum Error {
     Error(String),
 }
 
-pub struct Compiler {}
-
-impl Compiler {
-    pub fn new() -> Compiler {
-        Compiler {}
-    }
+/// A source file that has been parsed, had its names resolved and been type checked, ready for
+/// code to be generated from it
+pub struct Program {
+    /// The stem of the source file, which names the files written for it
+    pub name: String,
+    pub symbols: SymbolTable,
+    pub statements: Vec<AbstractSyntaxTreeNode>,
+}
 
-    pub fn compile<P: AsRef<Path>>(&self, path: P) -> core::result::Result<(), Box<dyn std::error::Error>> {
+impl Program {
+    pub fn parse<P: AsRef<Path>>(path: P) -> core::result::Result<Program, Box<dyn std::error::Error>> {
         let file = path.as_ref();
-        debug!("Compiling file: {:?}", file);
-
-        let file_name = file.file_stem().unwrap().to_os_string().into_string().unwrap();
+        let name = file.file_stem().unwrap().to_os_string().into_string().unwrap();
 
         let content = fs::read_to_string(file).unwrap(); // FIXME
         let chars: Chars = content.chars();
@@ -41,142 +47,420 @@ impl Compiler {
 
         let code_generator = CodeGenerator::new(tokens);
 
-        let mut out = File::create(format!("{}.s", file_name))?;
-        let mut registers = Registers::new();
-
-        cgpreamble(out.by_ref())?;
-        cgcomment(out.by_ref(), "Starting users code")?;
+        let mut symbols = SymbolTable::new();
+        let mut statements = Vec::new();
 
         for code in code_generator {
             debug!("Abstract Syntax Tree: {:#?}", code);
-            self.interpret_ast_to_asm(out.by_ref(), &mut registers, code)?;
-            registers.free_all();
+            let code = symbols.resolve(code)?;
+            let code = TypeChecker::new(&symbols).check(code)?;
+            statements.push(code);
+        }
+
+        Ok(Program { name, symbols, statements })
+    }
+}
+
+pub struct Compiler<B: Backend<File>> {
+    /// Whether array indexes are checked against the array length at run time
+    bounds_checking: bool,
+    /// The platform the assembly is written for
+    target: Target,
+    /// How the instructions are written, where the instruction set has more than one way
+    syntax: Syntax,
+    /// What is written for the program, which the backend has to be able to write
+    emit: Emit,
+    backend: PhantomData<B>,
+}
+
+impl<B: Backend<File>> Default for Compiler<B> {
+    fn default() -> Self {
+        Compiler::new()
+    }
+}
+
+impl<B: Backend<File>> Compiler<B> {
+    pub fn new() -> Compiler<B> {
+        Compiler {
+            bounds_checking: false,
+            target: Target::host(),
+            syntax: Syntax::Att,
+            emit: Emit::Assembly,
+            backend: PhantomData,
+        }
+    }
+
+    pub fn with_bounds_checking(self, enabled: bool) -> Compiler<B> {
+        Compiler {
+            bounds_checking: enabled,
+            ..self
+        }
+    }
+
+    pub fn with_target(self, target: Target) -> Compiler<B> {
+        Compiler {
+            target,
+            ..self
+        }
+    }
+
+    pub fn with_syntax(self, syntax: Syntax) -> Compiler<B> {
+        Compiler {
+            syntax,
+            ..self
+        }
+    }
+
+    pub fn with_emit(self, emit: Emit) -> Compiler<B> {
+        Compiler {
+            emit,
+            ..self
+        }
+    }
+
+    pub fn compile<P: AsRef<Path>>(&self, path: P) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        let file = path.as_ref();
+        debug!("Compiling file: {:?}", file);
+
+        let program = Program::parse(file)?;
+        let file_name = program.name.clone();
+
+        // A source file named like the output would otherwise be replaced by it
+        let output = format!("{}.{}", file_name, self.emit.extension());
+        if Path::new(&output).exists() && fs::canonicalize(&output)? == fs::canonicalize(file)? {
+            return Err(format!("Unable to write [{}] as it is the source file", output).into());
         }
 
-        cgcomment(out.by_ref(), "Ending users code")?;
-        cgpostamble(out.by_ref())?;
+        let out = File::create(&output)?;
+        let mut backend = B::new(self.target, out);
+        backend.set_syntax(self.syntax)?;
+        if self.emit == Emit::Object {
+            backend.set_object_output()?;
+        }
+
+        backend.survey(&program)?;
+        backend.prologue()?;
+        backend.comment("Starting users code")?;
+
+        for code in program.statements {
+            self.interpret_statement_to_asm(&mut backend, &program.symbols, code)?;
+        }
+
+        backend.comment("Ending users code")?;
+        backend.epilogue()?;
+
+        // Only assembly and objects are linked, and those for another platform are left for that
+        // platform's toolchain to build
+        if !matches!(self.emit, Emit::Assembly | Emit::Object) || self.target != Target::host() {
+            return Ok(());
+        }
 
         use std::process::Command;
 
         let mut cc = Command::new("cc");
         cc.arg("-o");
         cc.arg(&file_name);
-        cc.arg(format!("{}.s", file_name));
+        cc.arg(&output);
 
         debug!("command: {:?}", cc);
 
-        let result = cc.spawn().unwrap();
-
-        // .arg("-l")
-            // .arg("-a");
-        // .spawn()
-        // .expect("ls command failed to start");
+        let result = cc.status()?;
 
         debug!("result: {:?}", result);
 
+        if !result.success() {
+            return Err(format!("Unable to build [{}] from [{}], cc failed with [{}]", file_name, output, result).into());
+        }
 
         Ok(())
     }
 
-    fn interpret_ast_to_asm<W: Write>(&self, w: &mut W, registers: &mut Registers, ast: AbstractSyntaxTreeNode) -> core::result::Result<Option<RegisterIndex>, Box<dyn std::error::Error>> {
+    // Runs a statement for what it does, throwing away any value it gives
+    fn interpret_statement_to_asm(&self, backend: &mut B, symbols: &SymbolTable, ast: AbstractSyntaxTreeNode) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        if let Some(r) = self.interpret_ast_to_asm(backend, symbols, ast)? {
+            backend.discard(r)?;
+        }
+        backend.free_all_registers();
+        Ok(())
+    }
+
+    // Computes the value of a node that must have one into a register
+    fn interpret_value_to_asm(&self, backend: &mut B, symbols: &SymbolTable, ast: AbstractSyntaxTreeNode) -> core::result::Result<B::Register, Box<dyn std::error::Error>> {
+        // Only statements and exit() have no value, so only they are written out for the error
+        // rather than every expression as it is computed
+        let statement = match ast {
+            AbstractSyntaxTreeNode::Construct(_, _) |
+            AbstractSyntaxTreeNode::Compound(_, _, _) |
+            AbstractSyntaxTreeNode::Call(AbstractSyntaxTreeCallNodeType::Exit, _, _) => Some(format!("{:?}", ast)),
+            _ => None,
+        };
+        self.interpret_ast_to_asm(backend, symbols, ast)?
+            .ok_or_else(|| format!("Expected a value but got: {}", statement.unwrap_or_else(|| "an expression".to_string())).into())
+    }
+
+    // Computes the value of a node into a register, or gives `None` for a node that has no value
+    fn interpret_ast_to_asm(&self, backend: &mut B, symbols: &SymbolTable, ast: AbstractSyntaxTreeNode) -> core::result::Result<Option<B::Register>, Box<dyn std::error::Error>> {
         debug!("Interpreting abstract syntax tree: {:?}", ast);
-        return match ast {
-            AbstractSyntaxTreeNode::Expression(AbstractSyntaxTreeExpressionNodeType::Add, left, right) =>
-                Ok(
-                    Some(
-                        cgadd(
-                            self.interpret_ast_to_asm(w, registers, *left)?.expect("Expected a value to be placed in a register"),
-                            self.interpret_ast_to_asm(w, registers, *right)?.expect("Expected a value to be placed in a register"),
-                            registers,
-                            w,
-                        )?
-                    )
-                ),
-            AbstractSyntaxTreeNode::Expression(AbstractSyntaxTreeExpressionNodeType::Subtract, left, right) =>
-                Ok(
-                    Some(
-                        cgsub(
-                            self.interpret_ast_to_asm(w, registers, *left)?.expect("Expected a value to be placed in a register"),
-                            self.interpret_ast_to_asm(w, registers, *right)?.expect("Expected a value to be placed in a register"),
-                            registers,
-                            w,
-                        )?
-                    )
-                ),
-            AbstractSyntaxTreeNode::Expression(AbstractSyntaxTreeExpressionNodeType::LessThan, left, right) => {
-                Ok(
-                    Some(
-                        cglessthan(
-                            self.interpret_ast_to_asm(w, registers, *left)?.expect("Expected a value to be placed in a register"),
-                            self.interpret_ast_to_asm(w, registers, *right)?.expect("Expected a value to be placed in a register"),
-                            registers,
-                            w,
-                        )?
-                    )
-                )
-            }
-            AbstractSyntaxTreeNode::Expression(AbstractSyntaxTreeExpressionNodeType::Multiply, left, right) => {
-                Ok(
-                    Some(
-                        cgmul(
-                            self.interpret_ast_to_asm(w, registers, *left)?.expect("Expected a value to be placed in a register"),
-                            self.interpret_ast_to_asm(w, registers, *right)?.expect("Expected a value to be placed in a register"),
-                            registers,
-                            w,
-                        )?
-                    )
-                )
-            }
-            AbstractSyntaxTreeNode::Expression(AbstractSyntaxTreeExpressionNodeType::Divide, left, right) =>
-                Ok(
-                    Some(
-                        cgdiv(
-                            self.interpret_ast_to_asm(w, registers, *left)?.expect("Expected a value to be placed in a register"),
-                            self.interpret_ast_to_asm(w, registers, *right)?.expect("Expected a value to be placed in a register"),
-                            registers,
-                            w,
-                        )?
-                    )
-                ),
-            AbstractSyntaxTreeNode::Leaf(AbstractSyntaxTreeLeafNodeType::U32(i)) =>
-                Ok(
-                    Some(
-                        cgload(
-                            i,
-                            registers,
-                            w,
-                        )?
-                    )
-                ),
+        match ast {
+            AbstractSyntaxTreeNode::Expression(AbstractSyntaxTreeExpressionNodeType::Add, ty, left, right) =>
+                self.interpret_binary_to_asm(backend, symbols, BinaryOperation::Add, &ty, *left, *right).map(Some),
+            AbstractSyntaxTreeNode::Expression(AbstractSyntaxTreeExpressionNodeType::Subtract, ty, left, right) =>
+                self.interpret_binary_to_asm(backend, symbols, BinaryOperation::Subtract, &ty, *left, *right).map(Some),
+            AbstractSyntaxTreeNode::Expression(AbstractSyntaxTreeExpressionNodeType::Multiply, ty, left, right) =>
+                self.interpret_binary_to_asm(backend, symbols, BinaryOperation::Multiply, &ty, *left, *right).map(Some),
+            AbstractSyntaxTreeNode::Expression(AbstractSyntaxTreeExpressionNodeType::Divide, ty, left, right) =>
+                self.interpret_binary_to_asm(backend, symbols, BinaryOperation::Divide, &ty, *left, *right).map(Some),
+            AbstractSyntaxTreeNode::Expression(AbstractSyntaxTreeExpressionNodeType::LeftShift, ty, left, right) =>
+                self.interpret_binary_to_asm(backend, symbols, BinaryOperation::LeftShift, &ty, *left, *right).map(Some),
+            // Shifting right can not leave bits above the width of the type so the result needs no extending
+            AbstractSyntaxTreeNode::Expression(AbstractSyntaxTreeExpressionNodeType::RightShift, ty, left, right) => {
+                let left = self.interpret_value_to_asm(backend, symbols, *left)?;
+                let right = self.interpret_value_to_asm(backend, symbols, *right)?;
+                backend.binary(BinaryOperation::RightShift, left, right, &ty).map(Some)
+            }
+            AbstractSyntaxTreeNode::Expression(AbstractSyntaxTreeExpressionNodeType::LessThan, _, left, right) =>
+                self.interpret_comparison_to_asm(backend, symbols, Comparison::LessThan, *left, *right).map(Some),
+            AbstractSyntaxTreeNode::Expression(AbstractSyntaxTreeExpressionNodeType::LessThanEqual, _, left, right) =>
+                self.interpret_comparison_to_asm(backend, symbols, Comparison::LessThanEqual, *left, *right).map(Some),
+            AbstractSyntaxTreeNode::Expression(AbstractSyntaxTreeExpressionNodeType::GreaterThan, _, left, right) =>
+                self.interpret_comparison_to_asm(backend, symbols, Comparison::GreaterThan, *left, *right).map(Some),
+            AbstractSyntaxTreeNode::Expression(AbstractSyntaxTreeExpressionNodeType::GreaterThanEqual, _, left, right) =>
+                self.interpret_comparison_to_asm(backend, symbols, Comparison::GreaterThanEqual, *left, *right).map(Some),
+            AbstractSyntaxTreeNode::Expression(AbstractSyntaxTreeExpressionNodeType::NotEqual, _, left, right) =>
+                self.interpret_comparison_to_asm(backend, symbols, Comparison::NotEqual, *left, *right).map(Some),
+            AbstractSyntaxTreeNode::Expression(AbstractSyntaxTreeExpressionNodeType::Equality, _, left, right) =>
+                self.interpret_comparison_to_asm(backend, symbols, Comparison::Equal, *left, *right).map(Some),
+            AbstractSyntaxTreeNode::Compound(AbstractSyntaxTreeCompoundNodeType::Switch, condition, statements) => {
+                self.interpret_switch_to_asm(backend, symbols, *condition, statements)?;
+                Ok(None)
+            }
+            AbstractSyntaxTreeNode::Ternary(AbstractSyntaxTreeTernaryNodeType::Conditional, _, condition, when_true, when_false) =>
+                self.interpret_conditional_to_asm(backend, symbols, *condition, *when_true, *when_false).map(Some),
+            AbstractSyntaxTreeNode::Unary(AbstractSyntaxTreeUnaryNodeType::Convert, ty, left) => {
+                let from = left.ty().clone();
+                let r = self.interpret_value_to_asm(backend, symbols, *left)?;
+                backend.convert(r, &from, &ty).map(Some)
+            }
+            AbstractSyntaxTreeNode::Unary(AbstractSyntaxTreeUnaryNodeType::BoundsCheck(length, location), _, left) => {
+                let r = self.interpret_value_to_asm(backend, symbols, *left)?;
+                if !self.bounds_checking {
+                    return Ok(Some(r));
+                }
+                backend.bounds_check(r, length, location.line).map(Some)
+            }
+            AbstractSyntaxTreeNode::Leaf(AbstractSyntaxTreeLeafNodeType::U64(i), ty) =>
+                backend.load(i, &ty).map(Some),
             AbstractSyntaxTreeNode::Construct(AbstractSyntaxTreeConstructNodeType::Print, left) => {
-                cgprintint(
-                    self.interpret_ast_to_asm(w, registers, *left)?.expect("Expected a value to be placed in a register"),
-                    w,
-                )?;
+                let ty = left.ty().promote();
+                let r = self.interpret_value_to_asm(backend, symbols, *left)?;
+                let function = if ty.is_pointer() {
+                    Function::PrintString
+                } else if ty.is_signed() {
+                    Function::PrintInt
+                } else {
+                    Function::PrintUnsignedInt
+                };
+                backend.call(function, &[r])?;
+                Ok(None)
+            }
+            AbstractSyntaxTreeNode::Call(AbstractSyntaxTreeCallNodeType::Read, _, arguments) => {
+                let pointer = arguments.into_iter().next().ok_or("Expected a pointer to read into")?;
+                let ty = match pointer.ty() {
+                    Type::Pointer(to) => (**to).clone(),
+                    unhandled => return Err(format!("Expected a pointer to read into but got: {:?}", unhandled).into()),
+                };
+                let r = self.interpret_value_to_asm(backend, symbols, pointer)?;
+                backend.call(Function::ReadInt(ty), &[r])
+            }
+            AbstractSyntaxTreeNode::Call(AbstractSyntaxTreeCallNodeType::Exit, _, arguments) => {
+                let status = arguments.into_iter().next().ok_or("Expected an exit status")?;
+                let r = self.interpret_value_to_asm(backend, symbols, status)?;
+                backend.call(Function::Exit, &[r])
+            }
+            AbstractSyntaxTreeNode::Construct(AbstractSyntaxTreeConstructNodeType::Return, left) => {
+                let r = self.interpret_value_to_asm(backend, symbols, *left)?;
+                backend.return_value(r)?;
                 Ok(None)
             }
-            AbstractSyntaxTreeNode::Construct(AbstractSyntaxTreeConstructNodeType::Declaration, left) => {
-                self.interpret_ast_to_asm(w, registers, *left)
+            AbstractSyntaxTreeNode::Call(AbstractSyntaxTreeCallNodeType::ReadLine, _, arguments) => {
+                let mut arguments = arguments.into_iter();
+                let buffer = self.interpret_value_to_asm(backend, symbols, arguments.next().ok_or("Expected a buffer")?)?;
+                let size = self.interpret_value_to_asm(backend, symbols, arguments.next().ok_or("Expected a size")?)?;
+                backend.call(Function::ReadLine, &[buffer, size])
             }
-            AbstractSyntaxTreeNode::Expression(AbstractSyntaxTreeExpressionNodeType::Assignment, left, right) => {
+            AbstractSyntaxTreeNode::Leaf(AbstractSyntaxTreeLeafNodeType::String(string), _) =>
+                backend.load_string(&string).map(Some),
+            AbstractSyntaxTreeNode::Construct(AbstractSyntaxTreeConstructNodeType::Parameter(_), parameter) => {
+                match *parameter {
+                    AbstractSyntaxTreeNode::Leaf(AbstractSyntaxTreeLeafNodeType::Symbol(id, _), _) => backend.declare_parameter(symbols.symbol(id))?,
+                    unhandled => return Err(format!("Unhandled abstract syntax tree element: {:?}", unhandled).into()),
+                }
+                Ok(None)
+            }
+            AbstractSyntaxTreeNode::Construct(AbstractSyntaxTreeConstructNodeType::Declaration, left) |
+            AbstractSyntaxTreeNode::Construct(AbstractSyntaxTreeConstructNodeType::Local, left) => {
                 match *left {
-                    AbstractSyntaxTreeNode::Leaf(AbstractSyntaxTreeLeafNodeType::Identifier(identifier)) => {
-                        cgglobsym(&identifier, w)?;
-
-                        let index = cgstorglob(
-                            &identifier,
-                            self.interpret_ast_to_asm(w, registers, *right)?.expect("Expected a value to be placed in a register"),
-                            w,
-                        );
-                        index.map(Some)
+                    AbstractSyntaxTreeNode::Leaf(AbstractSyntaxTreeLeafNodeType::Symbol(id, _), _) => {
+                        self.interpret_declaration_to_asm(backend, symbols, id)?;
+                        Ok(None)
+                    }
+                    AbstractSyntaxTreeNode::Expression(AbstractSyntaxTreeExpressionNodeType::Assignment, _, ref symbol, _) => {
+                        match **symbol {
+                            AbstractSyntaxTreeNode::Leaf(AbstractSyntaxTreeLeafNodeType::Symbol(id, _), _) => self.interpret_declaration_to_asm(backend, symbols, id)?,
+                            ref unhandled => return Err(format!("Unhandled abstract syntax tree element: {:?}", unhandled).into()),
+                        }
+                        self.interpret_ast_to_asm(backend, symbols, *left)
                     }
-                    unhandled => panic!("Unhandled abstract syntax tree element: {:?}", unhandled),
+                    unhandled => Err(format!("Unhandled abstract syntax tree element: {:?}", unhandled).into()),
                 }
             }
-            AbstractSyntaxTreeNode::Leaf(AbstractSyntaxTreeLeafNodeType::Identifier(identifier)) => {
-                cgloadglob(&identifier, registers, w).map(Some)
+            AbstractSyntaxTreeNode::Expression(AbstractSyntaxTreeExpressionNodeType::Assignment, _, left, right) => {
+                match *left {
+                    AbstractSyntaxTreeNode::Leaf(AbstractSyntaxTreeLeafNodeType::Symbol(id, _), ty) => {
+                        let value = self.interpret_value_to_asm(backend, symbols, *right)?;
+                        backend.store_symbol(symbols.symbol(id), value, &ty).map(Some)
+                    }
+                    AbstractSyntaxTreeNode::Unary(AbstractSyntaxTreeUnaryNodeType::Dereference, ty, pointer) => {
+                        let value = self.interpret_value_to_asm(backend, symbols, *right)?;
+                        let pointer = self.interpret_value_to_asm(backend, symbols, *pointer)?;
+                        backend.store_dereference(value, pointer, &ty).map(Some)
+                    }
+                    unhandled => Err(format!("Unhandled abstract syntax tree element: {:?}", unhandled).into()),
+                }
+            }
+            AbstractSyntaxTreeNode::Unary(AbstractSyntaxTreeUnaryNodeType::AddressOf, ty, left) => {
+                match *left {
+                    AbstractSyntaxTreeNode::Leaf(AbstractSyntaxTreeLeafNodeType::Symbol(id, _), _) =>
+                        backend.address_of(symbols.symbol(id), &ty).map(Some),
+                    unhandled => Err(format!("Unhandled abstract syntax tree element: {:?}", unhandled).into()),
+                }
+            }
+            AbstractSyntaxTreeNode::Unary(AbstractSyntaxTreeUnaryNodeType::Dereference, ty, left) => {
+                let pointer = self.interpret_value_to_asm(backend, symbols, *left)?;
+                backend.load_dereference(pointer, &ty).map(Some)
+            }
+            AbstractSyntaxTreeNode::Leaf(AbstractSyntaxTreeLeafNodeType::Symbol(id, _), ty) =>
+                backend.load_symbol(symbols.symbol(id), &ty).map(Some),
+            unhandled => Err(format!("Unhandled abstract syntax tree element: {:?}", unhandled).into()),
+        }
+    }
+
+    fn interpret_declaration_to_asm(&self, backend: &mut B, symbols: &SymbolTable, id: SymbolId) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        let symbol = symbols.symbol(id);
+        match symbol.storage {
+            Storage::Global => backend.declare_global(symbol),
+            Storage::Frame(_) => backend.declare_local(symbol),
+        }
+    }
+
+    // Arithmetic is done on whole registers, so the result is extended from the width of its type
+    // to throw away anything carried past it
+    fn interpret_binary_to_asm(&self, backend: &mut B, symbols: &SymbolTable, operation: BinaryOperation, ty: &Type, left: AbstractSyntaxTreeNode, right: AbstractSyntaxTreeNode) -> core::result::Result<B::Register, Box<dyn std::error::Error>> {
+        let left = self.interpret_value_to_asm(backend, symbols, left)?;
+        // A constant is handed over as it is, so that the backend can avoid loading it into a register
+        let r = match constant(&right, ty) {
+            Some(value) => backend.binary_constant(operation, left, value, ty)?,
+            None => {
+                let right = self.interpret_value_to_asm(backend, symbols, right)?;
+                backend.binary(operation, left, right, ty)?
             }
-            unhandled => panic!("Unhandled abstract syntax tree element: {:?}", unhandled),
         };
+        backend.extend(r, ty)
+    }
+
+    // Comparisons depend on the type of their operands rather than their boolean result
+    fn interpret_comparison_to_asm(&self, backend: &mut B, symbols: &SymbolTable, comparison: Comparison, left: AbstractSyntaxTreeNode, right: AbstractSyntaxTreeNode) -> core::result::Result<B::Register, Box<dyn std::error::Error>> {
+        let operands = left.ty().clone();
+        let left = self.interpret_value_to_asm(backend, symbols, left)?;
+        let right = self.interpret_value_to_asm(backend, symbols, right)?;
+        backend.compare(comparison, left, right, &operands)
+    }
+
+    // Dispatches to the label of the matching case, then writes the statements with their labels
+    // in place so control falls through from one case to the next
+    fn interpret_switch_to_asm(&self, backend: &mut B, symbols: &SymbolTable, condition: AbstractSyntaxTreeNode, statements: Vec<AbstractSyntaxTreeNode>) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        let ty = condition.ty().clone();
+        let r = self.interpret_value_to_asm(backend, symbols, condition)?;
+
+        let end = backend.allocate_label();
+        let mut default = end;
+        let mut cases = Vec::new();
+        let mut targets = Vec::new();
+        for statement in &statements {
+            targets.push(match statement {
+                AbstractSyntaxTreeNode::Leaf(AbstractSyntaxTreeLeafNodeType::Case(value), _) => {
+                    let label = backend.allocate_label();
+                    cases.push((*value, label));
+                    Some(label)
+                }
+                AbstractSyntaxTreeNode::Leaf(AbstractSyntaxTreeLeafNodeType::Default, _) => {
+                    default = backend.allocate_label();
+                    Some(default)
+                }
+                _ => None,
+            });
+        }
+
+        let labels = targets.iter().flatten().copied().collect::<Vec<_>>();
+        backend.switch(r, &ty, &cases, &labels, default, end)?;
+
+        for (statement, target) in statements.into_iter().zip(targets) {
+            match (statement, target) {
+                (_, Some(label)) => backend.label(label)?,
+                (AbstractSyntaxTreeNode::Leaf(AbstractSyntaxTreeLeafNodeType::Break, _), _) => backend.jump(end)?,
+                (statement, None) => self.interpret_statement_to_asm(backend, symbols, statement)?,
+            }
+        }
+        backend.label(end)?;
+        Ok(())
     }
-}
\ No newline at end of file
+
+    fn interpret_conditional_to_asm(&self, backend: &mut B, symbols: &SymbolTable, condition: AbstractSyntaxTreeNode, when_true: AbstractSyntaxTreeNode, when_false: AbstractSyntaxTreeNode) -> core::result::Result<B::Register, Box<dyn std::error::Error>> {
+        // When neither arm can be observed being evaluated we can compute both and pick one with a
+        // conditional move, otherwise only the chosen arm may run so we branch around the other.
+        // Evaluating the arms before the condition keeps fewer registers live when conditionals nest,
+        // which is only allowed when the condition has no side effects the arms could see.
+        if !condition.has_side_effects() && !when_true.has_side_effects() && !when_false.has_side_effects() {
+            let when_true = self.interpret_value_to_asm(backend, symbols, when_true)?;
+            let when_false = self.interpret_value_to_asm(backend, symbols, when_false)?;
+            let condition = self.interpret_value_to_asm(backend, symbols, condition)?;
+            return backend.select(condition, when_true, when_false);
+        }
+
+        let condition = self.interpret_value_to_asm(backend, symbols, condition)?;
+        backend.conditional(
+            condition,
+            |backend| self.interpret_value_to_asm(backend, symbols, when_true),
+            |backend| self.interpret_value_to_asm(backend, symbols, when_false),
+        )
+    }
+}
+
+// The value of a constant of type `ty`, including one converted from a type `ty` can represent,
+// which leaves its value as it is
+fn constant(ast: &AbstractSyntaxTreeNode, ty: &Type) -> Option<u64> {
+    match ast {
+        AbstractSyntaxTreeNode::Leaf(AbstractSyntaxTreeLeafNodeType::U64(value), constant) if constant == ty => Some(*value),
+        AbstractSyntaxTreeNode::Unary(AbstractSyntaxTreeUnaryNodeType::Convert, to, left) if to == ty => match &**left {
+            AbstractSyntaxTreeNode::Leaf(AbstractSyntaxTreeLeafNodeType::U64(value), from) if ty.can_represent(from) => Some(*value),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The lowest case value and the length of a jump table covering every case, if one is worth
+/// it: there need to be a few cases and they need to fill at least a third of the table.
+pub(crate) fn jump_table_range(cases: &[(u64, LabelIndex)], signed: bool) -> Option<(u64, u64)> {
+    if cases.len() < 4 {
+        return None;
+    }
+    let values = cases.iter().map(|(value, _)| if signed { *value as i64 as i128 } else { *value as i128 });
+    let low = values.clone().min().unwrap();
+    let high = values.max().unwrap();
+    let length = high - low + 1;
+    if length > 3 * cases.len() as i128 {
+        return None;
+    }
+    Some((low as u64, length as u64))
+}
@@ -0,0 +1,828 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::rc::Rc;
+
+use crate::asm::backend::{Backend, BinaryOperation, Comparison, Function};
+use crate::asm::labels::{LabelIndex, Labels};
+use crate::asm::target::Target;
+use crate::symbols::Symbol;
+use crate::types::{Struct, Type};
+
+// Reads characters into the buffer until a newline, the end of the input or the buffer has room
+// only for the terminating zero
+const READ_LINE: &str = "/* Reads a line into the buffer without its newline, giving the length of the line or -1 at the end of the input */
+static long read_line(signed char *buffer, long size) {
+    long length = 0;
+    while (length < size - 1) {
+        int c = getchar();
+        if (c == EOF) {
+            buffer[length] = 0;
+            return length == 0 ? -1 : length;
+        }
+        if (c == '\\n') {
+            break;
+        }
+        buffer[length++] = c;
+    }
+    buffer[length] = 0;
+    return length;
+}
+";
+
+const CHECK_BOUNDS: &str = "/* Gives the index when it is within an array of the given length, otherwise reports the line and aborts */
+static long check_bounds(long index, long length, long line) {
+    if (index < 0 || index >= length) {
+        printf(\"Array index out of bounds on line %ld\\n\", line);
+        fflush(stdout);
+        abort();
+    }
+    return index;
+}
+";
+
+/// How tightly an expression binds, so that it is only put in parentheses where C needs them
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+enum Precedence {
+    /// Names, literals, calls, indexing and member access
+    Primary,
+    /// Casts, dereferences, taking an address and negative literals
+    Unary,
+    /// Binary operators, conditionals and assignment, which are always put in parentheses when
+    /// they are the operand of another
+    Binary,
+}
+
+/// Writes a program as C99 source, for running it anywhere there is a C compiler and checking
+/// the semantics of the language against those of C. Variables keep their own types and the
+/// program becomes `main`. The type checker has already scaled pointer arithmetic and turned
+/// indexing and member access into it, so they are recovered where the offsets allow and
+/// otherwise done on `char` pointers. The language wraps signed arithmetic, which C leaves
+/// undefined, so it is done on the unsigned type of the same width and converted back, and its
+/// `char` is written as `signed char` as C lets plain `char` be either.
+pub struct CEmitter<W: Write> {
+    out: W,
+    globals: Vec<Symbol>,
+    parameters: Vec<Symbol>,
+    /// The variables of `main`, declared at the start of it
+    locals: Vec<Symbol>,
+    /// The declarations of the variables `main` keeps values in while it evaluates what follows
+    temporaries: Vec<String>,
+    /// The structs and unions the program uses with the tag each has in C, as anonymous ones
+    /// need one made up
+    structs: Vec<(Rc<Struct>, String)>,
+    /// The definitions of the structs, each after the ones it contains
+    definitions: Vec<String>,
+    includes: BTreeSet<&'static str>,
+    /// The functions standing in for the builtins C has no equivalent of, by name
+    helpers: BTreeMap<String, String>,
+    /// The expressions of the statement being written, which operands index
+    expressions: Vec<Expression>,
+    /// The switches being written, innermost last
+    switches: Vec<Switch>,
+    labels: Labels,
+    /// Whether the last statement written returns, so `main` needs no `return` of its own
+    returned: bool,
+    body: Vec<u8>,
+    indent: usize,
+}
+
+/// An expression of the statement being written, standing in for the register its value would be in
+#[derive(Clone, Copy, Debug)]
+pub struct Operand(usize);
+
+/// What is known of an expression beyond its text, for writing the ones built from it the way C
+/// would have them
+#[derive(Clone, Debug)]
+struct Expression {
+    text: String,
+    precedence: Precedence,
+    ty: Type,
+    /// Whether evaluating it could change a variable
+    writes: bool,
+    /// The value of a constant
+    constant: Option<u64>,
+    /// What a pointer points at, when it can be named without a dereference: a variable, an
+    /// element of an array or a member of a struct
+    object: Option<String>,
+    /// The index and the size it was multiplied by, for pointer arithmetic to leave the scaling to C
+    scaled: Option<(Operand, u64)>,
+    /// The expression this one converts
+    converted: Option<Operand>,
+}
+
+impl Expression {
+    fn new(text: String, precedence: Precedence, ty: Type) -> Expression {
+        Expression {
+            text,
+            precedence,
+            ty,
+            writes: false,
+            constant: None,
+            object: None,
+            scaled: None,
+            converted: None,
+        }
+    }
+}
+
+/// A switch being written. Case labels line up with the switch and the statements under them are
+/// indented. Falling through to a label is marked as meant, and a label ending the switch still
+/// needs a statement to label.
+struct Switch {
+    /// The case each label of the switch is written as
+    cases: Vec<(LabelIndex, String)>,
+    end: LabelIndex,
+    /// Whether nothing has been written since the last label
+    labelled: bool,
+    /// Whether the last statement written carries on to the next
+    falls: bool,
+}
+
+impl<W: Write> CEmitter<W> {
+    fn write(&mut self) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        // Declaring the variables may name structs that need defining before them
+        let globals = self.globals.clone().into_iter()
+            .map(|symbol| self.declarator(&symbol.ty, &symbol.name))
+            .collect::<Vec<_>>();
+        let locals = self.locals.clone().into_iter()
+            .map(|symbol| self.declarator(&symbol.ty, &symbol.name))
+            .collect::<Vec<_>>();
+        // C wants the arguments as plain `char`, so they are taken under another name and
+        // given to the program's parameter as `signed char`
+        let mut arguments = Vec::new();
+        let parameters = self.parameters.clone().into_iter()
+            .map(|symbol| {
+                if symbol.ty != Type::Char.pointer_to().pointer_to() {
+                    return self.declarator(&symbol.ty, &symbol.name);
+                }
+                let declaration = self.declarator(&symbol.ty, &symbol.name);
+                let cast = self.declarator(&symbol.ty, "");
+                arguments.push(format!("{} = ({}){}_", declaration, cast, symbol.name));
+                format!("char **{}_", symbol.name)
+            })
+            .collect::<Vec<_>>();
+
+        let out = &mut self.out;
+        for include in &self.includes {
+            writeln!(out, "#include <{}>", include)?;
+        }
+        writeln!(out)?;
+        for definition in &self.definitions {
+            writeln!(out, "{}", definition)?;
+        }
+        for global in &globals {
+            writeln!(out, "{};", global)?;
+        }
+        if !globals.is_empty() {
+            writeln!(out)?;
+        }
+        for helper in self.helpers.values() {
+            writeln!(out, "{}", helper)?;
+        }
+
+        if parameters.is_empty() {
+            writeln!(out, "int main(void) {{")?;
+        } else {
+            writeln!(out, "int main({}) {{", parameters.join(", "))?;
+        }
+        for argument in &arguments {
+            writeln!(out, "    {};", argument)?;
+        }
+        for local in &locals {
+            writeln!(out, "    {};", local)?;
+        }
+        for temporary in &self.temporaries {
+            writeln!(out, "    {};", temporary)?;
+        }
+        out.write_all(&self.body)?;
+        writeln!(out, "}}")?;
+        Ok(())
+    }
+
+    // Writes a statement, which the label of the switch it is in no longer ends with
+    fn line(&mut self, line: &str) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        writeln!(self.body, "{}{}", "    ".repeat(self.indent), line)?;
+        if let Some(switch) = self.switches.last_mut() {
+            switch.labelled = false;
+            switch.falls = true;
+        }
+        self.returned = false;
+        Ok(())
+    }
+
+    // Writes a statement that control never carries on past
+    fn last_line(&mut self, line: &str) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        self.line(line)?;
+        if let Some(switch) = self.switches.last_mut() {
+            switch.falls = false;
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, expression: Expression) -> Operand {
+        self.expressions.push(expression);
+        Operand(self.expressions.len() - 1)
+    }
+
+    fn expression(&self, operand: Operand) -> &Expression {
+        &self.expressions[operand.0]
+    }
+
+    // Gives the expression in parentheses when it binds more loosely than its place needs
+    fn operand(&self, operand: Operand, precedence: Precedence) -> String {
+        let expression = self.expression(operand);
+        if expression.precedence > precedence {
+            return format!("({})", expression.text);
+        }
+        expression.text.clone()
+    }
+
+    fn writes(&self, operands: &[Operand]) -> bool {
+        operands.iter().any(|operand| self.expression(*operand).writes)
+    }
+
+    // C leaves the order operands are evaluated in open while the language evaluates them from
+    // left to right, so a left operand the right one could change is saved first
+    fn ordered(&mut self, left: Operand, right: Operand, ty: Type, combine: impl FnOnce(&mut Self, String, String) -> (String, Precedence)) -> Operand {
+        let expression = self.expression(left).clone();
+        let right_text = self.operand(right, Precedence::Unary);
+        let (text, precedence) = if self.expression(right).writes && expression.constant.is_none() {
+            let saved = self.temporary(&expression.ty);
+            let (result, _) = combine(self, saved.clone(), right_text);
+            (format!("({} = {}, {})", saved, expression.text, result), Precedence::Primary)
+        } else {
+            let left_text = self.operand(left, Precedence::Unary);
+            combine(self, left_text, right_text)
+        };
+        let writes = self.writes(&[left, right]);
+        self.push(Expression { writes, ..Expression::new(text, precedence, ty) })
+    }
+
+    // Applies the operator to operands already in parentheses where they need them. Signed
+    // arithmetic that can overflow is done unsigned, where it wraps, and converted back. Shifts
+    // are done on 64 bits like they are in a register, so shifting a narrower value by its width
+    // or more is not undefined either.
+    fn arithmetic(&mut self, operation: BinaryOperation, ty: &Type, left: String, right: String) -> (String, Precedence) {
+        let wide = match operation {
+            BinaryOperation::Add |
+            BinaryOperation::Subtract |
+            BinaryOperation::Multiply if ty.is_signed() => ty.to_unsigned().ok(),
+            BinaryOperation::LeftShift if ty.is_signed() || ty.size() < 8 => Some(Type::UnsignedLong),
+            BinaryOperation::RightShift if ty.is_signed() && ty.size() < 8 => Some(Type::Long),
+            BinaryOperation::RightShift if ty.size() < 8 => Some(Type::UnsignedLong),
+            _ => None,
+        };
+        let wide = match wide {
+            Some(wide) => self.declarator(&wide, ""),
+            None => return (format!("{} {} {}", left, operator(operation), right), Precedence::Binary),
+        };
+        let cast = self.declarator(ty, "");
+        // The count of a shift is not part of the value, so it keeps its type
+        let right = match operation {
+            BinaryOperation::LeftShift | BinaryOperation::RightShift => right,
+            _ => format!("({}){}", wide, right),
+        };
+        (format!("({})(({}){} {} {})", cast, wide, left, operator(operation), right), Precedence::Unary)
+    }
+
+    fn pointer_arithmetic(&mut self, operation: BinaryOperation, ty: &Type, left: Operand, right: Operand) -> core::result::Result<Operand, Box<dyn std::error::Error>> {
+        let to = match ty {
+            Type::Pointer(to) => (**to).clone(),
+            unhandled => return Err(format!("Expected a pointer but got: {:?}", unhandled).into()),
+        };
+        let sign = operator(operation);
+        let writes = self.writes(&[left, right]);
+
+        // A member at a constant offset into a struct
+        let member = match (operation, self.expression(left).converted, self.expression(right).constant) {
+            (BinaryOperation::Add, Some(pointer), Some(offset)) => self.member(&to, pointer, offset),
+            _ => None,
+        };
+        if let Some(member) = member {
+            let text = format!("&{}", member);
+            return Ok(self.push(Expression { writes, object: Some(member), ..Expression::new(text, Precedence::Unary, ty.clone()) }));
+        }
+
+        if let Some(index) = self.index(&to, right) {
+            let text = format!("{} {} {}", self.operand(left, Precedence::Unary), sign, self.operand(index, Precedence::Unary));
+            let object = match operation {
+                BinaryOperation::Add => Some(format!("{}[{}]", self.operand(left, Precedence::Primary), self.expression(index).text)),
+                _ => None,
+            };
+            return Ok(self.push(Expression { writes, object, ..Expression::new(text, Precedence::Binary, ty.clone()) }));
+        }
+
+        // A pointer converted only to be moved is moved as it was
+        let bytes = match self.expression(left).converted {
+            Some(pointer) if self.expression(pointer).ty.is_pointer() => pointer,
+            _ => left,
+        };
+        let cast = self.declarator(ty, "");
+        let text = format!("({})((char *){} {} {})", cast, self.operand(bytes, Precedence::Unary), sign, self.operand(right, Precedence::Unary));
+        Ok(self.push(Expression { writes, ..Expression::new(text, Precedence::Unary, ty.clone()) }))
+    }
+
+    // The index that an offset of scaled pointer arithmetic was scaled from, when the scaling can
+    // be left to C
+    fn index(&self, pointee: &Type, offset: Operand) -> Option<Operand> {
+        if pointee.size() == 1 {
+            return Some(offset);
+        }
+        match self.expression(offset).scaled {
+            Some((index, size)) if size == pointee.size() as u64 => Some(index),
+            _ => None,
+        }
+    }
+
+    // Names the member of type `ty` at the offset into the struct the pointer points at
+    fn member(&self, ty: &Type, pointer: Operand, offset: u64) -> Option<String> {
+        let expression = self.expression(pointer);
+        let layout = match &expression.ty {
+            Type::Pointer(to) => match &**to {
+                Type::Struct(layout) => layout.clone(),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        let member = layout.members.iter().find(|m| m.offset as u64 == offset && m.ty == *ty)?;
+        match &expression.object {
+            Some(object) => Some(format!("{}.{}", object, member.name)),
+            None => Some(format!("{}->{}", self.operand(pointer, Precedence::Primary), member.name)),
+        }
+    }
+
+    fn dereference(&self, pointer: Operand) -> (String, Precedence) {
+        if let Some(object) = &self.expression(pointer).object {
+            return (object.clone(), Precedence::Primary);
+        }
+        (format!("*{}", self.operand(pointer, Precedence::Unary)), Precedence::Unary)
+    }
+
+    // Declares a new variable in `main` to hold a value of the type for a while
+    fn temporary(&mut self, ty: &Type) -> String {
+        let name = format!("saved_{}", self.temporaries.len() + 1);
+        let declaration = self.declarator(ty, &name);
+        self.temporaries.push(declaration);
+        name
+    }
+
+    // Gives the name of a function that reads an integer into a value of the type
+    fn read(&mut self, ty: &Type) -> String {
+        let c_type = self.declarator(ty, "");
+        let name = format!("read_{}", c_type.replace(' ', "_"));
+        self.includes.insert("stdio.h");
+        self.helpers.entry(name.clone()).or_insert_with(|| format!(
+"/* Reads an integer into the value, giving 1 when one was read, 0 when the input is not an integer and -1 at the end of the input */
+static int {}({} *value) {{
+    long read;
+    int status = scanf(\"%ld\", &read);
+    if (status == 1) {{
+        *value = read;
+    }}
+    return status;
+}}
+", name, c_type));
+        name
+    }
+
+    // Declares `inner`, a name or an empty string for a cast, as having the type
+    fn declarator(&mut self, ty: &Type, inner: &str) -> String {
+        match ty {
+            Type::Pointer(to) => match **to {
+                Type::Array(_, _) => self.declarator(to, &format!("(*{})", inner)),
+                _ => self.declarator(to, &format!("*{}", inner)),
+            },
+            Type::Array(element, length) => self.declarator(element, &format!("{}[{}]", inner, length)),
+            Type::Struct(layout) => {
+                let tag = self.tag(layout);
+                join(format!("{} {}", layout.kind, tag), inner)
+            }
+            ty => join(scalar(ty), inner),
+        }
+    }
+
+    // Gives the tag of the struct, defining it the first time it is used
+    fn tag(&mut self, layout: &Rc<Struct>) -> String {
+        if let Some((_, tag)) = self.structs.iter().find(|(s, _)| **s == **layout) {
+            return tag.clone();
+        }
+        let tag = if layout.name.starts_with('<') {
+            format!("anonymous_{}", self.structs.len())
+        } else {
+            layout.name.clone()
+        };
+        self.structs.push((layout.clone(), tag.clone()));
+
+        let mut definition = format!("{} {} {{\n", layout.kind, tag);
+        for member in &layout.members {
+            definition.push_str(&format!("    {};\n", self.declarator(&member.ty, &member.name)));
+        }
+        definition.push_str("};\n");
+        self.definitions.push(definition);
+        tag
+    }
+}
+
+impl<W: Write> Backend<W> for CEmitter<W> {
+    type Register = Operand;
+
+    fn new(_target: Target, out: W) -> Self {
+        CEmitter {
+            out,
+            globals: Vec::new(),
+            parameters: Vec::new(),
+            locals: Vec::new(),
+            temporaries: Vec::new(),
+            structs: Vec::new(),
+            definitions: Vec::new(),
+            includes: BTreeSet::new(),
+            helpers: BTreeMap::new(),
+            expressions: Vec::new(),
+            switches: Vec::new(),
+            labels: Labels::new(),
+            returned: false,
+            body: Vec::new(),
+            indent: 1,
+        }
+    }
+
+    // The source is written once the body of `main` is, as the definitions it needs come before it
+    fn prologue(&mut self) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    fn epilogue(&mut self) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        if !self.returned {
+            self.line("return 0;")?;
+        }
+        self.write()
+    }
+
+    fn comment(&mut self, comment: &str) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        writeln!(self.body, "{}/* {} */", "    ".repeat(self.indent), comment)?;
+        Ok(())
+    }
+
+    fn load(&mut self, value: u64, ty: &Type) -> core::result::Result<Operand, Box<dyn std::error::Error>> {
+        let (text, precedence) = literal(value, ty);
+        Ok(self.push(Expression { constant: Some(value), ..Expression::new(text, precedence, ty.clone()) }))
+    }
+
+    fn load_string(&mut self, string: &str) -> core::result::Result<Operand, Box<dyn std::error::Error>> {
+        let text = format!("(signed char *)\"{}\"", escape(string));
+        Ok(self.push(Expression::new(text, Precedence::Unary, Type::Char.pointer_to())))
+    }
+
+    fn binary(&mut self, operation: BinaryOperation, r1: Operand, r2: Operand, ty: &Type) -> core::result::Result<Operand, Box<dyn std::error::Error>> {
+        if ty.is_pointer() && matches!(operation, BinaryOperation::Add | BinaryOperation::Subtract) {
+            return self.pointer_arithmetic(operation, ty, r1, r2);
+        }
+        // The difference is divided by the size of the pointee after, so it is taken in bytes
+        if operation == BinaryOperation::Subtract && self.expression(r1).ty.is_pointer() {
+            let text = format!("(char *){} - (char *){}", self.operand(r1, Precedence::Unary), self.operand(r2, Precedence::Unary));
+            let writes = self.writes(&[r1, r2]);
+            return Ok(self.push(Expression { writes, ..Expression::new(text, Precedence::Binary, ty.clone()) }));
+        }
+        let scaled = match (operation, self.expression(r2).constant) {
+            (BinaryOperation::Multiply, Some(size)) => Some((r1, size)),
+            _ => None,
+        };
+        let operand = self.ordered(r1, r2, ty.clone(), |emitter, left, right| emitter.arithmetic(operation, ty, left, right));
+        self.expressions[operand.0].scaled = scaled;
+        Ok(operand)
+    }
+
+    fn compare(&mut self, comparison: Comparison, r1: Operand, r2: Operand, _ty: &Type) -> core::result::Result<Operand, Box<dyn std::error::Error>> {
+        Ok(self.ordered(r1, r2, Type::Int, |_, left, right| (format!("{} {} {}", left, self::comparison(comparison), right), Precedence::Binary)))
+    }
+
+    // Values already have the width of their type in C
+    fn extend(&mut self, r: Operand, _ty: &Type) -> core::result::Result<Operand, Box<dyn std::error::Error>> {
+        Ok(r)
+    }
+
+    fn convert(&mut self, r: Operand, from: &Type, to: &Type) -> core::result::Result<Operand, Box<dyn std::error::Error>> {
+        let expression = self.expression(r).clone();
+        let object = match to {
+            Type::Pointer(pointee) => self.member(pointee, r, 0),
+            _ => None,
+        };
+        let converted = |text: String, precedence: Precedence, object: Option<String>| Expression {
+            writes: expression.writes,
+            object,
+            converted: Some(r),
+            ..Expression::new(text, precedence, to.clone())
+        };
+
+        if let Type::Pointer(pointee) = to {
+            // An array decaying to a pointer to its first element, or the first member of a struct
+            if let Type::Pointer(array) = from {
+                if let Type::Array(element, _) = &**array {
+                    if element == pointee {
+                        if let Some(array) = expression.object.clone() {
+                            return Ok(self.push(converted(array, Precedence::Primary, object)));
+                        }
+                    }
+                }
+            }
+            if let Some(member) = object.clone() {
+                return Ok(self.push(converted(format!("&{}", member), Precedence::Unary, object)));
+            }
+        }
+        if let Some(value) = expression.constant {
+            if to.is_integer() {
+                let (text, precedence) = literal(to.canonical(value), to);
+                return Ok(self.push(converted(text, precedence, object)));
+            }
+            let text = format!("({}){}", self.declarator(to, ""), value);
+            return Ok(self.push(converted(text, Precedence::Unary, object)));
+        }
+
+        // Integers narrower than a pointer go through a long so they are sign or zero extended
+        // as they would be in a register
+        let cast = self.declarator(to, "");
+        let operand = self.operand(r, Precedence::Unary);
+        if to.is_pointer() != from.is_pointer() && to.size().min(from.size()) < 8 {
+            return Ok(self.push(converted(format!("({})(long){}", cast, operand), Precedence::Unary, object)));
+        }
+        Ok(self.push(converted(format!("({}){}", cast, operand), Precedence::Unary, object)))
+    }
+
+    fn declare_global(&mut self, symbol: &Symbol) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        self.globals.push(symbol.clone());
+        Ok(())
+    }
+
+    fn declare_local(&mut self, symbol: &Symbol) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        self.locals.push(symbol.clone());
+        Ok(())
+    }
+
+    fn declare_parameter(&mut self, symbol: &Symbol) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        self.parameters.push(symbol.clone());
+        Ok(())
+    }
+
+    fn load_symbol(&mut self, symbol: &Symbol, ty: &Type) -> core::result::Result<Operand, Box<dyn std::error::Error>> {
+        Ok(self.push(Expression::new(symbol.name.clone(), Precedence::Primary, ty.clone())))
+    }
+
+    fn store_symbol(&mut self, symbol: &Symbol, r: Operand, ty: &Type) -> core::result::Result<Operand, Box<dyn std::error::Error>> {
+        let text = format!("{} = {}", symbol.name, self.expression(r).text);
+        Ok(self.push(Expression { writes: true, ..Expression::new(text, Precedence::Binary, ty.clone()) }))
+    }
+
+    // An array used as a pointer to its first element is just its name in C too
+    fn address_of(&mut self, symbol: &Symbol, ty: &Type) -> core::result::Result<Operand, Box<dyn std::error::Error>> {
+        if *ty != symbol.ty.clone().pointer_to() {
+            return Ok(self.push(Expression::new(symbol.name.clone(), Precedence::Primary, ty.clone())));
+        }
+        let text = format!("&{}", symbol.name);
+        Ok(self.push(Expression { object: Some(symbol.name.clone()), ..Expression::new(text, Precedence::Unary, ty.clone()) }))
+    }
+
+    fn load_dereference(&mut self, r: Operand, ty: &Type) -> core::result::Result<Operand, Box<dyn std::error::Error>> {
+        let (text, precedence) = self.dereference(r);
+        let writes = self.writes(&[r]);
+        Ok(self.push(Expression { writes, ..Expression::new(text, precedence, ty.clone()) }))
+    }
+
+    fn store_dereference(&mut self, value: Operand, pointer: Operand, ty: &Type) -> core::result::Result<Operand, Box<dyn std::error::Error>> {
+        let (object, _) = self.dereference(pointer);
+        let text = format!("{} = {}", object, self.expression(value).text);
+        Ok(self.push(Expression { writes: true, ..Expression::new(text, Precedence::Binary, ty.clone()) }))
+    }
+
+    fn bounds_check(&mut self, r: Operand, length: u32, line: u32) -> core::result::Result<Operand, Box<dyn std::error::Error>> {
+        self.includes.insert("stdio.h");
+        self.includes.insert("stdlib.h");
+        self.helpers.insert("check_bounds".to_string(), CHECK_BOUNDS.to_string());
+        let expression = self.expression(r).clone();
+        let text = format!("check_bounds({}, {}, {})", expression.text, length, line);
+        Ok(self.push(Expression { writes: expression.writes, ..Expression::new(text, Precedence::Primary, expression.ty) }))
+    }
+
+    fn call(&mut self, function: Function, arguments: &[Operand]) -> core::result::Result<Option<Operand>, Box<dyn std::error::Error>> {
+        let (text, ty) = match (&function, arguments) {
+            (Function::PrintString, [string]) => {
+                self.includes.insert("stdio.h");
+                let line = format!("puts((char *){});", self.operand(*string, Precedence::Unary));
+                self.line(&line)?;
+                return Ok(None);
+            }
+            // Narrower integers are printed as the long they convert to
+            (Function::PrintInt, [value]) | (Function::PrintUnsignedInt, [value]) => {
+                self.includes.insert("stdio.h");
+                let (format, long) = match function {
+                    Function::PrintInt => ("%ld", Type::Long),
+                    _ => ("%lu", Type::UnsignedLong),
+                };
+                let from = self.expression(*value).ty.clone();
+                let value = if from == long { *value } else { self.convert(*value, &from, &long)? };
+                let line = format!("printf(\"{}\\n\", {});", format, self.expression(value).text);
+                self.line(&line)?;
+                return Ok(None);
+            }
+            (Function::ReadInt(ty), [pointer]) => {
+                let name = self.read(ty);
+                (format!("{}({})", name, self.expression(*pointer).text), Type::Int)
+            }
+            (Function::ReadLine, [buffer, size]) => {
+                self.includes.insert("stdio.h");
+                self.helpers.insert("read_line".to_string(), READ_LINE.to_string());
+                (format!("read_line({}, {})", self.expression(*buffer).text, self.expression(*size).text), Type::Long)
+            }
+            (Function::Exit, [status]) => {
+                self.includes.insert("stdlib.h");
+                let line = format!("exit({});", self.expression(*status).text);
+                self.last_line(&line)?;
+                return Ok(None);
+            }
+            (function, arguments) => return Err(format!("Unable to call {:?} with {} arguments", function, arguments.len()).into()),
+        };
+        Ok(Some(self.push(Expression { writes: true, ..Expression::new(text, Precedence::Primary, ty) })))
+    }
+
+    fn return_value(&mut self, r: Operand) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        let line = format!("return {};", self.expression(r).text);
+        self.last_line(&line)?;
+        self.returned = true;
+        Ok(())
+    }
+
+    fn allocate_label(&mut self) -> LabelIndex {
+        self.labels.allocate_label()
+    }
+
+    // The only labels are the cases of a switch and its end, which closes it
+    fn label(&mut self, label: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        let switch = self.switches.last().ok_or("Unable to write a label outside of a switch in C")?;
+        if label == switch.end {
+            if switch.labelled {
+                self.line("break;")?;
+            }
+            self.switches.pop();
+            self.indent -= 1;
+            return self.line("}");
+        }
+        let case = switch.cases.iter().find(|(case, _)| *case == label).map(|(_, case)| case.clone()).ok_or("Unable to write a label that is not a case of the switch in C")?;
+        if switch.falls {
+            self.line("/* fall through */")?;
+        }
+        self.indent -= 1;
+        self.line(&case)?;
+        self.indent += 1;
+        if let Some(switch) = self.switches.last_mut() {
+            switch.labelled = true;
+            switch.falls = false;
+        }
+        Ok(())
+    }
+
+    fn jump(&mut self, label: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        match self.switches.last() {
+            Some(switch) if switch.end == label => self.last_line("break;"),
+            _ => Err("Unable to jump anywhere but out of a switch in C".into()),
+        }
+    }
+
+    fn jump_if_zero(&mut self, _r: Operand, _label: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        Err("Unable to jump anywhere but out of a switch in C".into())
+    }
+
+    fn jump_if_equal(&mut self, _r: Operand, _value: u64, _label: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        Err("Unable to jump anywhere but out of a switch in C".into())
+    }
+
+    fn jump_table(&mut self, _r: Operand, _low: u64, _targets: &[LabelIndex], _default: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        Err("Unable to jump anywhere but out of a switch in C".into())
+    }
+
+    fn switch(&mut self, r: Operand, ty: &Type, cases: &[(u64, LabelIndex)], _targets: &[LabelIndex], default: LabelIndex, end: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        let line = format!("switch ({}) {{", self.expression(r).text);
+        self.line(&line)?;
+        let mut labels = cases.iter()
+            .map(|(value, label)| (*label, format!("case {}:", literal(*value, ty).0)))
+            .collect::<Vec<_>>();
+        if default != end {
+            labels.push((default, "default:".to_string()));
+        }
+        self.switches.push(Switch { cases: labels, end, labelled: false, falls: false });
+        self.indent += 1;
+        Ok(())
+    }
+
+    // Only the arm chosen is evaluated by `?:` in C too
+    fn conditional<T, F>(&mut self, condition: Operand, when_true: T, when_false: F) -> core::result::Result<Operand, Box<dyn std::error::Error>>
+        where T: FnOnce(&mut Self) -> core::result::Result<Operand, Box<dyn std::error::Error>>,
+              F: FnOnce(&mut Self) -> core::result::Result<Operand, Box<dyn std::error::Error>> {
+        let when_true = when_true(self)?;
+        let when_false = when_false(self)?;
+        self.select(condition, when_true, when_false)
+    }
+
+    fn move_register(&mut self, _from: Operand, _to: Operand) -> core::result::Result<Operand, Box<dyn std::error::Error>> {
+        Err("Unable to move a value in C, each is only an expression".into())
+    }
+
+    fn select(&mut self, condition: Operand, when_true: Operand, when_false: Operand) -> core::result::Result<Operand, Box<dyn std::error::Error>> {
+        let text = format!(
+            "{} ? {} : {}",
+            self.operand(condition, Precedence::Unary),
+            self.operand(when_true, Precedence::Unary),
+            self.operand(when_false, Precedence::Unary),
+        );
+        let writes = self.writes(&[condition, when_true, when_false]);
+        let ty = self.expression(when_true).ty.clone();
+        Ok(self.push(Expression { writes, ..Expression::new(text, Precedence::Binary, ty) }))
+    }
+
+    fn free_register(&mut self, _r: Operand) {}
+
+    fn discard(&mut self, r: Operand) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        let line = format!("{};", self.expression(r).text);
+        self.line(&line)
+    }
+
+    // Nothing is left of a statement once it has been written
+    fn free_all_registers(&mut self) {
+        self.expressions.clear();
+    }
+}
+
+fn join(base: String, inner: &str) -> String {
+    if inner.is_empty() {
+        return base;
+    }
+    format!("{} {}", base, inner)
+}
+
+fn operator(operation: BinaryOperation) -> &'static str {
+    match operation {
+        BinaryOperation::Add => "+",
+        BinaryOperation::Subtract => "-",
+        BinaryOperation::Multiply => "*",
+        BinaryOperation::Divide => "/",
+        BinaryOperation::LeftShift => "<<",
+        BinaryOperation::RightShift => ">>",
+    }
+}
+
+fn comparison(comparison: Comparison) -> &'static str {
+    match comparison {
+        Comparison::Equal => "==",
+        Comparison::NotEqual => "!=",
+        Comparison::LessThan => "<",
+        Comparison::GreaterThan => ">",
+        Comparison::LessThanEqual => "<=",
+        Comparison::GreaterThanEqual => ">=",
+    }
+}
+
+/// A constant of the type. The most negative value of a type can only be written as an
+/// expression, as C reads the minus sign as negating the positive literal after it.
+fn literal(value: u64, ty: &Type) -> (String, Precedence) {
+    let (value, suffix) = match ty {
+        Type::Int => (value as i32 as i64 as i128, ""),
+        Type::Long => (value as i64 as i128, "L"),
+        Type::UnsignedInt => (value as u32 as i128, "U"),
+        Type::UnsignedLong => (value as i128, "UL"),
+        ty => return (format!("({}){}", scalar(ty), value as i64), Precedence::Unary),
+    };
+    if value == i32::MIN as i128 || value == i64::MIN as i128 {
+        return (format!("({}{} - 1)", value + 1, suffix), Precedence::Primary);
+    }
+    if value < 0 {
+        return (format!("{}{}", value, suffix), Precedence::Unary);
+    }
+    (format!("{}{}", value, suffix), Precedence::Primary)
+}
+
+// Names a type that is not built from others. Whether a plain `char` is signed is up to the C
+// compiler, so the signed one the language has is asked for.
+fn scalar(ty: &Type) -> String {
+    match ty {
+        Type::Char => "signed char".to_string(),
+        ty => ty.to_string(),
+    }
+}
+
+// Writes the string as a C string literal, with anything unprintable as an octal escape
+fn escape(string: &str) -> String {
+    let mut escaped = String::new();
+    let mut previous = 0;
+    for byte in string.bytes() {
+        match byte {
+            b'"' => escaped.push_str("\\\""),
+            b'\\' => escaped.push_str("\\\\"),
+            b'\n' => escaped.push_str("\\n"),
+            b'\t' => escaped.push_str("\\t"),
+            // Two question marks could start a trigraph
+            b'?' if previous == b'?' => escaped.push_str("\\?"),
+            b' '..=b'~' => escaped.push(byte as char),
+            other => escaped.push_str(&format!("\\{:03o}", other)),
+        }
+        previous = byte;
+    }
+    escaped
+}
@@ -0,0 +1,552 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::io::Write;
+
+use crate::asm::backend::{Backend, BinaryOperation, Comparison, Function};
+use crate::asm::labels::{LabelIndex, Labels};
+use crate::asm::target::Target;
+use crate::symbols::{Storage, Symbol};
+use crate::types::Type;
+
+/// The functions of the C library the program calls, declared as they are used
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Declaration {
+    Printf,
+    Puts,
+    Scanf,
+    Getchar,
+    Fflush,
+    Abort,
+    Exit,
+}
+
+impl Declaration {
+    fn signature(&self) -> &'static str {
+        match self {
+            Declaration::Printf => "declare i32 @printf(ptr, ...)",
+            Declaration::Puts => "declare i32 @puts(ptr)",
+            Declaration::Scanf => "declare i32 @scanf(ptr, ...)",
+            Declaration::Getchar => "declare i32 @getchar()",
+            Declaration::Fflush => "declare i32 @fflush(ptr)",
+            Declaration::Abort => "declare void @abort()",
+            Declaration::Exit => "declare void @exit(i32)",
+        }
+    }
+}
+
+// Reads characters into the buffer until a newline, the end of the input or the buffer has room
+// only for the terminating zero, giving the length of the line or -1 at the end of the input
+const READLINE: &str = "define internal i64 @.readline(ptr %buffer, i64 %size) {
+entry:
+  %limit = sub i64 %size, 1
+  br label %next
+next:
+  %n = phi i64 [ 0, %entry ], [ %n.next, %store ]
+  %full = icmp sge i64 %n, %limit
+  br i1 %full, label %done, label %read
+read:
+  %c = call i32 @getchar()
+  %eof = icmp eq i32 %c, -1
+  br i1 %eof, label %end, label %check
+check:
+  %newline = icmp eq i32 %c, 10
+  br i1 %newline, label %done, label %store
+store:
+  %at = getelementptr i8, ptr %buffer, i64 %n
+  %byte = trunc i32 %c to i8
+  store i8 %byte, ptr %at
+  %n.next = add i64 %n, 1
+  br label %next
+end:
+  %at.end = getelementptr i8, ptr %buffer, i64 %n
+  store i8 0, ptr %at.end
+  %empty = icmp eq i64 %n, 0
+  %length = select i1 %empty, i64 -1, i64 %n
+  ret i64 %length
+done:
+  %at.done = getelementptr i8, ptr %buffer, i64 %n
+  store i8 0, ptr %at.done
+  ret i64 %n
+}";
+
+/// Writes a program as textual LLVM IR for `clang`, `llc` or `opt` to build. Values are kept as
+/// `i64` the way the assembly backends keep them in whole registers, and converted to the width
+/// of their type when stored. Globals are module globals and the parameters of `main` are copied
+/// into `alloca`s, so every variable has an address. Pointers are opaque `ptr`s, so the IR needs
+/// LLVM 15 or later, or LLVM 14 given `-opaque-pointers`.
+pub struct LlvmEmitter<W: Write> {
+    out: W,
+    globals: Vec<Symbol>,
+    /// The parameters of `main` with their types, if it has them
+    parameters: Vec<(String, Type)>,
+    /// The variables of `main`, each given an `alloca` of its own
+    locals: Vec<Symbol>,
+    /// The name of the constant holding each string, including the formats passed to the C library
+    strings: HashMap<String, String>,
+    data: Vec<(String, String)>,
+    declarations: BTreeSet<Declaration>,
+    /// Whether `scanf` needs somewhere to read into
+    reads: bool,
+    readline: bool,
+    labels: Labels,
+    temporaries: u32,
+    body: Vec<u8>,
+    /// The label of the basic block being written
+    block: String,
+    /// Whether the block being written has ended, so anything more needs a block of its own
+    terminated: bool,
+}
+
+/// An `i64` operand, either a constant or the temporary an instruction gave its result in
+#[derive(Clone, Copy, Debug)]
+pub enum Value {
+    Constant(i64),
+    Temporary(u32),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Constant(value) => write!(f, "{}", value),
+            Value::Temporary(index) => write!(f, "%t.{}", index),
+        }
+    }
+}
+
+impl<W: Write> LlvmEmitter<W> {
+    fn instruction(&mut self, instruction: String) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        // Code after a return or a branch is never run but still has to be in a block
+        if self.terminated {
+            let label = self.labels.allocate_label();
+            self.start_block(label)?;
+        }
+        writeln!(self.body, "  {}", instruction)?;
+        Ok(())
+    }
+
+    // Writes an instruction giving a value, named by a new temporary
+    fn value(&mut self, instruction: String) -> core::result::Result<Value, Box<dyn std::error::Error>> {
+        self.temporaries += 1;
+        let temporary = Value::Temporary(self.temporaries);
+        self.instruction(format!("{} = {}", temporary, instruction))?;
+        Ok(temporary)
+    }
+
+    // Writes the instruction ending the block
+    fn terminator(&mut self, instruction: String) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        self.instruction(instruction)?;
+        self.terminated = true;
+        Ok(())
+    }
+
+    // Starts a block, which the block before it falls through to unless it has already ended
+    fn start_block(&mut self, label: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        if !self.terminated {
+            writeln!(self.body, "  br label %{}", label)?;
+        }
+        writeln!(self.body, "{}:", label)?;
+        self.block = label.name();
+        self.terminated = false;
+        Ok(())
+    }
+
+    fn declare(&mut self, declaration: Declaration, call: String) -> core::result::Result<Value, Box<dyn std::error::Error>> {
+        self.declarations.insert(declaration);
+        self.value(call)
+    }
+
+    fn string(&mut self, string: &str) -> String {
+        if let Some(name) = self.strings.get(string) {
+            return name.clone();
+        }
+        let name = format!("@.str.{}", self.data.len());
+        self.strings.insert(string.to_string(), name.clone());
+        self.data.push((name.clone(), string.to_string()));
+        name
+    }
+
+    fn address(&self, symbol: &Symbol) -> String {
+        match symbol.storage {
+            Storage::Global => format!("@{}", symbol.name),
+            Storage::Frame(_) => format!("%{}.addr", symbol.name),
+        }
+    }
+
+    // Turns a value into a pointer for a load, store or call
+    fn pointer(&mut self, value: Value) -> core::result::Result<Value, Box<dyn std::error::Error>> {
+        self.value(format!("inttoptr i64 {} to ptr", value))
+    }
+
+    // Loads the value of type `ty` at the pointer, sign or zero extending it
+    fn load_from(&mut self, pointer: &str, ty: &Type) -> core::result::Result<Value, Box<dyn std::error::Error>> {
+        let value = self.value(format!("load {}, ptr {}, align {}", llvm_type(ty), pointer, ty.alignment()))?;
+        match ty {
+            Type::Pointer(_) => self.value(format!("ptrtoint ptr {} to i64", value)),
+            ty if ty.size() == 8 => Ok(value),
+            ty => {
+                let extension = if ty.is_signed() { "sext" } else { "zext" };
+                self.value(format!("{} {} {} to i64", extension, llvm_type(ty), value))
+            }
+        }
+    }
+
+    fn store_to(&mut self, value: Value, pointer: &str, ty: &Type) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        let value = match ty {
+            Type::Pointer(_) => self.value(format!("inttoptr i64 {} to ptr", value))?,
+            ty if ty.size() == 8 => value,
+            ty => self.value(format!("trunc i64 {} to {}", value, llvm_type(ty)))?,
+        };
+        self.instruction(format!("store {} {}, ptr {}, align {}", llvm_type(ty), value, pointer, ty.alignment()))
+    }
+
+    fn write(&mut self) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        let out = &mut self.out;
+        for symbol in &self.globals {
+            let initialiser = match symbol.ty {
+                Type::Array(_, _) | Type::Struct(_) => "zeroinitializer",
+                Type::Pointer(_) => "null",
+                _ => "0",
+            };
+            writeln!(out, "@{} = global {} {}, align {}", symbol.name, llvm_type(&symbol.ty), initialiser, symbol.ty.alignment())?;
+        }
+        for (name, string) in &self.data {
+            writeln!(out, "{} = private unnamed_addr constant [{} x i8] c\"{}\\00\", align 1", name, string.len() + 1, escape(string))?;
+        }
+        writeln!(out)?;
+        for declaration in &self.declarations {
+            writeln!(out, "{}", declaration.signature())?;
+        }
+        writeln!(out)?;
+
+        let parameters = self.parameters.iter()
+            .map(|(name, ty)| format!("{} %{}", llvm_type(ty), name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        writeln!(out, "define i32 @main({}) {{", parameters)?;
+        writeln!(out, "entry:")?;
+        for (name, ty) in &self.parameters {
+            writeln!(out, "  %{}.addr = alloca {}, align {}", name, llvm_type(ty), ty.alignment())?;
+            writeln!(out, "  store {} %{}, ptr %{}.addr, align {}", llvm_type(ty), name, name, ty.alignment())?;
+        }
+        for symbol in &self.locals {
+            writeln!(out, "  %{}.addr = alloca {}, align {}", symbol.name, llvm_type(&symbol.ty), symbol.ty.alignment())?;
+        }
+        if self.reads {
+            writeln!(out, "  %.read = alloca i64, align 8")?;
+        }
+        out.write_all(&self.body)?;
+        writeln!(out, "}}")?;
+
+        if self.readline {
+            writeln!(out)?;
+            writeln!(out, "{}", READLINE)?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Backend<W> for LlvmEmitter<W> {
+    type Register = Value;
+
+    fn new(_target: Target, out: W) -> Self {
+        LlvmEmitter {
+            out,
+            globals: Vec::new(),
+            parameters: Vec::new(),
+            locals: Vec::new(),
+            strings: HashMap::new(),
+            data: Vec::new(),
+            declarations: BTreeSet::new(),
+            reads: false,
+            readline: false,
+            labels: Labels::new(),
+            temporaries: 0,
+            body: Vec::new(),
+            block: "entry".to_string(),
+            terminated: false,
+        }
+    }
+
+    // The module is written once the body of `main` is, as the globals and declarations it needs
+    // come before it
+    fn prologue(&mut self) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    fn epilogue(&mut self) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        self.terminator("ret i32 0".to_string())?;
+        self.write()
+    }
+
+    fn comment(&mut self, comment: &str) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        writeln!(self.body, "  ; {}", comment)?;
+        Ok(())
+    }
+
+    fn load(&mut self, value: u64, _ty: &Type) -> core::result::Result<Value, Box<dyn std::error::Error>> {
+        Ok(Value::Constant(value as i64))
+    }
+
+    fn load_string(&mut self, string: &str) -> core::result::Result<Value, Box<dyn std::error::Error>> {
+        let string = self.string(string);
+        self.value(format!("ptrtoint ptr {} to i64", string))
+    }
+
+    // Arithmetic is done on whole `i64`s, which the compiler extends from the width of the type after
+    fn binary(&mut self, operation: BinaryOperation, r1: Value, r2: Value, ty: &Type) -> core::result::Result<Value, Box<dyn std::error::Error>> {
+        let instruction = match operation {
+            BinaryOperation::Add => "add",
+            BinaryOperation::Subtract => "sub",
+            BinaryOperation::Multiply => "mul",
+            BinaryOperation::Divide if ty.is_signed() => "sdiv",
+            BinaryOperation::Divide => "udiv",
+            BinaryOperation::LeftShift => "shl",
+            BinaryOperation::RightShift if ty.is_signed() => "ashr",
+            BinaryOperation::RightShift => "lshr",
+        };
+        self.value(format!("{} i64 {}, {}", instruction, r1, r2))
+    }
+
+    // Comparisons give an `i1` that is widened back to the `i64` everything else works on
+    fn compare(&mut self, comparison: Comparison, r1: Value, r2: Value, ty: &Type) -> core::result::Result<Value, Box<dyn std::error::Error>> {
+        let comparison = match comparison {
+            Comparison::Equal => "eq",
+            Comparison::NotEqual => "ne",
+            Comparison::LessThan => "lt",
+            Comparison::GreaterThan => "gt",
+            Comparison::LessThanEqual => "le",
+            Comparison::GreaterThanEqual => "ge",
+        };
+        let predicate = match comparison {
+            "eq" | "ne" => comparison.to_string(),
+            _ if ty.is_signed() => format!("s{}", comparison),
+            _ => format!("u{}", comparison),
+        };
+        let condition = self.value(format!("icmp {} i64 {}, {}", predicate, r1, r2))?;
+        self.value(format!("zext i1 {} to i64", condition))
+    }
+
+    // Sign or zero extends the low bytes of the value to the whole `i64`
+    fn extend(&mut self, r: Value, ty: &Type) -> core::result::Result<Value, Box<dyn std::error::Error>> {
+        let width = match ty.size() {
+            1 => "i8",
+            4 => "i32",
+            _ => return Ok(r),
+        };
+        let truncated = self.value(format!("trunc i64 {} to {}", r, width))?;
+        let extension = if ty.is_signed() { "sext" } else { "zext" };
+        self.value(format!("{} {} {} to i64", extension, width, truncated))
+    }
+
+    fn declare_global(&mut self, symbol: &Symbol) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        self.globals.push(symbol.clone());
+        Ok(())
+    }
+
+    fn declare_local(&mut self, symbol: &Symbol) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        self.locals.push(symbol.clone());
+        Ok(())
+    }
+
+    fn declare_parameter(&mut self, symbol: &Symbol) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        self.parameters.push((symbol.name.clone(), symbol.ty.clone()));
+        Ok(())
+    }
+
+    fn load_symbol(&mut self, symbol: &Symbol, ty: &Type) -> core::result::Result<Value, Box<dyn std::error::Error>> {
+        let address = self.address(symbol);
+        self.load_from(&address, ty)
+    }
+
+    fn store_symbol(&mut self, symbol: &Symbol, r: Value, ty: &Type) -> core::result::Result<Value, Box<dyn std::error::Error>> {
+        let address = self.address(symbol);
+        self.store_to(r, &address, ty)?;
+        Ok(r)
+    }
+
+    fn address_of(&mut self, symbol: &Symbol, _ty: &Type) -> core::result::Result<Value, Box<dyn std::error::Error>> {
+        let address = self.address(symbol);
+        self.value(format!("ptrtoint ptr {} to i64", address))
+    }
+
+    fn load_dereference(&mut self, r: Value, ty: &Type) -> core::result::Result<Value, Box<dyn std::error::Error>> {
+        let pointer = self.pointer(r)?;
+        self.load_from(&pointer.to_string(), ty)
+    }
+
+    fn store_dereference(&mut self, value: Value, pointer: Value, ty: &Type) -> core::result::Result<Value, Box<dyn std::error::Error>> {
+        let pointer = self.pointer(pointer)?;
+        self.store_to(value, &pointer.to_string(), ty)?;
+        Ok(value)
+    }
+
+    // Negative indexes compare as large unsigned values so one unsigned comparison covers both ends
+    fn bounds_check(&mut self, r: Value, length: u32, line: u32) -> core::result::Result<Value, Box<dyn std::error::Error>> {
+        let fail = self.labels.allocate_label();
+        let ok = self.labels.allocate_label();
+        let out_of_bounds = self.value(format!("icmp uge i64 {}, {}", r, length))?;
+        self.terminator(format!("br i1 {}, label %{}, label %{}", out_of_bounds, fail, ok))?;
+        self.start_block(fail)?;
+        let message = self.string("Array index out of bounds on line %ld\n");
+        self.declare(Declaration::Printf, format!("call i32 (ptr, ...) @printf(ptr {}, i64 {})", message, line))?;
+        self.declare(Declaration::Fflush, "call i32 @fflush(ptr null)".to_string())?;
+        self.declarations.insert(Declaration::Abort);
+        self.instruction("call void @abort()".to_string())?;
+        self.terminator("unreachable".to_string())?;
+        self.start_block(ok)?;
+        Ok(r)
+    }
+
+    fn call(&mut self, function: Function, arguments: &[Value]) -> core::result::Result<Option<Value>, Box<dyn std::error::Error>> {
+        match (&function, arguments) {
+            (Function::PrintString, [string]) => {
+                let string = self.pointer(*string)?;
+                self.declare(Declaration::Puts, format!("call i32 @puts(ptr {})", string))?;
+                Ok(None)
+            }
+            (Function::PrintInt, [value]) | (Function::PrintUnsignedInt, [value]) => {
+                let format = self.string(if let Function::PrintInt = function { "%ld\n" } else { "%lu\n" });
+                self.declare(Declaration::Printf, format!("call i32 (ptr, ...) @printf(ptr {}, i64 {})", format, value))?;
+                Ok(None)
+            }
+            // The integer is read into a long of its own and only stored, at the width of the
+            // value pointed to, when there was one to read
+            (Function::ReadInt(ty), [pointer]) => {
+                self.reads = true;
+                let format = self.string("%ld");
+                let status = self.declare(Declaration::Scanf, format!("call i32 (ptr, ...) @scanf(ptr {}, ptr %.read)", format))?;
+                let read = self.value(format!("icmp eq i32 {}, 1", status))?;
+                let store = self.labels.allocate_label();
+                let skip = self.labels.allocate_label();
+                self.terminator(format!("br i1 {}, label %{}, label %{}", read, store, skip))?;
+                self.start_block(store)?;
+                let value = self.value("load i64, ptr %.read, align 8".to_string())?;
+                let pointer = self.pointer(*pointer)?;
+                self.store_to(value, &pointer.to_string(), ty)?;
+                self.start_block(skip)?;
+                self.value(format!("sext i32 {} to i64", status)).map(Some)
+            }
+            (Function::ReadLine, [buffer, size]) => {
+                let buffer = self.pointer(*buffer)?;
+                self.readline = true;
+                self.declarations.insert(Declaration::Getchar);
+                self.value(format!("call i64 @.readline(ptr {}, i64 {})", buffer, size)).map(Some)
+            }
+            (Function::Exit, [status]) => {
+                let status = self.value(format!("trunc i64 {} to i32", status))?;
+                self.declarations.insert(Declaration::Exit);
+                self.instruction(format!("call void @exit(i32 {})", status))?;
+                self.terminator("unreachable".to_string())?;
+                Ok(None)
+            }
+            (function, arguments) => Err(format!("Unable to call {:?} with {} arguments", function, arguments.len()).into()),
+        }
+    }
+
+    fn return_value(&mut self, r: Value) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        let status = self.value(format!("trunc i64 {} to i32", r))?;
+        self.terminator(format!("ret i32 {}", status))
+    }
+
+    fn allocate_label(&mut self) -> LabelIndex {
+        self.labels.allocate_label()
+    }
+
+    fn label(&mut self, label: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        self.start_block(label)
+    }
+
+    fn jump(&mut self, label: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        self.terminator(format!("br label %{}", label))
+    }
+
+    fn jump_if_zero(&mut self, r: Value, label: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        let next = self.labels.allocate_label();
+        let zero = self.value(format!("icmp eq i64 {}, 0", r))?;
+        self.terminator(format!("br i1 {}, label %{}, label %{}", zero, label, next))?;
+        self.start_block(next)
+    }
+
+    fn jump_if_equal(&mut self, r: Value, value: u64, label: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        let next = self.labels.allocate_label();
+        let equal = self.value(format!("icmp eq i64 {}, {}", r, value as i64))?;
+        self.terminator(format!("br i1 {}, label %{}, label %{}", equal, label, next))?;
+        self.start_block(next)
+    }
+
+    fn jump_table(&mut self, r: Value, low: u64, targets: &[LabelIndex], default: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        let cases = targets.iter().enumerate()
+            .map(|(index, label)| format!("i64 {}, label %{}", low.wrapping_add(index as u64) as i64, label))
+            .collect::<Vec<_>>();
+        self.terminator(format!("switch i64 {}, label %{} [ {} ]", r, default, cases.join(" ")))
+    }
+
+    // Every case starts a block of its own, which `switch` picks between
+    fn switch(&mut self, r: Value, _ty: &Type, cases: &[(u64, LabelIndex)], _targets: &[LabelIndex], default: LabelIndex, _end: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        let cases = cases.iter()
+            .map(|(value, label)| format!("i64 {}, label %{}", *value as i64, label))
+            .collect::<Vec<_>>();
+        self.terminator(format!("switch i64 {}, label %{} [ {} ]", r, default, cases.join(" ")))
+    }
+
+    // Each arm may have started blocks of its own, so the `phi` joining them names the block each
+    // ended in
+    fn conditional<T, F>(&mut self, condition: Value, when_true: T, when_false: F) -> core::result::Result<Value, Box<dyn std::error::Error>>
+        where T: FnOnce(&mut Self) -> core::result::Result<Value, Box<dyn std::error::Error>>,
+              F: FnOnce(&mut Self) -> core::result::Result<Value, Box<dyn std::error::Error>> {
+        let true_label = self.labels.allocate_label();
+        let false_label = self.labels.allocate_label();
+        let end_label = self.labels.allocate_label();
+
+        let condition = self.value(format!("icmp ne i64 {}, 0", condition))?;
+        self.terminator(format!("br i1 {}, label %{}, label %{}", condition, true_label, false_label))?;
+
+        self.start_block(true_label)?;
+        let when_true = when_true(self)?;
+        let true_block = self.block.clone();
+        self.terminator(format!("br label %{}", end_label))?;
+
+        self.start_block(false_label)?;
+        let when_false = when_false(self)?;
+        let false_block = self.block.clone();
+
+        self.start_block(end_label)?;
+        self.value(format!("phi i64 [ {}, %{} ], [ {}, %{} ]", when_true, true_block, when_false, false_block))
+    }
+
+    fn move_register(&mut self, _from: Value, _to: Value) -> core::result::Result<Value, Box<dyn std::error::Error>> {
+        Err("Unable to move a value in LLVM IR, each is only ever given once".into())
+    }
+
+    fn select(&mut self, condition: Value, when_true: Value, when_false: Value) -> core::result::Result<Value, Box<dyn std::error::Error>> {
+        let condition = self.value(format!("icmp ne i64 {}, 0", condition))?;
+        self.value(format!("select i1 {}, i64 {}, i64 {}", condition, when_true, when_false))
+    }
+
+    fn free_register(&mut self, _r: Value) {}
+
+    fn free_all_registers(&mut self) {}
+}
+
+/// The LLVM type a value of the type is stored as. Arrays and structs are only ever reached
+/// through pointers at byte offsets, so they are just the right number of bytes.
+fn llvm_type(ty: &Type) -> String {
+    match ty {
+        Type::Pointer(_) => "ptr".to_string(),
+        Type::Array(_, _) | Type::Struct(_) => format!("[{} x i8]", ty.size()),
+        ty => format!("i{}", ty.size() * 8),
+    }
+}
+
+// Writes the string as an LLVM string constant, with anything unprintable as a hex escape
+fn escape(string: &str) -> String {
+    let mut escaped = String::new();
+    for byte in string.bytes() {
+        match byte {
+            b'"' | b'\\' => escaped.push_str(&format!("\\{:02X}", byte)),
+            b' '..=b'~' => escaped.push(byte as char),
+            other => escaped.push_str(&format!("\\{:02X}", other)),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,63 @@
+use std::fmt;
+use std::str::FromStr;
+
+pub mod c;
+pub mod llvm;
+pub mod wat;
+
+/// What the compiler writes for a program
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Emit {
+    /// Assembly for the target, which is assembled and linked when the target is the host
+    Assembly,
+    /// A relocatable ELF object for x86-64 Linux, encoded by the compiler itself without an
+    /// assembler. When the target is the host it is still linked into an executable by the system
+    /// linker, run through `cc`, which has to be installed.
+    Object,
+    /// A WebAssembly module in the text format, to be run by a host providing its runtime
+    Wat,
+    /// Textual LLVM IR with opaque pointers, to be built with clang or llc from LLVM 15 on
+    Llvm,
+    /// C99 source, to be built with any C compiler
+    C,
+}
+
+impl Emit {
+    /// The extension of the file written for the program
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Emit::Assembly => "s",
+            Emit::Object => "o",
+            Emit::Wat => "wat",
+            Emit::Llvm => "ll",
+            Emit::C => "c",
+        }
+    }
+}
+
+impl fmt::Display for Emit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Emit::Assembly => write!(f, "asm"),
+            Emit::Object => write!(f, "obj"),
+            Emit::Wat => write!(f, "wat"),
+            Emit::Llvm => write!(f, "llvm"),
+            Emit::C => write!(f, "c"),
+        }
+    }
+}
+
+impl FromStr for Emit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Emit, String> {
+        match s {
+            "asm" => Ok(Emit::Assembly),
+            "obj" => Ok(Emit::Object),
+            "wat" => Ok(Emit::Wat),
+            "llvm" => Ok(Emit::Llvm),
+            "c" => Ok(Emit::C),
+            unknown => Err(format!("Unknown output [{}], expected one of asm, obj, wat, llvm or c", unknown)),
+        }
+    }
+}
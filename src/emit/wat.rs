@@ -0,0 +1,681 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::io::Write;
+
+use crate::asm::backend::{Backend, BinaryOperation, Comparison, Function};
+use crate::asm::labels::{LabelIndex, Labels};
+use crate::asm::target::Target;
+use crate::ast::*;
+use crate::compiler::{jump_table_range, Program};
+use crate::symbols::{Storage, Symbol, SymbolId};
+use crate::types::Type;
+
+// The first bytes of memory are never handed out so that no variable or string is at the null
+// pointer. `read_i64` writes the integer it reads into the word at `READ_ADDRESS`.
+const READ_ADDRESS: u32 = 8;
+const DATA_START: u32 = 16;
+
+const PAGE_SIZE: u32 = 65536;
+
+/// The functions of the runtime, imported from the host's `env` module as the program uses them
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Import {
+    PrintInt,
+    PrintUnsignedInt,
+    /// Prints the zero terminated string at the address it is given
+    PrintString,
+    /// Reads an integer into the word at the address it is given, giving 1 when one was read, 0
+    /// when the input is not an integer and -1 at the end of the input
+    ReadInt,
+    /// Reads a line into the buffer at the address it is given, of the size it is given, giving
+    /// the length of the line or -1 at the end of the input
+    ReadLine,
+    Exit,
+    /// Reports an index out of bounds on the line it is given
+    BoundsFail,
+}
+
+impl Import {
+    fn name(&self) -> &'static str {
+        match self {
+            Import::PrintInt => "print_i64",
+            Import::PrintUnsignedInt => "print_u64",
+            Import::PrintString => "print_string",
+            Import::ReadInt => "read_i64",
+            Import::ReadLine => "read_line",
+            Import::Exit => "exit",
+            Import::BoundsFail => "bounds_fail",
+        }
+    }
+
+    fn signature(&self) -> &'static str {
+        match self {
+            Import::PrintInt | Import::PrintUnsignedInt | Import::Exit | Import::BoundsFail => "(param i64)",
+            Import::PrintString => "(param i32)",
+            Import::ReadInt => "(param i32) (result i64)",
+            Import::ReadLine => "(param i32 i64) (result i64)",
+        }
+    }
+}
+
+/// Where a variable is kept in the module
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Placement {
+    /// A mutable `i64` global of the module
+    Global,
+    /// A parameter or local of `main`
+    Local,
+    /// Linear memory at the given address, for arrays, structs and anything whose address is taken
+    Memory(u32),
+    /// Linear memory at the given offset from the frame of `main` on the stack, for the same
+    /// variables of `main`
+    Frame(i32),
+}
+
+/// Writes a program as a WebAssembly module in the text format. The program becomes an exported
+/// `main` taking `argc` and `argv` and giving the exit status, and the memory holding strings and
+/// any variable that needs an address is exported as `memory` for the host to read and write.
+/// Memory from the exported `heap_base` up is free for the host, for example to lay out `argv`.
+/// Below it is the stack, holding the frame of `main` for those of its variables that need to be
+/// in memory.
+/// Values are `i64`s on the operand stack, taken by each instruction in the order the compiler
+/// computed them.
+pub struct WatEmitter<W: Write> {
+    out: W,
+    /// The variables whose address is taken somewhere in the program
+    addressed: HashSet<String>,
+    placements: HashMap<String, Placement>,
+    globals: Vec<String>,
+    /// The names of the parameters of `main`, if it has them
+    parameters: Vec<String>,
+    /// The names of the variables of `main` kept in locals
+    locals: Vec<String>,
+    /// The bytes the frame of `main` takes on the stack
+    frame_size: u32,
+    /// Whether any variable is in the frame, so `main` has to set it up
+    framed: bool,
+    /// The address of each string literal, laid out as they are first used
+    strings: HashMap<String, u32>,
+    data: Vec<(u32, String)>,
+    /// The first byte of memory nothing has been placed at
+    memory_end: u32,
+    imports: BTreeSet<Import>,
+    labels: Labels,
+    body: Vec<u8>,
+    /// How many blocks the next instruction is nested in
+    depth: usize,
+}
+
+/// A value on the operand stack. Instructions take theirs from the top, so there is nothing to
+/// tell them apart by.
+#[derive(Clone, Copy, Debug)]
+pub struct Operand;
+
+impl<W: Write> WatEmitter<W> {
+    fn instruction<S: AsRef<str>>(&mut self, instruction: S) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        writeln!(self.body, "    {}{}", "  ".repeat(self.depth), instruction.as_ref())?;
+        Ok(())
+    }
+
+    fn import(&mut self, import: Import) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        self.imports.insert(import);
+        self.instruction(format!("call ${}", import.name()))
+    }
+
+    fn begin_block(&mut self, label: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        self.instruction(format!("block ${}", label))?;
+        self.depth += 1;
+        Ok(())
+    }
+
+    // Opens an `if` taking the condition on the stack, closed by `end_block`
+    fn begin_if(&mut self, instruction: &str) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        self.instruction(instruction)?;
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn end_block(&mut self) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        self.depth -= 1;
+        self.instruction("end")
+    }
+
+    fn allocate(&mut self, ty: &Type) -> u32 {
+        let alignment = ty.alignment().max(1);
+        let address = self.memory_end.div_ceil(alignment) * alignment;
+        self.memory_end = address + ty.size();
+        address
+    }
+
+    fn string_address(&mut self, string: &str) -> u32 {
+        if let Some(address) = self.strings.get(string) {
+            return *address;
+        }
+        let address = self.memory_end;
+        self.memory_end += string.len() as u32 + 1;
+        self.strings.insert(string.to_string(), address);
+        self.data.push((address, string.to_string()));
+        address
+    }
+
+    // Aggregates and variables with their address taken have to be somewhere in memory, anything
+    // else can be a global or a local
+    fn place(&mut self, symbol: &Symbol) -> Placement {
+        let in_memory = matches!(symbol.ty, Type::Array(_, _) | Type::Struct(_)) || self.addressed.contains(&symbol.name);
+        let placement = match symbol.storage {
+            Storage::Global if in_memory => Placement::Memory(self.allocate(&symbol.ty)),
+            Storage::Global => {
+                self.globals.push(symbol.name.clone());
+                Placement::Global
+            }
+            Storage::Frame(offset) if in_memory => {
+                self.framed = true;
+                Placement::Frame(offset)
+            }
+            Storage::Frame(_) => Placement::Local,
+        };
+        self.placements.insert(symbol.name.clone(), placement);
+        placement
+    }
+
+    // Puts the `i32` address of a variable in memory on the stack
+    fn address(&mut self, placement: Placement) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        match placement {
+            Placement::Memory(address) => self.instruction(format!("i32.const {}", address)),
+            Placement::Frame(offset) => {
+                self.instruction("local.get $.frame")?;
+                self.instruction(format!("i32.const {}", -offset))?;
+                self.instruction("i32.sub")
+            }
+            placement => Err(format!("Unable to take the address of a variable placed as {:?}", placement).into()),
+        }
+    }
+
+    // Gives the stack back as it was before `main` took its frame
+    fn release_frame(&mut self) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        if self.framed {
+            self.instruction("local.get $.frame")?;
+            self.instruction("global.set $.stack_pointer")?;
+        }
+        Ok(())
+    }
+
+    fn placement(&self, symbol: &Symbol) -> core::result::Result<Placement, Box<dyn std::error::Error>> {
+        self.placements.get(&symbol.name).copied()
+            .ok_or_else(|| format!("Use of [{}] before it was declared", symbol.name).into())
+    }
+
+    fn write(&mut self) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        let out = &mut self.out;
+        writeln!(out, "(module")?;
+        for import in &self.imports {
+            writeln!(out, "  (import \"env\" \"{}\" (func ${} {}))", import.name(), import.name(), import.signature())?;
+        }
+        // The stack only ever holds the frame of `main`, and grows down from the heap
+        let stack_top = self.memory_end.div_ceil(16) * 16 + if self.framed { self.frame_size } else { 0 };
+        let pages = stack_top.div_ceil(PAGE_SIZE);
+        writeln!(out, "  (memory (export \"memory\") {})", pages)?;
+        writeln!(out, "  (global $heap_base (export \"heap_base\") i32 (i32.const {}))", stack_top)?;
+        if self.framed {
+            writeln!(out, "  (global $.stack_pointer (mut i32) (i32.const {}))", stack_top)?;
+        }
+        for name in &self.globals {
+            writeln!(out, "  (global ${} (mut i64) (i64.const 0))", name)?;
+        }
+        for (address, string) in &self.data {
+            writeln!(out, "  (data (i32.const {}) \"{}\\00\")", address, escape(string))?;
+        }
+
+        let parameters = match self.parameters.as_slice() {
+            [] => "(param i64 i64)".to_string(),
+            names => names.iter().map(|name| format!("(param ${} i64)", name)).collect::<Vec<_>>().join(" "),
+        };
+        writeln!(out, "  (func $main (export \"main\") {} (result i64)", parameters)?;
+        // Scratch locals for values that have to be held while others are put on the stack
+        writeln!(out, "    (local $.value i64)")?;
+        writeln!(out, "    (local $.address i32)")?;
+        for name in &self.locals {
+            writeln!(out, "    (local ${} i64)", name)?;
+        }
+        if self.framed {
+            writeln!(out, "    (local $.frame i32)")?;
+            writeln!(out, "    global.get $.stack_pointer")?;
+            writeln!(out, "    local.tee $.frame")?;
+            writeln!(out, "    i32.const {}", self.frame_size)?;
+            writeln!(out, "    i32.sub")?;
+            writeln!(out, "    global.set $.stack_pointer")?;
+        }
+        out.write_all(&self.body)?;
+        writeln!(out, "    i64.const 0")?;
+        writeln!(out, "  )")?;
+        writeln!(out, ")")?;
+        Ok(())
+    }
+}
+
+impl<W: Write> Backend<W> for WatEmitter<W> {
+    type Register = Operand;
+
+    fn new(_target: Target, out: W) -> Self {
+        WatEmitter {
+            out,
+            addressed: HashSet::new(),
+            placements: HashMap::new(),
+            globals: Vec::new(),
+            parameters: Vec::new(),
+            locals: Vec::new(),
+            frame_size: 0,
+            framed: false,
+            strings: HashMap::new(),
+            data: Vec::new(),
+            memory_end: DATA_START,
+            imports: BTreeSet::new(),
+            labels: Labels::new(),
+            body: Vec::new(),
+            depth: 0,
+        }
+    }
+
+    fn survey(&mut self, program: &Program) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        self.addressed = addressed(&program.statements).into_iter()
+            .map(|id| program.symbols.symbol(id).name.clone())
+            .collect();
+        self.frame_size = program.symbols.frame_size();
+        Ok(())
+    }
+
+    // The module is written once the body of `main` is, as the declarations it needs come before it
+    fn prologue(&mut self) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    fn epilogue(&mut self) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        self.release_frame()?;
+        self.write()
+    }
+
+    fn comment(&mut self, comment: &str) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        self.instruction(format!(";; {}", comment))
+    }
+
+    fn load(&mut self, value: u64, _ty: &Type) -> core::result::Result<Operand, Box<dyn std::error::Error>> {
+        self.instruction(format!("i64.const {}", value as i64))?;
+        Ok(Operand)
+    }
+
+    fn load_string(&mut self, string: &str) -> core::result::Result<Operand, Box<dyn std::error::Error>> {
+        let address = self.string_address(string);
+        self.instruction(format!("i64.const {}", address))?;
+        Ok(Operand)
+    }
+
+    // Arithmetic is done on whole `i64`s, which the compiler extends from the width of the type after
+    fn binary(&mut self, operation: BinaryOperation, _r1: Operand, _r2: Operand, ty: &Type) -> core::result::Result<Operand, Box<dyn std::error::Error>> {
+        self.instruction(match operation {
+            BinaryOperation::Add => "i64.add",
+            BinaryOperation::Subtract => "i64.sub",
+            BinaryOperation::Multiply => "i64.mul",
+            BinaryOperation::Divide if ty.is_signed() => "i64.div_s",
+            BinaryOperation::Divide => "i64.div_u",
+            BinaryOperation::LeftShift => "i64.shl",
+            BinaryOperation::RightShift if ty.is_signed() => "i64.shr_s",
+            BinaryOperation::RightShift => "i64.shr_u",
+        })?;
+        Ok(Operand)
+    }
+
+    // Comparisons give an `i32` that is widened back to the `i64` everything else works on
+    fn compare(&mut self, comparison: Comparison, _r1: Operand, _r2: Operand, ty: &Type) -> core::result::Result<Operand, Box<dyn std::error::Error>> {
+        let comparison = match comparison {
+            Comparison::Equal => "eq",
+            Comparison::NotEqual => "ne",
+            Comparison::LessThan => "lt",
+            Comparison::GreaterThan => "gt",
+            Comparison::LessThanEqual => "le",
+            Comparison::GreaterThanEqual => "ge",
+        };
+        let suffix = match comparison {
+            "eq" | "ne" => "",
+            _ if ty.is_signed() => "_s",
+            _ => "_u",
+        };
+        self.instruction(format!("i64.{}{}", comparison, suffix))?;
+        self.instruction("i64.extend_i32_u")?;
+        Ok(Operand)
+    }
+
+    // Sign or zero extends the low bytes of the value on the stack to the whole `i64`
+    fn extend(&mut self, _r: Operand, ty: &Type) -> core::result::Result<Operand, Box<dyn std::error::Error>> {
+        match (ty.size(), ty.is_signed()) {
+            (1, true) => self.instruction("i64.extend8_s")?,
+            (1, false) => {
+                self.instruction("i64.const 255")?;
+                self.instruction("i64.and")?;
+            }
+            (4, true) => self.instruction("i64.extend32_s")?,
+            (4, false) => {
+                self.instruction("i64.const 4294967295")?;
+                self.instruction("i64.and")?;
+            }
+            _ => {}
+        }
+        Ok(Operand)
+    }
+
+    fn declare_global(&mut self, symbol: &Symbol) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        self.place(symbol);
+        Ok(())
+    }
+
+    fn declare_local(&mut self, symbol: &Symbol) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        if let Placement::Local = self.place(symbol) {
+            self.locals.push(symbol.name.clone());
+        }
+        Ok(())
+    }
+
+    // Parameters are locals of `main` already, unless they have to be copied into memory
+    fn declare_parameter(&mut self, symbol: &Symbol) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        self.parameters.push(symbol.name.clone());
+        let placement = self.place(symbol);
+        if let Placement::Memory(_) | Placement::Frame(_) = placement {
+            self.address(placement)?;
+            self.instruction(format!("local.get ${}", symbol.name))?;
+            self.instruction(store_instruction(&symbol.ty))?;
+        }
+        Ok(())
+    }
+
+    fn load_symbol(&mut self, symbol: &Symbol, ty: &Type) -> core::result::Result<Operand, Box<dyn std::error::Error>> {
+        match self.placement(symbol)? {
+            Placement::Global => self.instruction(format!("global.get ${}", symbol.name))?,
+            Placement::Local => self.instruction(format!("local.get ${}", symbol.name))?,
+            placement => {
+                self.address(placement)?;
+                self.instruction(load_instruction(ty))?;
+            }
+        }
+        Ok(Operand)
+    }
+
+    // Leaves the value on the stack as it now reads back
+    fn store_symbol(&mut self, symbol: &Symbol, r: Operand, ty: &Type) -> core::result::Result<Operand, Box<dyn std::error::Error>> {
+        match self.placement(symbol)? {
+            Placement::Global => {
+                self.extend(r, ty)?;
+                self.instruction(format!("global.set ${}", symbol.name))?;
+                self.instruction(format!("global.get ${}", symbol.name))?;
+            }
+            Placement::Local => {
+                self.extend(r, ty)?;
+                self.instruction(format!("local.tee ${}", symbol.name))?;
+            }
+            placement => {
+                self.instruction("local.set $.value")?;
+                self.address(placement)?;
+                self.instruction("local.get $.value")?;
+                self.instruction(store_instruction(ty))?;
+                self.instruction("local.get $.value")?;
+            }
+        }
+        Ok(Operand)
+    }
+
+    fn address_of(&mut self, symbol: &Symbol, _ty: &Type) -> core::result::Result<Operand, Box<dyn std::error::Error>> {
+        match self.placement(symbol)? {
+            Placement::Memory(address) => self.instruction(format!("i64.const {}", address))?,
+            Placement::Frame(offset) => {
+                self.address(Placement::Frame(offset))?;
+                self.instruction("i64.extend_i32_u")?;
+            }
+            placement => return Err(format!("Unable to take the address of [{}] placed as {:?}", symbol.name, placement).into()),
+        }
+        Ok(Operand)
+    }
+
+    fn load_dereference(&mut self, _r: Operand, ty: &Type) -> core::result::Result<Operand, Box<dyn std::error::Error>> {
+        self.instruction("i32.wrap_i64")?;
+        self.instruction(load_instruction(ty))?;
+        Ok(Operand)
+    }
+
+    // The address goes below the value for the store, and the value is kept as the result
+    fn store_dereference(&mut self, _value: Operand, _pointer: Operand, ty: &Type) -> core::result::Result<Operand, Box<dyn std::error::Error>> {
+        self.instruction("i32.wrap_i64")?;
+        self.instruction("local.set $.address")?;
+        self.instruction("local.set $.value")?;
+        self.instruction("local.get $.address")?;
+        self.instruction("local.get $.value")?;
+        self.instruction(store_instruction(ty))?;
+        self.instruction("local.get $.value")?;
+        Ok(Operand)
+    }
+
+    // Negative indexes compare as large unsigned values so one unsigned comparison covers both ends
+    fn bounds_check(&mut self, _r: Operand, length: u32, line: u32) -> core::result::Result<Operand, Box<dyn std::error::Error>> {
+        self.instruction("local.tee $.value")?;
+        self.instruction(format!("i64.const {}", length))?;
+        self.instruction("i64.ge_u")?;
+        self.begin_if("if")?;
+        self.instruction(format!("i64.const {}", line))?;
+        self.import(Import::BoundsFail)?;
+        self.instruction("unreachable")?;
+        self.end_block()?;
+        self.instruction("local.get $.value")?;
+        Ok(Operand)
+    }
+
+    fn call(&mut self, function: Function, _arguments: &[Operand]) -> core::result::Result<Option<Operand>, Box<dyn std::error::Error>> {
+        match function {
+            Function::PrintInt => self.import(Import::PrintInt)?,
+            Function::PrintUnsignedInt => self.import(Import::PrintUnsignedInt)?,
+            Function::PrintString => {
+                self.instruction("i32.wrap_i64")?;
+                self.import(Import::PrintString)?;
+            }
+            // The integer is read into a word of its own and only stored, at the width of the
+            // value pointed to, when there was one to read
+            Function::ReadInt(ty) => {
+                self.instruction("i32.wrap_i64")?;
+                self.instruction("local.set $.address")?;
+                self.instruction(format!("i32.const {}", READ_ADDRESS))?;
+                self.import(Import::ReadInt)?;
+                self.instruction("local.tee $.value")?;
+                self.instruction("i64.const 1")?;
+                self.instruction("i64.eq")?;
+                self.begin_if("if")?;
+                self.instruction("local.get $.address")?;
+                self.instruction(format!("i32.const {}", READ_ADDRESS))?;
+                self.instruction("i64.load")?;
+                self.instruction(store_instruction(&ty))?;
+                self.end_block()?;
+                self.instruction("local.get $.value")?;
+                return Ok(Some(Operand));
+            }
+            Function::ReadLine => {
+                self.instruction("local.set $.value")?;
+                self.instruction("i32.wrap_i64")?;
+                self.instruction("local.get $.value")?;
+                self.import(Import::ReadLine)?;
+                return Ok(Some(Operand));
+            }
+            Function::Exit => {
+                self.import(Import::Exit)?;
+                self.instruction("unreachable")?;
+            }
+        }
+        Ok(None)
+    }
+
+    fn return_value(&mut self, _r: Operand) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        self.release_frame()?;
+        self.instruction("return")
+    }
+
+    fn allocate_label(&mut self) -> LabelIndex {
+        self.labels.allocate_label()
+    }
+
+    // WebAssembly has no jumps, only branches out of enclosing blocks, so a label is where the
+    // block opened for it ends
+    fn label(&mut self, _label: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        self.end_block()
+    }
+
+    fn jump(&mut self, label: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        self.instruction(format!("br ${}", label))
+    }
+
+    fn jump_if_zero(&mut self, _r: Operand, _label: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        Err("Unable to jump forward past code in WebAssembly, it only branches out of blocks".into())
+    }
+
+    fn jump_if_equal(&mut self, _r: Operand, _value: u64, _label: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        Err("Unable to jump forward past code in WebAssembly, it only branches out of blocks".into())
+    }
+
+    fn jump_table(&mut self, _r: Operand, _low: u64, _targets: &[LabelIndex], _default: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        Err("Unable to jump forward past code in WebAssembly, it only branches out of blocks".into())
+    }
+
+    // Each case label closes a block, so the blocks are opened innermost last and the dispatch
+    // inside all of them branches out of the block that closes at its label. `break` branches out
+    // of the outermost, which closes at the end of the switch.
+    fn switch(&mut self, _r: Operand, ty: &Type, cases: &[(u64, LabelIndex)], targets: &[LabelIndex], default: LabelIndex, end: LabelIndex) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        self.instruction("local.set $.value")?;
+        self.begin_block(end)?;
+        for label in targets.iter().rev() {
+            self.begin_block(*label)?;
+        }
+
+        match jump_table_range(cases, ty.is_signed()) {
+            Some((low, length)) => {
+                let mut table = vec![default; length as usize];
+                for (value, label) in cases {
+                    table[value.wrapping_sub(low) as usize] = *label;
+                }
+                self.instruction("local.get $.value")?;
+                self.instruction(format!("i64.const {}", low as i64))?;
+                self.instruction("i64.sub")?;
+                self.instruction("local.tee $.value")?;
+                self.instruction(format!("i64.const {}", length))?;
+                self.instruction("i64.ge_u")?;
+                self.instruction(format!("br_if ${}", default))?;
+                self.instruction("local.get $.value")?;
+                self.instruction("i32.wrap_i64")?;
+                let table = table.iter().map(|label| format!("${}", label)).collect::<Vec<_>>().join(" ");
+                self.instruction(format!("br_table {} ${}", table, default))?;
+            }
+            None => {
+                for (value, label) in cases {
+                    self.instruction("local.get $.value")?;
+                    self.instruction(format!("i64.const {}", *value as i64))?;
+                    self.instruction("i64.eq")?;
+                    self.instruction(format!("br_if ${}", label))?;
+                }
+                self.instruction(format!("br ${}", default))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn conditional<T, F>(&mut self, _condition: Operand, when_true: T, when_false: F) -> core::result::Result<Operand, Box<dyn std::error::Error>>
+        where T: FnOnce(&mut Self) -> core::result::Result<Operand, Box<dyn std::error::Error>>,
+              F: FnOnce(&mut Self) -> core::result::Result<Operand, Box<dyn std::error::Error>> {
+        self.instruction("i64.const 0")?;
+        self.instruction("i64.ne")?;
+        self.begin_if("if (result i64)")?;
+        when_true(self)?;
+        self.depth -= 1;
+        self.instruction("else")?;
+        self.depth += 1;
+        when_false(self)?;
+        self.end_block()?;
+        Ok(Operand)
+    }
+
+    fn move_register(&mut self, _from: Operand, _to: Operand) -> core::result::Result<Operand, Box<dyn std::error::Error>> {
+        Err("Unable to move a value in WebAssembly, values are only ever on the stack".into())
+    }
+
+    fn select(&mut self, _condition: Operand, _when_true: Operand, _when_false: Operand) -> core::result::Result<Operand, Box<dyn std::error::Error>> {
+        self.instruction("i64.const 0")?;
+        self.instruction("i64.ne")?;
+        self.instruction("select")?;
+        Ok(Operand)
+    }
+
+    fn free_register(&mut self, _r: Operand) {}
+
+    fn discard(&mut self, _r: Operand) -> core::result::Result<(), Box<dyn std::error::Error>> {
+        self.instruction("drop")
+    }
+
+    fn free_all_registers(&mut self) {}
+}
+
+/// The variables the program takes the address of anywhere, which have to be placed in memory
+fn addressed(statements: &[AbstractSyntaxTreeNode]) -> HashSet<SymbolId> {
+    fn visit(ast: &AbstractSyntaxTreeNode, addressed: &mut HashSet<SymbolId>) {
+        match ast {
+            AbstractSyntaxTreeNode::Unary(AbstractSyntaxTreeUnaryNodeType::AddressOf, _, left) => {
+                if let AbstractSyntaxTreeNode::Leaf(AbstractSyntaxTreeLeafNodeType::Symbol(id, _), _) = **left {
+                    addressed.insert(id);
+                }
+                visit(left, addressed);
+            }
+            AbstractSyntaxTreeNode::Construct(_, left) | AbstractSyntaxTreeNode::Unary(_, _, left) => visit(left, addressed),
+            AbstractSyntaxTreeNode::Expression(_, _, left, right) => {
+                visit(left, addressed);
+                visit(right, addressed);
+            }
+            AbstractSyntaxTreeNode::Ternary(_, _, left, middle, right) => {
+                visit(left, addressed);
+                visit(middle, addressed);
+                visit(right, addressed);
+            }
+            AbstractSyntaxTreeNode::Call(_, _, arguments) => arguments.iter().for_each(|a| visit(a, addressed)),
+            AbstractSyntaxTreeNode::Compound(_, left, statements) => {
+                visit(left, addressed);
+                statements.iter().for_each(|s| visit(s, addressed));
+            }
+            AbstractSyntaxTreeNode::Leaf(_, _) => {}
+        }
+    }
+
+    let mut addressed = HashSet::new();
+    statements.iter().for_each(|s| visit(s, &mut addressed));
+    addressed
+}
+
+// Loads the value of type `ty` at the address on the stack, sign or zero extending it
+fn load_instruction(ty: &Type) -> &'static str {
+    match (ty.size(), ty.is_signed()) {
+        (1, true) => "i64.load8_s",
+        (1, false) => "i64.load8_u",
+        (4, true) => "i64.load32_s",
+        (4, false) => "i64.load32_u",
+        _ => "i64.load",
+    }
+}
+
+fn store_instruction(ty: &Type) -> &'static str {
+    match ty.size() {
+        1 => "i64.store8",
+        4 => "i64.store32",
+        _ => "i64.store",
+    }
+}
+
+// Writes the string as a WebAssembly string literal, with anything unprintable as a hex escape
+fn escape(string: &str) -> String {
+    let mut escaped = String::new();
+    for byte in string.bytes() {
+        match byte {
+            b'"' => escaped.push_str("\\\""),
+            b'\\' => escaped.push_str("\\\\"),
+            b' '..=b'~' => escaped.push(byte as char),
+            other => escaped.push_str(&format!("\\{:02x}", other)),
+        }
+    }
+    escaped
+}
@@ -2,3 +2,6 @@ pub mod scanner;
 pub mod ast;
 pub mod compiler;
 pub mod asm;
+pub mod emit;
+pub mod symbols;
+pub mod types;
@@ -20,7 +20,7 @@ pub enum Token {
     Minus,
     Star,
     Slash,
-    U32(u32),
+    U64(u64),
     SemiColon,
     Keyword(KeywordToken),
     Space,
@@ -33,12 +33,42 @@ pub enum Token {
     GreaterThanEqual,
     NotEqual,
     Equality,
+    QuestionMark,
+    Colon,
+    Comma,
+    LeftShift,
+    RightShift,
+    Ampersand,
+    LeftParen,
+    RightParen,
+    LeftBracket,
+    RightBracket,
+    LeftBrace,
+    RightBrace,
+    Dot,
+    Arrow,
+    StringLiteral(String),
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum KeywordToken {
     Print,
     Int,
+    Char,
+    Long,
+    Void,
+    Signed,
+    Unsigned,
+    Struct,
+    Sizeof,
+    Union,
+    Enum,
+    Typedef,
+    Switch,
+    Case,
+    Default,
+    Break,
+    Return,
 }
 
 impl std::convert::TryFrom<&str> for KeywordToken {
@@ -48,6 +78,21 @@ impl std::convert::TryFrom<&str> for KeywordToken {
         match value {
             "print" => Ok(KeywordToken::Print),
             "int" => Ok(KeywordToken::Int),
+            "char" => Ok(KeywordToken::Char),
+            "long" => Ok(KeywordToken::Long),
+            "void" => Ok(KeywordToken::Void),
+            "signed" => Ok(KeywordToken::Signed),
+            "unsigned" => Ok(KeywordToken::Unsigned),
+            "struct" => Ok(KeywordToken::Struct),
+            "sizeof" => Ok(KeywordToken::Sizeof),
+            "union" => Ok(KeywordToken::Union),
+            "enum" => Ok(KeywordToken::Enum),
+            "typedef" => Ok(KeywordToken::Typedef),
+            "switch" => Ok(KeywordToken::Switch),
+            "case" => Ok(KeywordToken::Case),
+            "default" => Ok(KeywordToken::Default),
+            "break" => Ok(KeywordToken::Break),
+            "return" => Ok(KeywordToken::Return),
             v => Err(format!("Unable to handle KeywordToken: [{}]", v))
         }
     }
@@ -60,6 +105,9 @@ pub trait Precedence {
 impl Precedence for Token {
     fn precedence(&self) -> u32 {
         match self {
+            Token::QuestionMark => 5,
+            Token::LeftShift => 7,
+            Token::RightShift => 7,
             Token::Plus => 10,
             Token::Minus => 10,
             Token::Star => 20,
@@ -92,10 +140,27 @@ impl<T: Iterator<Item=char>> TokenIterator<T> {
         if let Some(t) = self.inner.next() {
             return Some(match t {
                 '+' => Token::Plus,
-                '-' => Token::Minus,
+                '-' => match self.inner.peek() {
+                    Some('>') => {
+                        self.inner.next();
+                        Token::Arrow
+                    }
+                    _ => Token::Minus
+                },
                 '*' => Token::Star,
                 '/' => Token::Slash,
                 ';' => Token::SemiColon,
+                '?' => Token::QuestionMark,
+                ':' => Token::Colon,
+                ',' => Token::Comma,
+                '&' => Token::Ampersand,
+                '(' => Token::LeftParen,
+                ')' => Token::RightParen,
+                '[' => Token::LeftBracket,
+                ']' => Token::RightBracket,
+                '{' => Token::LeftBrace,
+                '}' => Token::RightBrace,
+                '.' => Token::Dot,
                 '=' => {
                     match self.inner.peek() {
                         Some('=') => {
@@ -112,6 +177,10 @@ impl<T: Iterator<Item=char>> TokenIterator<T> {
                         self.inner.next();
                         Token::LessThanEqual
                     }
+                    Some('<') => {
+                        self.inner.next();
+                        Token::LeftShift
+                    }
                     _ => Token::LessThan
                 }
                 '>' => match self.inner.peek() {
@@ -119,6 +188,10 @@ impl<T: Iterator<Item=char>> TokenIterator<T> {
                         self.inner.next();
                         Token::GreaterThanEqual
                     }
+                    Some('>') => {
+                        self.inner.next();
+                        Token::RightShift
+                    }
                     _ => Token::GreaterThan
                 },
                 '!' => {
@@ -134,6 +207,51 @@ impl<T: Iterator<Item=char>> TokenIterator<T> {
         panic!("Error - Received no token but expected a whitespace")
     }
 
+    // Reads up to the closing quote, replacing escape sequences with the characters they stand for
+    fn read_string_literal_token(&mut self) -> Option<Token> {
+        self.inner.next();
+        let mut result = String::new();
+        loop {
+            match self.inner.next() {
+                Some('"') => return Some(Token::StringLiteral(result)),
+                Some('\\') => result.push(self.read_escape_sequence("string")),
+                Some('\n') | None => panic!("Error - Unterminated string literal"),
+                Some(c) => result.push(c),
+            }
+        }
+    }
+
+    // A character literal is an integer holding the code of the character
+    fn read_character_literal_token(&mut self) -> Option<Token> {
+        self.inner.next();
+        let c = match self.inner.next() {
+            Some('\\') => self.read_escape_sequence("character"),
+            Some('\'') | Some('\n') | None => panic!("Error - Expected a character in character literal"),
+            Some(c) => c,
+        };
+        match self.inner.next() {
+            Some('\'') => {}
+            _ => panic!("Error - Unterminated character literal"),
+        }
+        if !c.is_ascii() {
+            panic!("Unsupported character [{}] in character literal, only ASCII fits in a char", c);
+        }
+        Some(Token::U64(c as u64))
+    }
+
+    fn read_escape_sequence(&mut self, literal: &str) -> char {
+        match self.inner.next() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('0') => '\0',
+            Some('\\') => '\\',
+            Some('"') => '"',
+            Some('\'') => '\'',
+            Some(v) => panic!("Unsupported escape sequence [\\{}] in {} literal", v, literal),
+            None => panic!("Expected an escape sequence but got nothing"),
+        }
+    }
+
     fn read_alphabetic_token(&mut self) -> Option<Token> {
         let mut result = String::new();
         while self.inner.peek().map_or_else(|| false, |x| x.is_alphanumeric()) {
@@ -151,14 +269,16 @@ impl<T: Iterator<Item=char>> TokenIterator<T> {
     }
 
     fn read_int_lit_token(&mut self) -> Option<Token> {
-        let mut result = 0;
+        let mut result: u64 = 0;
 
-        while self.inner.peek().map_or_else(|| false, |x| x.is_digit(10)) {
+        while self.inner.peek().map_or_else(|| false, |x| x.is_ascii_digit()) {
             let next = self.inner.next().unwrap();
-            result = (result * 10) + next.to_digit(10).unwrap()
+            result = result.checked_mul(10)
+                .and_then(|r| r.checked_add(next.to_digit(10).unwrap() as u64))
+                .unwrap_or_else(|| panic!("Integer literal does not fit in 64 bits"))
         }
 
-        return Some(Token::U32(result));
+        Some(Token::U64(result))
     }
 }
 
@@ -167,16 +287,22 @@ impl<T: Iterator<Item=char>> Iterator for TokenIterator<T> {
     fn next(&mut self) -> Option<Token> {
         if let Some(&c) = self.inner.peek() {
             debug!("Peeked a char: [{}]", c);
-            if c.is_digit(10) {
+            if c.is_ascii_digit() {
                 return self.read_int_lit_token();
             }
             if c.is_alphabetic() {
                 return self.read_alphabetic_token();
             }
+            if c == '"' {
+                return self.read_string_literal_token();
+            }
+            if c == '\'' {
+                return self.read_character_literal_token();
+            }
             return self.read_symbol();
         }
 
-        return None;
+        None
     }
 }
 
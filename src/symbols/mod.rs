@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use log::debug;
+
+use crate::asm::parameter_offset;
+use crate::ast::*;
+use crate::types::Type;
+
+#[derive(Debug, Clone)]
+pub enum SymbolTableError {
+    Undeclared(String, Location),
+    Redeclared(String, Location, Location),
+}
+
+impl fmt::Display for SymbolTableError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SymbolTableError::Undeclared(name, location) =>
+                write!(f, "Use of undeclared variable [{}] on {}", name, location),
+            SymbolTableError::Redeclared(name, location, previous) =>
+                write!(f, "Redeclaration of [{}] on {}, previously declared on {}", name, location, previous),
+        }
+    }
+}
+
+impl std::error::Error for SymbolTableError {}
+
+/// An index into the `SymbolTable`, handed to code generation in place of a name
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct SymbolId(pub u32);
+
+/// Where the value of a symbol lives while the program runs
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Storage {
+    /// A named global in the data section
+    Global,
+    /// A slot in the stack frame of `main`, at the given offset from the frame pointer
+    Frame(i32),
+}
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub ty: Type,
+    pub location: Location,
+    pub storage: Storage,
+}
+
+#[derive(Debug, Clone)]
+pub struct SymbolTable {
+    symbols: Vec<Symbol>,
+    names: HashMap<String, SymbolId>,
+    /// How far below the frame pointer the variables of `main` reach, starting below the slots
+    /// its parameters are saved in
+    frame: u32,
+}
+
+impl Default for SymbolTable {
+    fn default() -> Self {
+        SymbolTable::new()
+    }
+}
+
+impl SymbolTable {
+    pub fn new() -> SymbolTable {
+        SymbolTable {
+            symbols: Vec::new(),
+            names: HashMap::new(),
+            frame: -parameter_offset(1) as u32,
+        }
+    }
+
+    /// The bytes the frame of `main` needs below the frame pointer for its parameters and
+    /// variables, kept a multiple of 16 so the stack stays aligned for calls
+    pub fn frame_size(&self) -> u32 {
+        self.frame.div_ceil(16) * 16
+    }
+
+    pub fn declare(&mut self, name: String, ty: Type, location: Location, storage: Storage) -> Result<SymbolId, SymbolTableError> {
+        if let Some(id) = self.names.get(&name) {
+            return Err(SymbolTableError::Redeclared(name, location, self.symbol(*id).location));
+        }
+
+        let id = SymbolId(self.symbols.len() as u32);
+        debug!("Declared symbol {:?} as {:?}", name, id);
+        self.names.insert(name.clone(), id);
+        self.symbols.push(Symbol { name, ty, location, storage });
+        Ok(id)
+    }
+
+    pub fn lookup(&self, name: &str, location: Location) -> Result<SymbolId, SymbolTableError> {
+        self.names.get(name)
+            .copied()
+            .ok_or_else(|| SymbolTableError::Undeclared(name.to_string(), location))
+    }
+
+    pub fn symbol(&self, id: SymbolId) -> &Symbol {
+        &self.symbols[id.0 as usize]
+    }
+
+    /// Replaces every identifier in the tree with the symbol it refers to, declaring new symbols
+    /// for `Declaration` nodes. The initialiser of a declaration is resolved before the name is
+    /// declared so `int x = x;` is reported as a use of an undeclared variable.
+    pub fn resolve(&mut self, ast: AbstractSyntaxTreeNode) -> Result<AbstractSyntaxTreeNode, SymbolTableError> {
+        match ast {
+            AbstractSyntaxTreeNode::Construct(kind @ AbstractSyntaxTreeConstructNodeType::Declaration, declaration) |
+            AbstractSyntaxTreeNode::Construct(kind @ AbstractSyntaxTreeConstructNodeType::Local, declaration) => {
+                let local = kind == AbstractSyntaxTreeConstructNodeType::Local;
+                let declaration = match *declaration {
+                    AbstractSyntaxTreeNode::Expression(AbstractSyntaxTreeExpressionNodeType::Assignment, ty, identifier, expression) => {
+                        let expression = self.resolve(*expression)?;
+                        let storage = self.storage(&identifier, local);
+                        AbstractSyntaxTreeNode::new_typed_interior(
+                            AbstractSyntaxTreeExpressionNodeType::Assignment,
+                            ty,
+                            self.declare_identifier(*identifier, storage)?,
+                            expression,
+                        )
+                    }
+                    identifier => {
+                        let storage = self.storage(&identifier, local);
+                        self.declare_identifier(identifier, storage)?
+                    }
+                };
+                Ok(AbstractSyntaxTreeNode::new_construct(kind, declaration))
+            }
+            AbstractSyntaxTreeNode::Construct(AbstractSyntaxTreeConstructNodeType::Parameter(index), identifier) => {
+                let parameter = self.declare_identifier(*identifier, Storage::Frame(parameter_offset(index)))?;
+                Ok(AbstractSyntaxTreeNode::new_construct(AbstractSyntaxTreeConstructNodeType::Parameter(index), parameter))
+            }
+            AbstractSyntaxTreeNode::Construct(op, left) =>
+                Ok(AbstractSyntaxTreeNode::new_construct(op, self.resolve(*left)?)),
+            AbstractSyntaxTreeNode::Call(op, ty, arguments) => {
+                let arguments = arguments.into_iter().map(|a| self.resolve(a)).collect::<Result<Vec<_>, _>>()?;
+                Ok(AbstractSyntaxTreeNode::new_typed_call(op, ty, arguments))
+            }
+            AbstractSyntaxTreeNode::Compound(op, left, statements) => {
+                let left = self.resolve(*left)?;
+                let statements = statements.into_iter().map(|s| self.resolve(s)).collect::<Result<Vec<_>, _>>()?;
+                Ok(AbstractSyntaxTreeNode::new_compound(op, left, statements))
+            }
+            AbstractSyntaxTreeNode::Expression(op, ty, left, right) =>
+                Ok(AbstractSyntaxTreeNode::new_typed_interior(op, ty, self.resolve(*left)?, self.resolve(*right)?)),
+            AbstractSyntaxTreeNode::Unary(op, ty, left) =>
+                Ok(AbstractSyntaxTreeNode::new_unary(op, ty, self.resolve(*left)?)),
+            AbstractSyntaxTreeNode::Ternary(op, ty, left, middle, right) =>
+                Ok(AbstractSyntaxTreeNode::new_typed_ternary(op, ty, self.resolve(*left)?, self.resolve(*middle)?, self.resolve(*right)?)),
+            AbstractSyntaxTreeNode::Leaf(AbstractSyntaxTreeLeafNodeType::Identifier(name, location), ty) =>
+                Ok(AbstractSyntaxTreeNode::new_typed_leaf_node(AbstractSyntaxTreeLeafNodeType::Symbol(self.lookup(&name, location)?, location), ty)),
+            leaf @ AbstractSyntaxTreeNode::Leaf(_, _) => Ok(leaf),
+        }
+    }
+
+    // Gives a variable of `main` the next slot of its frame that suits its type, and anything
+    // else a global
+    fn storage(&mut self, identifier: &AbstractSyntaxTreeNode, local: bool) -> Storage {
+        if !local {
+            return Storage::Global;
+        }
+        let ty = identifier.ty();
+        let alignment = ty.alignment().max(1);
+        self.frame = (self.frame + ty.size()).div_ceil(alignment) * alignment;
+        Storage::Frame(-(self.frame as i32))
+    }
+
+    fn declare_identifier(&mut self, identifier: AbstractSyntaxTreeNode, storage: Storage) -> Result<AbstractSyntaxTreeNode, SymbolTableError> {
+        match identifier {
+            AbstractSyntaxTreeNode::Leaf(AbstractSyntaxTreeLeafNodeType::Identifier(name, location), ty) => {
+                let id = self.declare(name, ty.clone(), location, storage)?;
+                Ok(AbstractSyntaxTreeNode::new_typed_leaf_node(AbstractSyntaxTreeLeafNodeType::Symbol(id, location), ty))
+            }
+            unhandled => panic!("Expected an identifier to declare but got: {:?}", unhandled),
+        }
+    }
+}
@@ -0,0 +1,777 @@
+use std::fmt;
+use std::rc::Rc;
+
+use crate::ast::*;
+use crate::scanner::KeywordToken;
+use crate::symbols::SymbolTable;
+
+#[derive(Debug, Clone)]
+pub enum TypeError {
+    Error(String, Option<Location>),
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TypeError::Error(message, Some(location)) => write!(f, "{} on {}", message, location),
+            TypeError::Error(message, None) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for TypeError {}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Type {
+    /// Carried by nodes that have not been type checked yet
+    None,
+    Void,
+    Char,
+    Int,
+    Long,
+    UnsignedChar,
+    UnsignedInt,
+    UnsignedLong,
+    Pointer(Box<Type>),
+    Array(Box<Type>, u32),
+    Struct(Rc<Struct>),
+}
+
+/// A member of a struct and where it is placed from the start of the struct
+#[derive(Debug, PartialEq, Clone)]
+pub struct Member {
+    pub name: String,
+    pub ty: Type,
+    pub offset: u32,
+}
+
+/// Whether the members of a `Struct` follow each other or share the same storage
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum StructKind {
+    Struct,
+    Union,
+}
+
+impl fmt::Display for StructKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StructKind::Struct => write!(f, "struct"),
+            StructKind::Union => write!(f, "union"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Struct {
+    pub kind: StructKind,
+    pub name: String,
+    pub members: Vec<Member>,
+    pub size: u32,
+    pub alignment: u32,
+}
+
+impl Struct {
+    /// Lays the members out in order as the System V ABI does: each member starts at the next
+    /// multiple of its alignment, and the struct is aligned as its most aligned member and
+    /// padded to a multiple of that alignment so arrays of it keep every member aligned. Every
+    /// member of a union starts at zero, making it as large as its largest member.
+    pub fn new(kind: StructKind, name: String, members: Vec<(String, Type)>) -> Result<Struct, String> {
+        let mut laid_out: Vec<Member> = Vec::new();
+        let mut size = 0;
+        let mut alignment = 1;
+
+        for (member, ty) in members {
+            if laid_out.iter().any(|m| m.name == member) {
+                return Err(format!("Duplicate member [{}] in [{} {}]", member, kind, name));
+            }
+            if ty.size() == 0 {
+                return Err(format!("Member [{}] of [{} {}] can not have type [{}]", member, kind, name, ty));
+            }
+            let offset = match kind {
+                StructKind::Struct => align(size, ty.alignment()),
+                StructKind::Union => 0,
+            };
+            alignment = alignment.max(ty.alignment());
+            size = size.max(offset + ty.size());
+            laid_out.push(Member { name: member, ty, offset });
+        }
+
+        if laid_out.is_empty() {
+            return Err(format!("[{} {}] must have at least one member", kind, name));
+        }
+
+        Ok(Struct {
+            kind,
+            name,
+            members: laid_out,
+            size: align(size, alignment),
+            alignment,
+        })
+    }
+
+    pub fn member(&self, name: &str) -> Option<&Member> {
+        self.members.iter().find(|m| m.name == name)
+    }
+}
+
+/// Rounds `offset` up to the next multiple of `alignment`
+fn align(offset: u32, alignment: u32) -> u32 {
+    offset.div_ceil(alignment) * alignment
+}
+
+impl std::convert::TryFrom<KeywordToken> for Type {
+    type Error = String;
+
+    fn try_from(value: KeywordToken) -> Result<Self, Self::Error> {
+        match value {
+            KeywordToken::Void => Ok(Type::Void),
+            KeywordToken::Char => Ok(Type::Char),
+            KeywordToken::Int => Ok(Type::Int),
+            KeywordToken::Long => Ok(Type::Long),
+            v => Err(format!("Unable to handle {:?} as a type", v))
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::None => write!(f, "<none>"),
+            Type::Void => write!(f, "void"),
+            Type::Char => write!(f, "char"),
+            Type::Int => write!(f, "int"),
+            Type::Long => write!(f, "long"),
+            Type::UnsignedChar => write!(f, "unsigned char"),
+            Type::UnsignedInt => write!(f, "unsigned int"),
+            Type::UnsignedLong => write!(f, "unsigned long"),
+            Type::Pointer(to) => write!(f, "{} *", to),
+            Type::Array(_, _) => {
+                // The dimensions are written outermost first after the element type
+                let mut ty = self;
+                let mut dimensions = String::new();
+                while let Type::Array(element, length) = ty {
+                    dimensions.push_str(&format!("[{}]", length));
+                    ty = element;
+                }
+                write!(f, "{}{}", ty, dimensions)
+            }
+            Type::Struct(layout) => write!(f, "{} {}", layout.kind, layout.name),
+        }
+    }
+}
+
+impl Type {
+    pub fn pointer_to(self) -> Type {
+        Type::Pointer(Box::new(self))
+    }
+
+    /// The unsigned type of the same width, as selected by the `unsigned` keyword
+    pub fn to_unsigned(&self) -> Result<Type, String> {
+        match self {
+            Type::Char | Type::UnsignedChar => Ok(Type::UnsignedChar),
+            Type::Int | Type::UnsignedInt => Ok(Type::UnsignedInt),
+            Type::Long | Type::UnsignedLong => Ok(Type::UnsignedLong),
+            other => Err(format!("Unable to make [{}] unsigned", other)),
+        }
+    }
+
+    /// The width in bytes of a value of this type
+    pub fn size(&self) -> u32 {
+        match self {
+            Type::None => panic!("Unable to size a value that has not been type checked"),
+            Type::Void => 0,
+            Type::Char | Type::UnsignedChar => 1,
+            Type::Int | Type::UnsignedInt => 4,
+            Type::Long | Type::UnsignedLong => 8,
+            Type::Pointer(_) => 8,
+            Type::Array(element, length) => element.size() * length,
+            Type::Struct(layout) => layout.size,
+        }
+    }
+
+    /// The boundary in bytes a value of this type is placed on in memory
+    pub fn alignment(&self) -> u32 {
+        match self {
+            Type::Array(element, _) => element.alignment(),
+            Type::Struct(layout) => layout.alignment,
+            other => other.size(),
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.is_signed() || matches!(self, Type::UnsignedChar | Type::UnsignedInt | Type::UnsignedLong)
+    }
+
+    pub fn is_pointer(&self) -> bool {
+        matches!(self, Type::Pointer(_))
+    }
+
+    /// Whether values of this type are compared, divided, shifted and printed
+    /// as two's complement numbers. Pointers are unsigned.
+    pub fn is_signed(&self) -> bool {
+        matches!(self, Type::Char | Type::Int | Type::Long)
+    }
+
+    /// Whether every value of `other` is also a value of this type, in which
+    /// case converting from `other` leaves the register untouched
+    pub fn can_represent(&self, other: &Type) -> bool {
+        if self.size() == 8 && other.is_pointer() {
+            return true;
+        }
+        match (self.is_signed(), other.is_signed()) {
+            (true, true) | (false, false) => self.size() >= other.size(),
+            (true, false) => self.size() > other.size(),
+            (false, true) => false,
+        }
+    }
+
+    /// The value a register of this type holds for the constant `value`: its low bits, sign
+    /// or zero extended to 64 bits
+    pub fn canonical(&self, value: u64) -> u64 {
+        match (self.size(), self.is_signed()) {
+            (1, true) => value as i8 as u64,
+            (1, false) => value as u8 as u64,
+            (4, true) => value as i32 as u64,
+            (4, false) => value as u32 as u64,
+            _ => value,
+        }
+    }
+
+    /// Integer promotion: anything narrower than an `int` is computed as an `int`
+    pub fn promote(&self) -> Type {
+        match self {
+            Type::Char | Type::UnsignedChar => Type::Int,
+            other => other.clone(),
+        }
+    }
+
+    /// The type both operands of an arithmetic operator are converted to: the
+    /// wider of the two promoted types, preferring unsigned when they are as wide
+    fn common(left: &Type, right: &Type) -> Type {
+        let (left, right) = (left.promote(), right.promote());
+        if left.size() > right.size() || (left.size() == right.size() && !left.is_signed()) { left } else { right }
+    }
+
+    fn is_assignable_from(&self, from: &Type) -> bool {
+        match (self, from) {
+            (to, from) if to.is_integer() && from.is_integer() => true,
+            (Type::Pointer(to), Type::Pointer(from)) => to == from || **to == Type::Void || **from == Type::Void,
+            _ => false,
+        }
+    }
+}
+
+pub struct TypeChecker<'a> {
+    symbols: &'a SymbolTable,
+}
+
+impl<'a> TypeChecker<'a> {
+    pub fn new(symbols: &'a SymbolTable) -> TypeChecker<'a> {
+        TypeChecker { symbols }
+    }
+
+    /// Annotates every expression in the tree with its type, rejecting operations that make no
+    /// sense for the types involved. Values are held widened to 64 bits in registers so implicit
+    /// integer promotions only change the annotated type; narrowing happens when storing.
+    pub fn check(&self, ast: AbstractSyntaxTreeNode) -> Result<AbstractSyntaxTreeNode, TypeError> {
+        Ok(decay(self.check_object(ast)?))
+    }
+
+    /// Type checks a node without turning arrays into pointers to their first element, for the
+    /// places that need the array itself: declaring it, assigning to it and taking its address
+    fn check_object(&self, ast: AbstractSyntaxTreeNode) -> Result<AbstractSyntaxTreeNode, TypeError> {
+        match ast {
+            AbstractSyntaxTreeNode::Construct(kind @ AbstractSyntaxTreeConstructNodeType::Declaration, declaration) |
+            AbstractSyntaxTreeNode::Construct(kind @ AbstractSyntaxTreeConstructNodeType::Local, declaration) => {
+                if *self.declared_type(&declaration) == Type::Void {
+                    return Err(TypeError::Error("Variables can not be declared as void".to_string(), location_of(&declaration)));
+                }
+                Ok(AbstractSyntaxTreeNode::new_construct(kind, self.check_object(*declaration)?))
+            }
+            AbstractSyntaxTreeNode::Construct(AbstractSyntaxTreeConstructNodeType::Parameter(index), parameter) =>
+                Ok(AbstractSyntaxTreeNode::new_construct(AbstractSyntaxTreeConstructNodeType::Parameter(index), self.check_object(*parameter)?)),
+            AbstractSyntaxTreeNode::Construct(AbstractSyntaxTreeConstructNodeType::Print, expression) => {
+                // Integers are printed as numbers and `char *` as the string it points to
+                let expression = self.check(*expression)?;
+                if !expression.ty().is_integer() && *expression.ty() != Type::Char.pointer_to() {
+                    return Err(TypeError::Error(format!("Unable to print a value of type [{}]", expression.ty()), location_of(&expression)));
+                }
+                Ok(AbstractSyntaxTreeNode::new_construct(AbstractSyntaxTreeConstructNodeType::Print, expression))
+            }
+            AbstractSyntaxTreeNode::Construct(AbstractSyntaxTreeConstructNodeType::Return, expression) => {
+                let expression = self.check(*expression)?;
+                if !expression.ty().is_integer() {
+                    return Err(TypeError::Error(format!("Unable to return a value of type [{}]", expression.ty()), location_of(&expression)));
+                }
+                Ok(AbstractSyntaxTreeNode::new_construct(AbstractSyntaxTreeConstructNodeType::Return, convert(expression, &Type::Int)))
+            }
+            AbstractSyntaxTreeNode::Compound(AbstractSyntaxTreeCompoundNodeType::Switch, condition, statements) => {
+                // Case values are converted to the promoted type of the condition up front, so
+                // two that only differ in bits the condition can not hold are duplicates
+                let condition = self.check(*condition)?;
+                if !condition.ty().is_integer() {
+                    return Err(TypeError::Error(format!("Unable to switch on a value of type [{}]", condition.ty()), location_of(&condition)));
+                }
+                let ty = condition.ty().promote();
+
+                let mut cases = Vec::new();
+                let mut checked = Vec::new();
+                for statement in statements {
+                    checked.push(match statement {
+                        AbstractSyntaxTreeNode::Unary(AbstractSyntaxTreeUnaryNodeType::Case(location), _, value) => {
+                            let value = self.check(*value)?;
+                            let value = match constant(&value) {
+                                Some((value, _)) => ty.canonical(value),
+                                _ => return Err(TypeError::Error("Case labels must be integer constant expressions".to_string(), Some(location))),
+                            };
+                            if cases.contains(&value) {
+                                return Err(TypeError::Error(format!("Duplicate case value [{}] in switch on [{}]", value, ty), Some(location)));
+                            }
+                            cases.push(value);
+                            AbstractSyntaxTreeNode::new_typed_leaf_node(AbstractSyntaxTreeLeafNodeType::Case(value), ty.clone())
+                        }
+                        statement => self.check(statement)?,
+                    });
+                }
+                Ok(AbstractSyntaxTreeNode::new_compound(AbstractSyntaxTreeCompoundNodeType::Switch, convert(condition, &ty), checked))
+            }
+            AbstractSyntaxTreeNode::Call(call, _, arguments) => {
+                let mut arguments = arguments.into_iter().map(|a| self.check(a)).collect::<Result<Vec<_>, _>>()?;
+                let location = arguments.iter().find_map(location_of);
+                let ty = match call {
+                    AbstractSyntaxTreeCallNodeType::Read => {
+                        match arguments[0].ty() {
+                            Type::Pointer(to) if to.is_integer() => Type::Int,
+                            other => return Err(TypeError::Error(format!("Unable to read into a value of type [{}], it needs a pointer to an integer", other), location)),
+                        }
+                    }
+                    AbstractSyntaxTreeCallNodeType::ReadLine => {
+                        if *arguments[0].ty() != Type::Char.pointer_to() || !arguments[1].ty().is_integer() {
+                            return Err(TypeError::Error(format!("Unable to read a line into [{}] of size [{}], it needs a [char *] and an integer", arguments[0].ty(), arguments[1].ty()), location));
+                        }
+                        let size = arguments.pop().unwrap();
+                        arguments.push(convert(size, &Type::Long));
+                        Type::Long
+                    }
+                    AbstractSyntaxTreeCallNodeType::Exit => {
+                        if !arguments[0].ty().is_integer() {
+                            return Err(TypeError::Error(format!("Unable to exit with a value of type [{}]", arguments[0].ty()), location));
+                        }
+                        let status = arguments.pop().unwrap();
+                        arguments.push(convert(status, &Type::Int));
+                        Type::Void
+                    }
+                };
+                Ok(AbstractSyntaxTreeNode::new_typed_call(call, ty, arguments))
+            }
+            AbstractSyntaxTreeNode::Expression(AbstractSyntaxTreeExpressionNodeType::Assignment, _, left, right) => {
+                let left = self.check_object(*left)?;
+                let right = self.check(*right)?;
+                if let Type::Array(_, _) | Type::Struct(_) = left.ty() {
+                    return Err(TypeError::Error(format!("Unable to assign to a value of type [{}]", left.ty()), location_of(&left)));
+                }
+                if !is_lvalue(&left) {
+                    return Err(TypeError::Error("Unable to assign to an expression that is not a variable or dereferenced pointer".to_string(), location_of(&left)));
+                }
+                if !is_assignable(left.ty(), &right) {
+                    return Err(TypeError::Error(format!("Unable to assign a value of type [{}] to [{}]", right.ty(), left.ty()), location_of(&left)));
+                }
+                let ty = left.ty().clone();
+                Ok(AbstractSyntaxTreeNode::new_typed_interior(AbstractSyntaxTreeExpressionNodeType::Assignment, ty, left, right))
+            }
+            AbstractSyntaxTreeNode::Expression(op @ AbstractSyntaxTreeExpressionNodeType::LeftShift, _, left, right) |
+            AbstractSyntaxTreeNode::Expression(op @ AbstractSyntaxTreeExpressionNodeType::RightShift, _, left, right) => {
+                // The result of a shift has the type of its promoted left operand, the
+                // right operand only supplies the count
+                let left = self.check(*left)?;
+                let right = self.check(*right)?;
+                if !left.ty().is_integer() || !right.ty().is_integer() {
+                    return Err(TypeError::Error(format!("Unable to apply {:?} to [{}] and [{}]", op, left.ty(), right.ty()), location_of(&left)));
+                }
+                let ty = left.ty().promote();
+                Ok(AbstractSyntaxTreeNode::new_typed_interior(op, ty.clone(), convert(left, &ty), right))
+            }
+            AbstractSyntaxTreeNode::Expression(AbstractSyntaxTreeExpressionNodeType::Index, _, array, index) => {
+                // `array[index]` is `*(array + index)`, with the index checked against the
+                // length of the array when it has one
+                let array = self.check_object(*array)?;
+                let index = self.check(*index)?;
+                if !index.ty().is_integer() {
+                    return Err(TypeError::Error(format!("Unable to index with a value of type [{}]", index.ty()), location_of(&index)));
+                }
+
+                let mut index = convert(index, &Type::Long);
+                if let (Type::Array(_, length), Some(location)) = (array.ty(), location_of(&array)) {
+                    index = AbstractSyntaxTreeNode::new_unary(AbstractSyntaxTreeUnaryNodeType::BoundsCheck(*length, location), Type::Long, index);
+                }
+
+                let pointer = decay(array);
+                let ty = match pointer.ty() {
+                    Type::Pointer(to) if **to != Type::Void => (**to).clone(),
+                    other => return Err(TypeError::Error(format!("Unable to index a value of type [{}]", other), location_of(&pointer))),
+                };
+                let address = check_pointer_arithmetic(AbstractSyntaxTreeExpressionNodeType::Add, pointer, index)?;
+                Ok(AbstractSyntaxTreeNode::new_unary(AbstractSyntaxTreeUnaryNodeType::Dereference, ty, address))
+            }
+            AbstractSyntaxTreeNode::Expression(op, _, left, right) => {
+                let left = self.check(*left)?;
+                let right = self.check(*right)?;
+                let additive = op == AbstractSyntaxTreeExpressionNodeType::Add || op == AbstractSyntaxTreeExpressionNodeType::Subtract;
+                if additive && (left.ty().is_pointer() || right.ty().is_pointer()) {
+                    return check_pointer_arithmetic(op, left, right);
+                }
+                let (ty, operands) = match (left.ty(), right.ty()) {
+                    (l, r) if l.is_integer() && r.is_integer() && op.is_comparison() => (Type::Int, Type::common(l, r)),
+                    (l, r) if l.is_integer() && r.is_integer() => (Type::common(l, r), Type::common(l, r)),
+                    (l, _) if l.is_pointer() && op.is_comparison() && is_assignable(l, &right) => (Type::Int, l.clone()),
+                    (_, r) if r.is_pointer() && op.is_comparison() && is_assignable(r, &left) => (Type::Int, r.clone()),
+                    (l, r) => return Err(TypeError::Error(format!("Unable to apply {:?} to [{}] and [{}]", op, l, r), location_of(&left))),
+                };
+                Ok(AbstractSyntaxTreeNode::new_typed_interior(op, ty, convert(left, &operands), convert(right, &operands)))
+            }
+            AbstractSyntaxTreeNode::Unary(AbstractSyntaxTreeUnaryNodeType::AddressOf, _, left) =>
+                address_of(self.check_object(*left)?),
+            AbstractSyntaxTreeNode::Unary(AbstractSyntaxTreeUnaryNodeType::Member(name), _, left) => {
+                // `s.a` is the value found at the offset of `a` from the address of `s`
+                let left = self.check_object(*left)?;
+                let layout = match left.ty() {
+                    Type::Struct(layout) => layout.clone(),
+                    other => return Err(TypeError::Error(format!("Unable to access member [{}] of a value of type [{}]", name, other), location_of(&left))),
+                };
+                let member = match layout.member(&name) {
+                    Some(member) => member,
+                    None => return Err(TypeError::Error(format!("[{}] has no member [{}]", left.ty(), name), location_of(&left))),
+                };
+
+                let ty = member.ty.clone().pointer_to();
+                let mut address = convert(address_of(left)?, &ty);
+                if member.offset != 0 {
+                    let offset = AbstractSyntaxTreeNode::new_typed_leaf_node(AbstractSyntaxTreeLeafNodeType::U64(member.offset as u64), Type::Long);
+                    address = AbstractSyntaxTreeNode::new_typed_interior(AbstractSyntaxTreeExpressionNodeType::Add, ty, address, offset);
+                }
+                Ok(AbstractSyntaxTreeNode::new_unary(AbstractSyntaxTreeUnaryNodeType::Dereference, member.ty.clone(), address))
+            }
+            AbstractSyntaxTreeNode::Unary(AbstractSyntaxTreeUnaryNodeType::Cast, ty, left) => {
+                // Integers and pointers convert to one another, anything else has to be left alone.
+                // The conversion is kept even between identical types so the result is never an lvalue.
+                let left = self.check(*left)?;
+                let scalar = |ty: &Type| ty.is_integer() || ty.is_pointer();
+                if !scalar(&ty) || !scalar(left.ty()) {
+                    return Err(TypeError::Error(format!("Unable to cast a value of type [{}] to [{}]", left.ty(), ty), location_of(&left)));
+                }
+                Ok(AbstractSyntaxTreeNode::new_unary(AbstractSyntaxTreeUnaryNodeType::Convert, ty, left))
+            }
+            AbstractSyntaxTreeNode::Unary(AbstractSyntaxTreeUnaryNodeType::Sizeof, _, left) => {
+                let left = self.check_object(*left)?;
+                if left.ty().size() == 0 {
+                    return Err(TypeError::Error(format!("Unable to take the size of a value of type [{}]", left.ty()), location_of(&left)));
+                }
+                let size = AbstractSyntaxTreeLeafNodeType::U64(left.ty().size() as u64);
+                Ok(AbstractSyntaxTreeNode::new_typed_leaf_node(size, Type::UnsignedLong))
+            }
+            AbstractSyntaxTreeNode::Unary(AbstractSyntaxTreeUnaryNodeType::Dereference, _, left) => {
+                let left = self.check(*left)?;
+                let ty = match left.ty() {
+                    Type::Pointer(to) if **to != Type::Void => (**to).clone(),
+                    other => return Err(TypeError::Error(format!("Unable to dereference a value of type [{}]", other), location_of(&left))),
+                };
+                Ok(AbstractSyntaxTreeNode::new_unary(AbstractSyntaxTreeUnaryNodeType::Dereference, ty, left))
+            }
+            AbstractSyntaxTreeNode::Ternary(op, _, condition, when_true, when_false) => {
+                let condition = self.check(*condition)?;
+                let when_true = self.check(*when_true)?;
+                let when_false = self.check(*when_false)?;
+                if !condition.ty().is_integer() && !condition.ty().is_pointer() {
+                    return Err(TypeError::Error(format!("Unable to use a value of type [{}] as a condition", condition.ty()), location_of(&condition)));
+                }
+                let ty = match (when_true.ty(), when_false.ty()) {
+                    (l, r) if l.is_integer() && r.is_integer() => Type::common(l, r),
+                    (l, _) if l.is_pointer() && is_assignable(l, &when_false) => l.clone(),
+                    (_, r) if r.is_pointer() && is_assignable(r, &when_true) => r.clone(),
+                    (l, r) => return Err(TypeError::Error(format!("Mismatched types [{}] and [{}] in conditional", l, r), location_of(&when_true))),
+                };
+                Ok(AbstractSyntaxTreeNode::new_typed_ternary(op, ty.clone(), condition, convert(when_true, &ty), convert(when_false, &ty)))
+            }
+            // Literals the parser already knows the type of, such as `sizeof`, keep it
+            literal @ AbstractSyntaxTreeNode::Leaf(AbstractSyntaxTreeLeafNodeType::U64(_), Type::UnsignedLong) => Ok(literal),
+            AbstractSyntaxTreeNode::Leaf(AbstractSyntaxTreeLeafNodeType::U64(v), _) => {
+                let ty = if v <= i32::MAX as u64 {
+                    Type::Int
+                } else if v <= i64::MAX as u64 {
+                    Type::Long
+                } else {
+                    Type::UnsignedLong
+                };
+                Ok(AbstractSyntaxTreeNode::new_typed_leaf_node(AbstractSyntaxTreeLeafNodeType::U64(v), ty))
+            }
+            AbstractSyntaxTreeNode::Leaf(AbstractSyntaxTreeLeafNodeType::Symbol(id, location), _) => {
+                let ty = self.symbols.symbol(id).ty.clone();
+                Ok(AbstractSyntaxTreeNode::new_typed_leaf_node(AbstractSyntaxTreeLeafNodeType::Symbol(id, location), ty))
+            }
+            AbstractSyntaxTreeNode::Leaf(AbstractSyntaxTreeLeafNodeType::String(string), _) =>
+                Ok(AbstractSyntaxTreeNode::new_typed_leaf_node(AbstractSyntaxTreeLeafNodeType::String(string), Type::Char.pointer_to())),
+            leaf @ AbstractSyntaxTreeNode::Leaf(AbstractSyntaxTreeLeafNodeType::Default, _) |
+            leaf @ AbstractSyntaxTreeNode::Leaf(AbstractSyntaxTreeLeafNodeType::Break, _) => Ok(leaf),
+            unhandled => panic!("Unable to type check: {:?}", unhandled),
+        }
+    }
+
+    fn declared_type(&self, declaration: &AbstractSyntaxTreeNode) -> &Type {
+        match declaration {
+            AbstractSyntaxTreeNode::Leaf(AbstractSyntaxTreeLeafNodeType::Symbol(id, _), _) => &self.symbols.symbol(*id).ty,
+            AbstractSyntaxTreeNode::Expression(AbstractSyntaxTreeExpressionNodeType::Assignment, _, left, _) => self.declared_type(left),
+            unhandled => panic!("Expected a declaration but got: {:?}", unhandled),
+        }
+    }
+}
+
+/// Adding an integer to a pointer moves it by that many of the values it points at, so the
+/// integer is scaled by the size of the pointee. Subtracting two pointers gives the number of
+/// values between them.
+fn check_pointer_arithmetic(op: AbstractSyntaxTreeExpressionNodeType, left: AbstractSyntaxTreeNode, right: AbstractSyntaxTreeNode) -> Result<AbstractSyntaxTreeNode, TypeError> {
+    let location = location_of(&left).or_else(|| location_of(&right));
+    let (left_type, right_type) = (left.ty().clone(), right.ty().clone());
+    let (pointer, offset) = match (op, &left_type, &right_type) {
+        (AbstractSyntaxTreeExpressionNodeType::Subtract, Type::Pointer(l), Type::Pointer(r)) if l == r && **l != Type::Void => {
+            let size = l.size();
+            let difference = AbstractSyntaxTreeNode::new_typed_interior(op, Type::Long, left, right);
+            return Ok(scale(AbstractSyntaxTreeExpressionNodeType::Divide, difference, size));
+        }
+        (_, l, r) if l.is_pointer() && r.is_integer() => (left, right),
+        (AbstractSyntaxTreeExpressionNodeType::Add, l, r) if l.is_integer() && r.is_pointer() => (right, left),
+        (_, l, r) => return Err(TypeError::Error(format!("Unable to apply {:?} to [{}] and [{}]", op, l, r), location)),
+    };
+
+    let ty = pointer.ty().clone();
+    let size = match &ty {
+        Type::Pointer(to) if **to != Type::Void => to.size(),
+        _ => return Err(TypeError::Error(format!("Unable to do arithmetic on a value of type [{}]", ty), location)),
+    };
+    let offset = scale(AbstractSyntaxTreeExpressionNodeType::Multiply, convert(offset, &Type::Long), size);
+    Ok(AbstractSyntaxTreeNode::new_typed_interior(op, ty, pointer, offset))
+}
+
+/// Multiplies or divides a `long` by the size of a pointee, leaving it alone for single bytes
+fn scale(op: AbstractSyntaxTreeExpressionNodeType, ast: AbstractSyntaxTreeNode, size: u32) -> AbstractSyntaxTreeNode {
+    if size == 1 {
+        return ast;
+    }
+    let size = AbstractSyntaxTreeNode::new_typed_leaf_node(AbstractSyntaxTreeLeafNodeType::U64(size as u64), Type::Long);
+    AbstractSyntaxTreeNode::new_typed_interior(op, Type::Long, ast, size)
+}
+
+/// Whether a value of the given node can be assigned to something of type `to`. As in C a
+/// literal `0` is a null pointer of every pointer type.
+fn is_assignable(to: &Type, from: &AbstractSyntaxTreeNode) -> bool {
+    let null = matches!(from, AbstractSyntaxTreeNode::Leaf(AbstractSyntaxTreeLeafNodeType::U64(0), _));
+    to.is_assignable_from(from.ty()) || (to.is_pointer() && null)
+}
+
+/// Whether the node refers to a location in memory that can be assigned to
+fn is_lvalue(ast: &AbstractSyntaxTreeNode) -> bool {
+    matches!(
+        ast,
+        AbstractSyntaxTreeNode::Leaf(AbstractSyntaxTreeLeafNodeType::Symbol(_, _), _) |
+        AbstractSyntaxTreeNode::Unary(AbstractSyntaxTreeUnaryNodeType::Dereference, _, _)
+    )
+}
+
+/// The address of a checked node, which must refer to a location in memory
+fn address_of(ast: AbstractSyntaxTreeNode) -> Result<AbstractSyntaxTreeNode, TypeError> {
+    match ast {
+        // `&*p` is just `p`
+        AbstractSyntaxTreeNode::Unary(AbstractSyntaxTreeUnaryNodeType::Dereference, _, pointer) => Ok(*pointer),
+        symbol @ AbstractSyntaxTreeNode::Leaf(AbstractSyntaxTreeLeafNodeType::Symbol(_, _), _) => {
+            let ty = symbol.ty().clone().pointer_to();
+            Ok(AbstractSyntaxTreeNode::new_unary(AbstractSyntaxTreeUnaryNodeType::AddressOf, ty, symbol))
+        }
+        other => Err(TypeError::Error("Unable to take the address of an expression that is not a variable".to_string(), location_of(&other))),
+    }
+}
+
+/// Arrays used as values become a pointer to their first element
+fn decay(ast: AbstractSyntaxTreeNode) -> AbstractSyntaxTreeNode {
+    let ty = match ast.ty() {
+        Type::Array(element, _) => (**element).clone().pointer_to(),
+        _ => return ast,
+    };
+    match ast {
+        // An array reached through a pointer starts where the pointer points
+        AbstractSyntaxTreeNode::Unary(AbstractSyntaxTreeUnaryNodeType::Dereference, _, pointer) => convert(*pointer, &ty),
+        array => AbstractSyntaxTreeNode::new_unary(AbstractSyntaxTreeUnaryNodeType::AddressOf, ty, array),
+    }
+}
+
+/// Wraps the node in a conversion to `ty` unless it already has that type
+fn convert(ast: AbstractSyntaxTreeNode, ty: &Type) -> AbstractSyntaxTreeNode {
+    if ast.ty() == ty {
+        return ast;
+    }
+    AbstractSyntaxTreeNode::new_unary(AbstractSyntaxTreeUnaryNodeType::Convert, ty.clone(), ast)
+}
+
+/// The value of an expression made only of literals, worked out as the program would at run time,
+/// along with its type. Anything reading or changing the program's state has no constant value.
+fn constant(ast: &AbstractSyntaxTreeNode) -> Option<(u64, &Type)> {
+    let value = match ast {
+        AbstractSyntaxTreeNode::Leaf(AbstractSyntaxTreeLeafNodeType::U64(value), _) => *value,
+        AbstractSyntaxTreeNode::Unary(AbstractSyntaxTreeUnaryNodeType::Convert, ty, left) if ty.is_integer() => {
+            let (value, from) = constant(left)?;
+            if !from.is_integer() {
+                return None;
+            }
+            ty.canonical(value)
+        }
+        AbstractSyntaxTreeNode::Expression(op, ty, left, right) if ty.is_integer() => {
+            let (left, operands) = constant(left)?;
+            let (right, _) = constant(right)?;
+            if !operands.is_integer() {
+                return None;
+            }
+            // Operands are held sign extended when their type is signed, so the 64 bit operations
+            // give the same result as narrower ones once it is truncated back to the type
+            let signed = operands.is_signed();
+            let value = match op {
+                AbstractSyntaxTreeExpressionNodeType::Add => left.wrapping_add(right),
+                AbstractSyntaxTreeExpressionNodeType::Subtract => left.wrapping_sub(right),
+                AbstractSyntaxTreeExpressionNodeType::Multiply => left.wrapping_mul(right),
+                AbstractSyntaxTreeExpressionNodeType::Divide if right == 0 => return None,
+                AbstractSyntaxTreeExpressionNodeType::Divide if signed => (left as i64).wrapping_div(right as i64) as u64,
+                AbstractSyntaxTreeExpressionNodeType::Divide => left / right,
+                AbstractSyntaxTreeExpressionNodeType::LeftShift if right < 64 => left << right,
+                AbstractSyntaxTreeExpressionNodeType::RightShift if right < 64 && signed => ((left as i64) >> right) as u64,
+                AbstractSyntaxTreeExpressionNodeType::RightShift if right < 64 => left >> right,
+                AbstractSyntaxTreeExpressionNodeType::LessThan if signed => ((left as i64) < (right as i64)) as u64,
+                AbstractSyntaxTreeExpressionNodeType::LessThan => (left < right) as u64,
+                AbstractSyntaxTreeExpressionNodeType::LessThanEqual if signed => ((left as i64) <= (right as i64)) as u64,
+                AbstractSyntaxTreeExpressionNodeType::LessThanEqual => (left <= right) as u64,
+                AbstractSyntaxTreeExpressionNodeType::GreaterThan if signed => ((left as i64) > (right as i64)) as u64,
+                AbstractSyntaxTreeExpressionNodeType::GreaterThan => (left > right) as u64,
+                AbstractSyntaxTreeExpressionNodeType::GreaterThanEqual if signed => ((left as i64) >= (right as i64)) as u64,
+                AbstractSyntaxTreeExpressionNodeType::GreaterThanEqual => (left >= right) as u64,
+                AbstractSyntaxTreeExpressionNodeType::Equality => (left == right) as u64,
+                AbstractSyntaxTreeExpressionNodeType::NotEqual => (left != right) as u64,
+                _ => return None,
+            };
+            ty.canonical(value)
+        }
+        AbstractSyntaxTreeNode::Ternary(AbstractSyntaxTreeTernaryNodeType::Conditional, _, condition, when_true, when_false) => {
+            let (condition, _) = constant(condition)?;
+            let (when_true, _) = constant(when_true)?;
+            let (when_false, _) = constant(when_false)?;
+            if condition != 0 { when_true } else { when_false }
+        }
+        _ => return None,
+    };
+    Some((value, ast.ty()))
+}
+
+/// The location of the first variable in the tree, the closest we have to a
+/// location for an expression
+fn location_of(ast: &AbstractSyntaxTreeNode) -> Option<Location> {
+    match ast {
+        AbstractSyntaxTreeNode::Construct(_, left) => location_of(left),
+        AbstractSyntaxTreeNode::Compound(_, left, _) => location_of(left),
+        AbstractSyntaxTreeNode::Call(_, _, arguments) => arguments.iter().find_map(location_of),
+        AbstractSyntaxTreeNode::Expression(_, _, left, right) => location_of(left).or_else(|| location_of(right)),
+        AbstractSyntaxTreeNode::Unary(_, _, left) => location_of(left),
+        AbstractSyntaxTreeNode::Ternary(_, _, left, middle, right) =>
+            location_of(left).or_else(|| location_of(middle)).or_else(|| location_of(right)),
+        AbstractSyntaxTreeNode::Leaf(AbstractSyntaxTreeLeafNodeType::Symbol(_, location), _) => Some(*location),
+        AbstractSyntaxTreeNode::Leaf(AbstractSyntaxTreeLeafNodeType::Identifier(_, location), _) => Some(*location),
+        AbstractSyntaxTreeNode::Leaf(_, _) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(kind: StructKind, members: &[(&str, Type)]) -> Result<Struct, String> {
+        let members = members.iter().map(|(name, ty)| (name.to_string(), ty.clone())).collect();
+        Struct::new(kind, "s".to_string(), members)
+    }
+
+    fn offsets(layout: &Struct) -> Vec<u32> {
+        layout.members.iter().map(|m| m.offset).collect()
+    }
+
+    #[test]
+    fn members_start_at_a_multiple_of_their_alignment() {
+        let s = layout(StructKind::Struct, &[("c", Type::Char), ("i", Type::Int), ("d", Type::Char), ("l", Type::Long)]).unwrap();
+
+        assert_eq!(offsets(&s), vec![0, 4, 8, 16]);
+        assert_eq!(s.size, 24);
+        assert_eq!(s.alignment, 8);
+    }
+
+    #[test]
+    fn struct_is_padded_to_its_alignment() {
+        let s = layout(StructKind::Struct, &[("i", Type::Int), ("c", Type::Char)]).unwrap();
+
+        assert_eq!(offsets(&s), vec![0, 4]);
+        assert_eq!(s.size, 8);
+        assert_eq!(s.alignment, 4);
+    }
+
+    #[test]
+    fn struct_of_chars_is_not_padded() {
+        let s = layout(StructKind::Struct, &[("a", Type::Char), ("b", Type::Char), ("c", Type::Char)]).unwrap();
+
+        assert_eq!(offsets(&s), vec![0, 1, 2]);
+        assert_eq!(s.size, 3);
+        assert_eq!(s.alignment, 1);
+    }
+
+    #[test]
+    fn array_member_is_aligned_as_its_element() {
+        let s = layout(StructKind::Struct, &[("c", Type::Char), ("a", Type::Array(Box::new(Type::Int), 3))]).unwrap();
+
+        assert_eq!(offsets(&s), vec![0, 4]);
+        assert_eq!(s.size, 16);
+        assert_eq!(s.alignment, 4);
+    }
+
+    #[test]
+    fn nested_struct_is_aligned_as_its_most_aligned_member() {
+        let inner = layout(StructKind::Struct, &[("c", Type::Char), ("l", Type::Long)]).unwrap();
+        let s = layout(StructKind::Struct, &[("c", Type::Char), ("inner", Type::Struct(Rc::new(inner)))]).unwrap();
+
+        assert_eq!(offsets(&s), vec![0, 8]);
+        assert_eq!(s.size, 24);
+        assert_eq!(s.alignment, 8);
+    }
+
+    #[test]
+    fn union_members_share_the_start() {
+        let u = layout(StructKind::Union, &[("c", Type::Char), ("l", Type::Long), ("i", Type::Int)]).unwrap();
+
+        assert_eq!(offsets(&u), vec![0, 0, 0]);
+        assert_eq!(u.size, 8);
+        assert_eq!(u.alignment, 8);
+    }
+
+    #[test]
+    fn union_is_padded_to_its_alignment() {
+        let u = layout(StructKind::Union, &[("a", Type::Array(Box::new(Type::Char), 5)), ("i", Type::Int)]).unwrap();
+
+        assert_eq!(u.size, 8);
+        assert_eq!(u.alignment, 4);
+    }
+
+    #[test]
+    fn duplicate_member_is_an_error() {
+        assert!(layout(StructKind::Struct, &[("a", Type::Int), ("a", Type::Char)]).is_err());
+    }
+
+    #[test]
+    fn void_member_is_an_error() {
+        assert!(layout(StructKind::Struct, &[("a", Type::Void)]).is_err());
+    }
+
+    #[test]
+    fn empty_struct_is_an_error() {
+        assert!(layout(StructKind::Struct, &[]).is_err());
+        assert!(layout(StructKind::Union, &[]).is_err());
+    }
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use std::process::Command;
+
+// Builds the C written for the program with the sanitizer for undefined behaviour and no flags
+// changing what C means, and gives what it printed
+fn run_c(test: &str, source: &str) -> String {
+    let (directory, output) = common::compile(test, source, &["--emit", "c"]);
+    assert!(output.status.success(), "Unable to write C for [{}]: {}", test, String::from_utf8_lossy(&output.stderr));
+    let executable = format!("{}_c", test);
+    let built = Command::new("cc")
+        .current_dir(&directory)
+        .args(["-std=c99", "-pedantic", "-fsanitize=undefined", "-fno-sanitize-recover=all", "-o", &executable])
+        .arg(format!("{}.c", test))
+        .output()
+        .unwrap();
+    assert!(built.status.success(), "Unable to build the C for [{}]: {}", test, String::from_utf8_lossy(&built.stderr));
+    let output = Command::new(directory.join(&executable)).output().unwrap();
+    assert!(output.status.success(), "The C for [{}] failed: {}", test, String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn signed_arithmetic_wraps_without_fwrapv() {
+    if !common::installed("cc") {
+        return;
+    }
+    let source = "\
+int i = 2147483647;
+print i + 1;
+print i * 2;
+int n = 0 - 2147483647 - 1;
+print n - 1;
+long l = 9223372036854775807;
+print l + 1;
+print l * 3;
+print 1 << 31;
+print 3 << 62;
+int k = 40;
+print n >> k;
+print n << 33;
+";
+    assert_eq!(run_c("wraps_without_fwrapv", source), common::run("wraps_without_fwrapv", source, &[]));
+}
+
+#[test]
+fn char_is_signed_without_fsigned_char() {
+    if !common::installed("cc") {
+        return;
+    }
+    let source = "\
+char c = 127;
+c = c + 1;
+print c;
+char d = 200;
+print d;
+print d >> 1;
+char s[4];
+s[0] = 250;
+print s[0] < 0;
+";
+    assert_eq!(run_c("signed_char", source), common::run("signed_char", source, &[]));
+}
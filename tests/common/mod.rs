@@ -0,0 +1,59 @@
+// Not every test file uses every helper
+#![allow(dead_code)]
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+/// A directory of its own for each test, as the compiler writes everything it makes into the
+/// directory it is run in. It is left behind to look at when a test fails.
+pub fn directory(test: &str) -> PathBuf {
+    let directory = env::temp_dir().join("compiler-tests").join(test);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+/// Saves `source` as `{test}.lang` in the test's directory and runs the compiler on it there
+pub fn compile(test: &str, source: &str, arguments: &[&str]) -> (PathBuf, Output) {
+    let directory = directory(test);
+    let file = format!("{}.lang", test);
+    fs::write(directory.join(&file), source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_compiler"))
+        .current_dir(&directory)
+        .args(["compile", "-f", &file])
+        .args(arguments)
+        .output()
+        .unwrap();
+    (directory, output)
+}
+
+/// Compiles `source` for the machine running the tests, runs it and gives what it printed
+pub fn run(test: &str, source: &str, arguments: &[&str]) -> String {
+    let (directory, output) = compile(test, source, arguments);
+    assert!(output.status.success(), "Unable to compile [{}]: {}", test, String::from_utf8_lossy(&output.stderr));
+    let output = Command::new(directory.join(test)).output().unwrap();
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Runs a program the compiler built with `input` on its standard input, giving what it printed
+pub fn run_with_input(command: &mut Command, input: &str) -> String {
+    let mut child = command.stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Whether a program can be found on the path, for the tests that check the output with tools
+/// that are not always installed
+pub fn installed(program: &str) -> bool {
+    env::var_os("PATH")
+        .map(|path| env::split_paths(&path).any(|directory| directory.join(program).is_file()))
+        .unwrap_or(false)
+}
+
+/// The lines of a file written by the compiler
+pub fn read(directory: &Path, file: &str) -> String {
+    fs::read_to_string(directory.join(file)).unwrap()
+}
@@ -0,0 +1,31 @@
+mod common;
+
+use std::process::Command;
+
+// The condition of `?:` is evaluated before either arm, so the arms see what it wrote
+const READ_IN_CONDITION: &str = "\
+long x;
+x = 5;
+print read(&x) ? x : 0;
+print x > 40 ? x + 1 : x - 1;
+";
+
+#[test]
+fn condition_is_evaluated_before_the_arms() {
+    let (directory, output) = common::compile("conditional_asm", READ_IN_CONDITION, &[]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let printed = common::run_with_input(&mut Command::new(directory.join("conditional_asm")), "42\n");
+    assert_eq!(printed, "42\n43\n");
+}
+
+#[test]
+fn condition_is_evaluated_before_the_arms_in_llvm_ir() {
+    if !common::installed("lli") {
+        return;
+    }
+    let (directory, output) = common::compile("conditional_llvm", READ_IN_CONDITION, &["--emit", "llvm"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let mut lli = Command::new("lli");
+    lli.current_dir(&directory).args(["-opaque-pointers", "conditional_llvm.ll"]);
+    assert_eq!(common::run_with_input(&mut lli, "42\n"), "42\n43\n");
+}
@@ -0,0 +1,185 @@
+mod common;
+
+use std::fs;
+use std::process::Command;
+
+use learn_to_write_a_compiler::asm::encoder::encode;
+use learn_to_write_a_compiler::asm::instruction::{Address, Arithmetic, Condition, Instruction, Line, Operand, Register, Shift, Size};
+use learn_to_write_a_compiler::asm::syntax::Syntax;
+
+// Checks the encoder against the GNU assembler, which is given each instruction as the compiler
+// writes it in both syntaxes. Symbols are left undefined, so the assembler leaves zeros where
+// their distances go, as the encoder does.
+
+const REGISTERS: [Register; 16] = [
+    Register::Rax, Register::Rcx, Register::Rdx, Register::Rbx, Register::Rsp, Register::Rbp, Register::Rsi, Register::Rdi,
+    Register::R8, Register::R9, Register::R10, Register::R11, Register::R12, Register::R13, Register::R14, Register::R15,
+];
+
+const SIZES: [Size; 3] = [Size::Byte, Size::DoubleWord, Size::QuadWord];
+
+const CONDITIONS: [Condition; 10] = [
+    Condition::Equal, Condition::NotEqual, Condition::Less, Condition::LessEqual, Condition::Greater,
+    Condition::GreaterEqual, Condition::Below, Condition::BelowEqual, Condition::Above, Condition::AboveEqual,
+];
+
+// Every base with each size of displacement, the bases needing a SIB byte or a displacement of
+// their own with an index, and an address relative to the instruction
+fn addresses() -> Vec<Address> {
+    let mut addresses = Vec::new();
+    for base in REGISTERS {
+        for displacement in [0, -8, 127, 128, -4096] {
+            addresses.push(Address::offset(base, displacement));
+        }
+    }
+    for (base, index, scale, displacement) in [
+        (Register::Rax, Register::Rcx, 1, 0),
+        (Register::Rsp, Register::Rdx, 2, 16),
+        (Register::Rbp, Register::R12, 4, 0),
+        (Register::R12, Register::R9, 8, -200),
+        (Register::R13, Register::Rax, 8, 0),
+        (Register::R15, Register::R14, 1, 1000),
+    ] {
+        addresses.push(Address::Indexed { base, index: Some((index, scale)), displacement });
+    }
+    addresses.push(Address::Relative("global".to_string()));
+    addresses
+}
+
+fn instructions() -> Vec<Instruction> {
+    let register = |r: Register, size: Size| Operand::Register(r, size);
+    let memory = addresses().into_iter().map(Operand::Memory).collect::<Vec<_>>();
+    let mut instructions = Vec::new();
+    for size in SIZES {
+        for (i, r) in REGISTERS.iter().enumerate() {
+            // Pairs each register with one from the other half, so every register is used on both sides
+            let other = REGISTERS[(i + 5) % 16];
+            instructions.push(Instruction::Move(size, register(*r, size), register(other, size)));
+            instructions.push(Instruction::Arithmetic(Arithmetic::Add, size, register(*r, size), register(other, size)));
+            instructions.push(Instruction::Arithmetic(Arithmetic::Test, size, register(*r, size), register(other, size)));
+            instructions.push(Instruction::Shift(Shift::Left, size, register(*r, size)));
+            instructions.push(Instruction::Increment(size, register(*r, size)));
+            instructions.push(Instruction::Divide(size, register(*r, size)));
+            for value in [0, 1, -1, 100, -128] {
+                instructions.push(Instruction::Move(size, Operand::Immediate(value), register(*r, size)));
+            }
+            for operation in [Arithmetic::Add, Arithmetic::Subtract, Arithmetic::And, Arithmetic::Xor, Arithmetic::Compare] {
+                instructions.push(Instruction::Arithmetic(operation, size, Operand::Immediate(5), register(*r, size)));
+                instructions.push(Instruction::Arithmetic(operation, size, Operand::Immediate(-100), register(*r, size)));
+                if size != Size::Byte {
+                    instructions.push(Instruction::Arithmetic(operation, size, Operand::Immediate(100000), register(*r, size)));
+                }
+            }
+            if size != Size::Byte {
+                instructions.push(Instruction::Move(size, Operand::Immediate(-2147483648), register(*r, size)));
+                instructions.push(Instruction::Arithmetic(Arithmetic::Multiply, size, register(other, size), register(*r, size)));
+                instructions.push(Instruction::Arithmetic(Arithmetic::Multiply, size, Operand::Immediate(8), register(*r, size)));
+                instructions.push(Instruction::Arithmetic(Arithmetic::Multiply, size, Operand::Immediate(-100000), register(*r, size)));
+                instructions.push(Instruction::ConditionalMove(Condition::Less, size, register(other, size), register(*r, size)));
+            }
+        }
+        for address in &memory {
+            let r = Register::R10;
+            instructions.push(Instruction::Move(size, register(Register::Rsi, size), address.clone()));
+            instructions.push(Instruction::Move(size, address.clone(), register(r, size)));
+            instructions.push(Instruction::Move(size, Operand::Immediate(-7), address.clone()));
+            instructions.push(Instruction::Arithmetic(Arithmetic::Subtract, size, address.clone(), register(Register::Rax, size)));
+            instructions.push(Instruction::Arithmetic(Arithmetic::Compare, size, register(Register::Rdi, size), address.clone()));
+            instructions.push(Instruction::Arithmetic(Arithmetic::Add, size, Operand::Immediate(3), address.clone()));
+            instructions.push(Instruction::Shift(Shift::RightArithmetic, size, address.clone()));
+            instructions.push(Instruction::Shift(Shift::RightLogical, size, address.clone()));
+            instructions.push(Instruction::SignedDivide(size, address.clone()));
+            if size != Size::Byte {
+                instructions.push(Instruction::Arithmetic(Arithmetic::Multiply, size, address.clone(), register(Register::R13, size)));
+            }
+        }
+    }
+    for r in REGISTERS {
+        for to in [Size::DoubleWord, Size::QuadWord] {
+            instructions.push(Instruction::SignExtend(Size::Byte, to, register(r, Size::Byte), register(r, to)));
+            instructions.push(Instruction::ZeroExtend(Size::Byte, to, register(r, Size::Byte), register(Register::Rbx, to)));
+        }
+        instructions.push(Instruction::SignExtend(Size::DoubleWord, Size::QuadWord, register(r, Size::DoubleWord), register(Register::R8, Size::QuadWord)));
+        for value in [5, 4294967296, u64::MAX, 1 << 63] {
+            instructions.push(Instruction::LoadConstant(value, r));
+        }
+        instructions.push(Instruction::Set(Condition::Equal, register(r, Size::Byte)));
+        instructions.push(Instruction::Jump(Operand::register(r)));
+        instructions.push(Instruction::Call(Operand::register(r)));
+        instructions.push(Instruction::Push(r));
+        instructions.push(Instruction::Pop(r));
+    }
+    for address in addresses() {
+        instructions.push(Instruction::LoadAddress(address.clone(), Register::R11));
+        instructions.push(Instruction::SignExtend(Size::Byte, Size::QuadWord, Operand::Memory(address.clone()), register(Register::Rcx, Size::QuadWord)));
+        instructions.push(Instruction::SignExtend(Size::DoubleWord, Size::QuadWord, Operand::Memory(address.clone()), register(Register::R9, Size::QuadWord)));
+        instructions.push(Instruction::ZeroExtend(Size::Byte, Size::DoubleWord, Operand::Memory(address.clone()), register(Register::Rdx, Size::DoubleWord)));
+        instructions.push(Instruction::Set(Condition::Below, Operand::Memory(address)));
+    }
+    for condition in CONDITIONS {
+        instructions.push(Instruction::Set(condition, register(Register::Rax, Size::Byte)));
+        instructions.push(Instruction::ConditionalMove(condition, Size::QuadWord, register(Register::Rcx, Size::QuadWord), register(Register::Rax, Size::QuadWord)));
+        instructions.push(Instruction::JumpIf(condition, Operand::Symbol("label".to_string())));
+    }
+    instructions.push(Instruction::Jump(Operand::Symbol("label".to_string())));
+    instructions.push(Instruction::Call(Operand::Symbol("printf@PLT".to_string())));
+    instructions.push(Instruction::SignExtendAccumulator);
+    instructions.push(Instruction::Leave);
+    instructions.push(Instruction::Return);
+    instructions.push(Instruction::Nop);
+    instructions
+}
+
+// The bytes of the text section the assembler makes of the instructions
+fn assemble(test: &str, syntax: Syntax, instructions: &[Instruction]) -> Vec<u8> {
+    let directory = common::directory(test);
+    let mut source = String::from(match syntax {
+        Syntax::Att => "\t.att_syntax\n",
+        Syntax::Intel => "\t.intel_syntax noprefix\n",
+    });
+    for instruction in instructions {
+        source.push_str(&syntax.line(&Line::Instruction(instruction.clone())));
+        source.push('\n');
+    }
+    fs::write(directory.join("encoder.s"), source).unwrap();
+    let assembled = Command::new("as")
+        .current_dir(&directory)
+        .args(["--64", "-o", "encoder.o", "encoder.s"])
+        .output()
+        .unwrap();
+    assert!(assembled.status.success(), "Unable to assemble [{}]: {}", test, String::from_utf8_lossy(&assembled.stderr));
+    let copied = Command::new("objcopy")
+        .current_dir(&directory)
+        .args(["-O", "binary", "--only-section=.text", "encoder.o", "encoder.bin"])
+        .output()
+        .unwrap();
+    assert!(copied.status.success(), "Unable to copy the text of [{}]: {}", test, String::from_utf8_lossy(&copied.stderr));
+    fs::read(directory.join("encoder.bin")).unwrap()
+}
+
+// Walks the assembler's bytes an instruction at a time, so a difference names the instruction
+fn encodes_as_the_assembler_does(test: &str, syntax: Syntax) {
+    if !common::installed("as") || !common::installed("objcopy") {
+        return;
+    }
+    let instructions = instructions();
+    let assembled = assemble(test, syntax, &instructions);
+    let mut offset = 0;
+    for instruction in &instructions {
+        let encoding = encode(instruction, false).unwrap();
+        let expected = assembled.get(offset..offset + encoding.bytes.len());
+        assert_eq!(Some(&encoding.bytes[..]), expected, "[{}] encodes differently from the assembler", syntax.line(&Line::Instruction(instruction.clone())).trim());
+        offset += encoding.bytes.len();
+    }
+    assert_eq!(offset, assembled.len());
+}
+
+#[test]
+fn encodes_as_the_assembler_does_from_att_syntax() {
+    encodes_as_the_assembler_does("encoder_att", Syntax::Att);
+}
+
+#[test]
+fn encodes_as_the_assembler_does_from_intel_syntax() {
+    encodes_as_the_assembler_does("encoder_intel", Syntax::Intel);
+}
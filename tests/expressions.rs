@@ -0,0 +1,34 @@
+mod common;
+
+#[test]
+fn deep_indexed_expression_fits_in_the_registers() {
+    let source = "\
+long a[5];
+int b[5];
+long i;
+i = 2;
+a[3] = 7;
+b[2] = 3;
+b[1] = 5;
+b[3] = 5;
+a[i] = b[i] + a[i + 1] * b[i - 1];
+print a[2];
+a[i - 1] = a[i] * 2 + b[i + 2 - 1] * (a[i + 1] - 3);
+print a[1];
+";
+    for emit in ["asm", "obj"] {
+        assert_eq!(common::run("deep_indexed_expression", source, &["--emit", emit]), "38\n96\n");
+    }
+}
+
+#[test]
+fn expression_needing_more_registers_is_an_error() {
+    let (_, output) = common::compile("expression_too_deep", "\
+long a, b, c, d, e, f;
+print a + (b + (c + (d + (e + f))));
+", &[]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("Unable to compute an expression needing more than 4 registers"), "{}", stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+}
@@ -0,0 +1,222 @@
+	// Start of preamble
+	.text
+.Lprintint:
+	stp	x29, x30, [sp, #-16]!
+	mov	x29, sp
+	mov	x1, x0
+	adrp	x0, .LC0
+	add	x0, x0, :lo12:.LC0
+	bl	printf
+	ldp	x29, x30, [sp], #16
+	ret
+.Lprintuint:
+	stp	x29, x30, [sp, #-16]!
+	mov	x29, sp
+	mov	x1, x0
+	adrp	x0, .LC1
+	add	x0, x0, :lo12:.LC1
+	bl	printf
+	ldp	x29, x30, [sp], #16
+	ret
+.Lprintstr:
+	stp	x29, x30, [sp, #-16]!
+	mov	x29, sp
+	bl	puts
+	ldp	x29, x30, [sp], #16
+	ret
+.Lreadint:
+	stp	x29, x30, [sp, #-32]!
+	mov	x29, sp
+	add	x1, sp, #16
+	adrp	x0, .LC2
+	add	x0, x0, :lo12:.LC2
+	bl	scanf
+	sxtw	x0, w0
+	ldr	x1, [sp, #16]
+	ldp	x29, x30, [sp], #32
+	ret
+.Lreadline:
+	stp	x29, x30, [sp, #-48]!
+	mov	x29, sp
+	stp	x19, x20, [sp, #16]
+	str	x21, [sp, #32]
+	mov	x19, x0
+	sub	x20, x1, #1
+	mov	x21, #0
+.Lreadline_next:
+	cmp	x21, x20
+	b.ge	.Lreadline_done
+	bl	getchar
+	cmn	w0, #1
+	b.eq	.Lreadline_end
+	cmp	w0, #10
+	b.eq	.Lreadline_done
+	strb	w0, [x19, x21]
+	add	x21, x21, #1
+	b	.Lreadline_next
+.Lreadline_end:
+	strb	wzr, [x19, x21]
+	mov	x0, #-1
+	cbz	x21, .Lreadline_return
+.Lreadline_done:
+	strb	wzr, [x19, x21]
+	mov	x0, x21
+.Lreadline_return:
+	ldp	x19, x20, [sp, #16]
+	ldr	x21, [sp, #32]
+	ldp	x29, x30, [sp], #48
+	ret
+	.globl	main
+main:
+	stp	x29, x30, [sp, #-16]!
+	mov	x29, sp
+	sub	sp, sp, #16
+	str	x0, [x29, #-8]
+	str	x1, [x29, #-16]
+	// End of preamble
+	// Starting users code
+	.comm	a,40,8
+	.comm	b,20,4
+	.comm	i,8,8
+	movz	x9, #2
+	adrp	x16, i
+	add	x16, x16, :lo12:i
+	str	x9, [x16]
+	movz	x9, #7
+	adrp	x10, a
+	add	x10, x10, :lo12:a
+	movz	x11, #3
+	cmp	x11, #5
+	b.lo	.L1
+	movz	x0, #5
+	bl	.Lboundsfail
+.L1:
+	lsl	x11, x11, #3
+	add	x10, x10, x11
+	str	x9, [x10]
+	movz	x9, #5
+	adrp	x10, b
+	add	x10, x10, :lo12:b
+	movz	x11, #1
+	cmp	x11, #5
+	b.lo	.L2
+	movz	x0, #6
+	bl	.Lboundsfail
+.L2:
+	lsl	x11, x11, #2
+	add	x10, x10, x11
+	str	w9, [x10]
+	adrp	x9, b
+	add	x9, x9, :lo12:b
+	adrp	x16, i
+	add	x16, x16, :lo12:i
+	ldr	x10, [x16]
+	cmp	x10, #5
+	b.lo	.L3
+	movz	x0, #7
+	bl	.Lboundsfail
+.L3:
+	lsl	x10, x10, #2
+	add	x9, x9, x10
+	ldrsw	x9, [x9]
+	adrp	x10, a
+	add	x10, x10, :lo12:a
+	adrp	x16, i
+	add	x16, x16, :lo12:i
+	ldr	x11, [x16]
+	add	x11, x11, #1
+	cmp	x11, #5
+	b.lo	.L4
+	movz	x0, #7
+	bl	.Lboundsfail
+.L4:
+	lsl	x11, x11, #3
+	add	x10, x10, x11
+	ldr	x10, [x10]
+	adrp	x11, b
+	add	x11, x11, :lo12:b
+	adrp	x16, i
+	add	x16, x16, :lo12:i
+	ldr	x12, [x16]
+	sub	x12, x12, #1
+	cmp	x12, #5
+	b.lo	.L5
+	movz	x0, #7
+	bl	.Lboundsfail
+.L5:
+	lsl	x12, x12, #2
+	add	x11, x11, x12
+	ldrsw	x11, [x11]
+	mul	x10, x10, x11
+	add	x9, x9, x10
+	adrp	x10, a
+	add	x10, x10, :lo12:a
+	adrp	x16, i
+	add	x16, x16, :lo12:i
+	ldr	x11, [x16]
+	movz	x12, #5000
+	add	x11, x11, x12
+	movz	x12, #5000
+	sub	x11, x11, x12
+	cmp	x11, #5
+	b.lo	.L6
+	movz	x0, #7
+	bl	.Lboundsfail
+.L6:
+	lsl	x11, x11, #3
+	add	x10, x10, x11
+	ldr	x10, [x10]
+	movz	x11, #3
+	mul	x10, x10, x11
+	add	x9, x9, x10
+	adrp	x10, a
+	add	x10, x10, :lo12:a
+	adrp	x16, i
+	add	x16, x16, :lo12:i
+	ldr	x11, [x16]
+	cmp	x11, #5
+	b.lo	.L7
+	movz	x0, #7
+	bl	.Lboundsfail
+.L7:
+	lsl	x11, x11, #3
+	add	x10, x10, x11
+	str	x9, [x10]
+	adrp	x9, a
+	add	x9, x9, :lo12:a
+	movz	x10, #2
+	cmp	x10, #5
+	b.lo	.L8
+	movz	x0, #8
+	bl	.Lboundsfail
+.L8:
+	lsl	x10, x10, #3
+	add	x9, x9, x10
+	ldr	x9, [x9]
+	mov	x0, x9
+	bl	.Lprintint
+	// Ending users code
+	mov	w0, #0
+	mov	sp, x29
+	ldp	x29, x30, [sp], #16
+	ret
+.Lboundsfail:
+	stp	x29, x30, [sp, #-16]!
+	mov	x29, sp
+	mov	x1, x0
+	adrp	x0, .LC3
+	add	x0, x0, :lo12:.LC3
+	bl	printf
+	mov	x0, #0
+	bl	fflush
+	bl	abort
+	.section	.rodata
+.LC0:
+	.string	"%ld\n"
+.LC1:
+	.string	"%lu\n"
+.LC2:
+	.string	"%ld"
+.LC3:
+	.string	"Array index out of bounds on line %ld\n"
+	.section	.note.GNU-stack,"",@progbits
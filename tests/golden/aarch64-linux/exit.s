@@ -0,0 +1,110 @@
+	// Start of preamble
+	.text
+.Lprintint:
+	stp	x29, x30, [sp, #-16]!
+	mov	x29, sp
+	mov	x1, x0
+	adrp	x0, .LC0
+	add	x0, x0, :lo12:.LC0
+	bl	printf
+	ldp	x29, x30, [sp], #16
+	ret
+.Lprintuint:
+	stp	x29, x30, [sp, #-16]!
+	mov	x29, sp
+	mov	x1, x0
+	adrp	x0, .LC1
+	add	x0, x0, :lo12:.LC1
+	bl	printf
+	ldp	x29, x30, [sp], #16
+	ret
+.Lprintstr:
+	stp	x29, x30, [sp, #-16]!
+	mov	x29, sp
+	bl	puts
+	ldp	x29, x30, [sp], #16
+	ret
+.Lreadint:
+	stp	x29, x30, [sp, #-32]!
+	mov	x29, sp
+	add	x1, sp, #16
+	adrp	x0, .LC2
+	add	x0, x0, :lo12:.LC2
+	bl	scanf
+	sxtw	x0, w0
+	ldr	x1, [sp, #16]
+	ldp	x29, x30, [sp], #32
+	ret
+.Lreadline:
+	stp	x29, x30, [sp, #-48]!
+	mov	x29, sp
+	stp	x19, x20, [sp, #16]
+	str	x21, [sp, #32]
+	mov	x19, x0
+	sub	x20, x1, #1
+	mov	x21, #0
+.Lreadline_next:
+	cmp	x21, x20
+	b.ge	.Lreadline_done
+	bl	getchar
+	cmn	w0, #1
+	b.eq	.Lreadline_end
+	cmp	w0, #10
+	b.eq	.Lreadline_done
+	strb	w0, [x19, x21]
+	add	x21, x21, #1
+	b	.Lreadline_next
+.Lreadline_end:
+	strb	wzr, [x19, x21]
+	mov	x0, #-1
+	cbz	x21, .Lreadline_return
+.Lreadline_done:
+	strb	wzr, [x19, x21]
+	mov	x0, x21
+.Lreadline_return:
+	ldp	x19, x20, [sp, #16]
+	ldr	x21, [sp, #32]
+	ldp	x29, x30, [sp], #48
+	ret
+	.globl	main
+main:
+	stp	x29, x30, [sp, #-16]!
+	mov	x29, sp
+	sub	sp, sp, #16
+	str	x0, [x29, #-8]
+	str	x1, [x29, #-16]
+	// End of preamble
+	// Starting users code
+	.comm	x,4,4
+	movz	x9, #3
+	adrp	x16, x
+	add	x16, x16, :lo12:x
+	str	w9, [x16]
+	adrp	x16, x
+	add	x16, x16, :lo12:x
+	ldrsw	x9, [x16]
+	mov	x0, x9
+	bl	.Lprintint
+	adrp	x16, x
+	add	x16, x16, :lo12:x
+	ldrsw	x9, [x16]
+	add	x9, x9, #1
+	sxtw	x9, w9
+	mov	x0, x9
+	bl	exit
+	movz	x9, #0
+	mov	x0, x9
+	bl	.Lprintint
+	// Ending users code
+	mov	w0, #0
+	mov	sp, x29
+	ldp	x29, x30, [sp], #16
+	ret
+	.section	.rodata
+.LC0:
+	.string	"%ld\n"
+.LC1:
+	.string	"%lu\n"
+.LC2:
+	.string	"%ld"
+	.section	.note.GNU-stack,"",@progbits
@@ -0,0 +1,179 @@
+	// Start of preamble
+	.text
+.Lprintint:
+	stp	x29, x30, [sp, #-16]!
+	mov	x29, sp
+	mov	x1, x0
+	adrp	x0, .LC0
+	add	x0, x0, :lo12:.LC0
+	bl	printf
+	ldp	x29, x30, [sp], #16
+	ret
+.Lprintuint:
+	stp	x29, x30, [sp, #-16]!
+	mov	x29, sp
+	mov	x1, x0
+	adrp	x0, .LC1
+	add	x0, x0, :lo12:.LC1
+	bl	printf
+	ldp	x29, x30, [sp], #16
+	ret
+.Lprintstr:
+	stp	x29, x30, [sp, #-16]!
+	mov	x29, sp
+	bl	puts
+	ldp	x29, x30, [sp], #16
+	ret
+.Lreadint:
+	stp	x29, x30, [sp, #-32]!
+	mov	x29, sp
+	add	x1, sp, #16
+	adrp	x0, .LC2
+	add	x0, x0, :lo12:.LC2
+	bl	scanf
+	sxtw	x0, w0
+	ldr	x1, [sp, #16]
+	ldp	x29, x30, [sp], #32
+	ret
+.Lreadline:
+	stp	x29, x30, [sp, #-48]!
+	mov	x29, sp
+	stp	x19, x20, [sp, #16]
+	str	x21, [sp, #32]
+	mov	x19, x0
+	sub	x20, x1, #1
+	mov	x21, #0
+.Lreadline_next:
+	cmp	x21, x20
+	b.ge	.Lreadline_done
+	bl	getchar
+	cmn	w0, #1
+	b.eq	.Lreadline_end
+	cmp	w0, #10
+	b.eq	.Lreadline_done
+	strb	w0, [x19, x21]
+	add	x21, x21, #1
+	b	.Lreadline_next
+.Lreadline_end:
+	strb	wzr, [x19, x21]
+	mov	x0, #-1
+	cbz	x21, .Lreadline_return
+.Lreadline_done:
+	strb	wzr, [x19, x21]
+	mov	x0, x21
+.Lreadline_return:
+	ldp	x19, x20, [sp, #16]
+	ldr	x21, [sp, #32]
+	ldp	x29, x30, [sp], #48
+	ret
+	.globl	main
+main:
+	stp	x29, x30, [sp, #-16]!
+	mov	x29, sp
+	movz	x16, #5056
+	sub	sp, sp, x16
+	str	x0, [x29, #-8]
+	str	x1, [x29, #-16]
+	// End of preamble
+	// Starting users code
+	.comm	total,4,4
+	movz	x9, #4
+	movz	x10, #5040
+	sub	x10, x29, x10
+	movz	x11, #0
+	lsl	x11, x11, #3
+	add	x10, x10, x11
+	str	x9, [x10]
+	movz	x9, #5
+	movz	x10, #5040
+	sub	x10, x29, x10
+	movz	x11, #1
+	lsl	x11, x11, #3
+	add	x10, x10, x11
+	str	x9, [x10]
+	movz	x9, #6
+	movz	x10, #5040
+	sub	x10, x29, x10
+	movz	x11, #2
+	lsl	x11, x11, #3
+	add	x10, x10, x11
+	str	x9, [x10]
+	movz	x9, #3
+	movz	x16, #5044
+	sub	x16, x29, x16
+	str	w9, [x16]
+	movz	x9, #5044
+	sub	x9, x29, x9
+	movz	x16, #5056
+	sub	x16, x29, x16
+	str	x9, [x16]
+	movz	x9, #9
+	movz	x10, #5016
+	sub	x10, x29, x10
+	movz	x11, #4999
+	add	x10, x10, x11
+	strb	w9, [x10]
+	movz	x16, #5056
+	sub	x16, x29, x16
+	ldr	x9, [x16]
+	ldrsw	x9, [x9]
+	sub	x16, x29, #8
+	ldrsw	x10, [x16]
+	add	x9, x9, x10
+	sxtw	x9, w9
+	movz	x16, #5056
+	sub	x16, x29, x16
+	ldr	x10, [x16]
+	str	w9, [x10]
+	sub	x16, x29, #16
+	ldr	x9, [x16]
+	movz	x10, #1
+	lsl	x10, x10, #3
+	add	x9, x9, x10
+	ldr	x9, [x9]
+	mov	x0, x9
+	bl	.Lprintstr
+	movz	x9, #5016
+	sub	x9, x29, x9
+	movz	x10, #4999
+	add	x9, x9, x10
+	ldrsb	x9, [x9]
+	movz	x10, #5040
+	sub	x10, x29, x10
+	movz	x11, #2
+	lsl	x11, x11, #3
+	add	x10, x10, x11
+	ldr	x10, [x10]
+	add	x9, x9, x10
+	movz	x16, #5044
+	sub	x16, x29, x16
+	ldrsw	x10, [x16]
+	add	x9, x9, x10
+	mov	x0, x9
+	bl	.Lprintint
+	movz	x16, #5044
+	sub	x16, x29, x16
+	ldrsw	x9, [x16]
+	adrp	x16, total
+	add	x16, x16, :lo12:total
+	str	w9, [x16]
+	adrp	x16, total
+	add	x16, x16, :lo12:total
+	ldrsw	x9, [x16]
+	mov	x0, x9
+	mov	sp, x29
+	ldp	x29, x30, [sp], #16
+	ret
+	// Ending users code
+	mov	w0, #0
+	mov	sp, x29
+	ldp	x29, x30, [sp], #16
+	ret
+	.section	.rodata
+.LC0:
+	.string	"%ld\n"
+.LC1:
+	.string	"%lu\n"
+.LC2:
+	.string	"%ld"
+	.section	.note.GNU-stack,"",@progbits
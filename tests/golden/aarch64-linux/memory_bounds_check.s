@@ -0,0 +1,414 @@
+	// Start of preamble
+	.text
+.Lprintint:
+	stp	x29, x30, [sp, #-16]!
+	mov	x29, sp
+	mov	x1, x0
+	adrp	x0, .LC0
+	add	x0, x0, :lo12:.LC0
+	bl	printf
+	ldp	x29, x30, [sp], #16
+	ret
+.Lprintuint:
+	stp	x29, x30, [sp, #-16]!
+	mov	x29, sp
+	mov	x1, x0
+	adrp	x0, .LC1
+	add	x0, x0, :lo12:.LC1
+	bl	printf
+	ldp	x29, x30, [sp], #16
+	ret
+.Lprintstr:
+	stp	x29, x30, [sp, #-16]!
+	mov	x29, sp
+	bl	puts
+	ldp	x29, x30, [sp], #16
+	ret
+.Lreadint:
+	stp	x29, x30, [sp, #-32]!
+	mov	x29, sp
+	add	x1, sp, #16
+	adrp	x0, .LC2
+	add	x0, x0, :lo12:.LC2
+	bl	scanf
+	sxtw	x0, w0
+	ldr	x1, [sp, #16]
+	ldp	x29, x30, [sp], #32
+	ret
+.Lreadline:
+	stp	x29, x30, [sp, #-48]!
+	mov	x29, sp
+	stp	x19, x20, [sp, #16]
+	str	x21, [sp, #32]
+	mov	x19, x0
+	sub	x20, x1, #1
+	mov	x21, #0
+.Lreadline_next:
+	cmp	x21, x20
+	b.ge	.Lreadline_done
+	bl	getchar
+	cmn	w0, #1
+	b.eq	.Lreadline_end
+	cmp	w0, #10
+	b.eq	.Lreadline_done
+	strb	w0, [x19, x21]
+	add	x21, x21, #1
+	b	.Lreadline_next
+.Lreadline_end:
+	strb	wzr, [x19, x21]
+	mov	x0, #-1
+	cbz	x21, .Lreadline_return
+.Lreadline_done:
+	strb	wzr, [x19, x21]
+	mov	x0, x21
+.Lreadline_return:
+	ldp	x19, x20, [sp, #16]
+	ldr	x21, [sp, #32]
+	ldp	x29, x30, [sp], #48
+	ret
+	.globl	main
+main:
+	stp	x29, x30, [sp, #-16]!
+	mov	x29, sp
+	sub	sp, sp, #16
+	str	x0, [x29, #-8]
+	str	x1, [x29, #-16]
+	// End of preamble
+	// Starting users code
+	.comm	a,20,4
+	.comm	m,48,4
+	.comm	b,32,8
+	movz	x9, #10
+	adrp	x10, b
+	add	x10, x10, :lo12:b
+	movz	x11, #0
+	cmp	x11, #4
+	b.lo	.L1
+	movz	x0, #3
+	bl	.Lboundsfail
+.L1:
+	lsl	x11, x11, #3
+	add	x10, x10, x11
+	str	x9, [x10]
+	movz	x9, #20
+	adrp	x10, b
+	add	x10, x10, :lo12:b
+	movz	x11, #1
+	cmp	x11, #4
+	b.lo	.L2
+	movz	x0, #3
+	bl	.Lboundsfail
+.L2:
+	lsl	x11, x11, #3
+	add	x10, x10, x11
+	str	x9, [x10]
+	.comm	x,24,8
+	.comm	pp,8,8
+	.comm	p,8,8
+	.comm	i,4,4
+	movz	x9, #1
+	adrp	x10, a
+	add	x10, x10, :lo12:a
+	movz	x11, #0
+	cmp	x11, #5
+	b.lo	.L3
+	movz	x0, #9
+	bl	.Lboundsfail
+.L3:
+	lsl	x11, x11, #2
+	add	x10, x10, x11
+	str	w9, [x10]
+	movz	x9, #9
+	adrp	x10, a
+	add	x10, x10, :lo12:a
+	movz	x11, #4
+	cmp	x11, #5
+	b.lo	.L4
+	movz	x0, #9
+	bl	.Lboundsfail
+.L4:
+	lsl	x11, x11, #2
+	add	x10, x10, x11
+	str	w9, [x10]
+	movz	x9, #77
+	adrp	x10, m
+	add	x10, x10, :lo12:m
+	movz	x11, #2
+	cmp	x11, #3
+	b.lo	.L5
+	movz	x0, #10
+	bl	.Lboundsfail
+.L5:
+	lsl	x11, x11, #4
+	add	x10, x10, x11
+	movz	x11, #3
+	cmp	x11, #4
+	b.lo	.L6
+	movz	x0, #10
+	bl	.Lboundsfail
+.L6:
+	lsl	x11, x11, #2
+	add	x10, x10, x11
+	str	w9, [x10]
+	movz	x9, #2
+	adrp	x16, i
+	add	x16, x16, :lo12:i
+	str	w9, [x16]
+	movz	x9, #33
+	adrp	x10, m
+	add	x10, x10, :lo12:m
+	adrp	x16, i
+	add	x16, x16, :lo12:i
+	ldrsw	x11, [x16]
+	cmp	x11, #3
+	b.lo	.L7
+	movz	x0, #12
+	bl	.Lboundsfail
+.L7:
+	lsl	x11, x11, #4
+	add	x10, x10, x11
+	adrp	x16, i
+	add	x16, x16, :lo12:i
+	ldrsw	x11, [x16]
+	add	x11, x11, #1
+	sxtw	x11, w11
+	cmp	x11, #4
+	b.lo	.L8
+	movz	x0, #12
+	bl	.Lboundsfail
+.L8:
+	lsl	x11, x11, #2
+	add	x10, x10, x11
+	str	w9, [x10]
+	adrp	x9, a
+	add	x9, x9, :lo12:a
+	movz	x10, #0
+	cmp	x10, #5
+	b.lo	.L9
+	movz	x0, #13
+	bl	.Lboundsfail
+.L9:
+	lsl	x10, x10, #2
+	add	x9, x9, x10
+	ldrsw	x9, [x9]
+	adrp	x10, a
+	add	x10, x10, :lo12:a
+	movz	x11, #4
+	cmp	x11, #5
+	b.lo	.L10
+	movz	x0, #13
+	bl	.Lboundsfail
+.L10:
+	lsl	x11, x11, #2
+	add	x10, x10, x11
+	ldrsw	x10, [x10]
+	add	x9, x9, x10
+	sxtw	x9, w9
+	adrp	x10, m
+	add	x10, x10, :lo12:m
+	movz	x11, #2
+	cmp	x11, #3
+	b.lo	.L11
+	movz	x0, #13
+	bl	.Lboundsfail
+.L11:
+	lsl	x11, x11, #4
+	add	x10, x10, x11
+	movz	x11, #3
+	cmp	x11, #4
+	b.lo	.L12
+	movz	x0, #13
+	bl	.Lboundsfail
+.L12:
+	lsl	x11, x11, #2
+	add	x10, x10, x11
+	ldrsw	x10, [x10]
+	add	x9, x9, x10
+	sxtw	x9, w9
+	adrp	x10, b
+	add	x10, x10, :lo12:b
+	movz	x11, #1
+	cmp	x11, #4
+	b.lo	.L13
+	movz	x0, #13
+	bl	.Lboundsfail
+.L13:
+	lsl	x11, x11, #3
+	add	x10, x10, x11
+	ldr	x10, [x10]
+	add	x9, x9, x10
+	mov	x0, x9
+	bl	.Lprintint
+	adrp	x9, a
+	add	x9, x9, :lo12:a
+	movz	x10, #1
+	cmp	x10, #5
+	b.lo	.L14
+	movz	x0, #14
+	bl	.Lboundsfail
+.L14:
+	lsl	x10, x10, #2
+	add	x9, x9, x10
+	adrp	x16, p
+	add	x16, x16, :lo12:p
+	str	x9, [x16]
+	adrp	x16, p
+	add	x16, x16, :lo12:p
+	ldr	x9, [x16]
+	movz	x10, #3
+	lsl	x10, x10, #2
+	add	x9, x9, x10
+	ldrsw	x9, [x9]
+	mov	x0, x9
+	bl	.Lprintint
+	adrp	x9, a
+	add	x9, x9, :lo12:a
+	movz	x10, #4
+	cmp	x10, #5
+	b.lo	.L15
+	movz	x0, #16
+	bl	.Lboundsfail
+.L15:
+	lsl	x10, x10, #2
+	add	x9, x9, x10
+	adrp	x10, a
+	add	x10, x10, :lo12:a
+	movz	x11, #0
+	cmp	x11, #5
+	b.lo	.L16
+	movz	x0, #16
+	bl	.Lboundsfail
+.L16:
+	lsl	x11, x11, #2
+	add	x10, x10, x11
+	sub	x9, x9, x10
+	movz	x10, #4
+	sdiv	x9, x9, x10
+	mov	x0, x9
+	bl	.Lprintint
+	movz	x9, #6676
+	movk	x9, #48793, lsl #16
+	movk	x9, #28, lsl #32
+	adrp	x10, x
+	add	x10, x10, :lo12:x
+	movz	x11, #16
+	add	x10, x10, x11
+	str	x9, [x10]
+	adrp	x9, x
+	add	x9, x9, :lo12:x
+	adrp	x16, pp
+	add	x16, x16, :lo12:pp
+	str	x9, [x16]
+	adrp	x16, pp
+	add	x16, x16, :lo12:pp
+	ldr	x9, [x16]
+	movz	x10, #4
+	add	x9, x9, x10
+	ldrsw	x9, [x9]
+	add	x9, x9, #1
+	sxtw	x9, w9
+	adrp	x16, pp
+	add	x16, x16, :lo12:pp
+	ldr	x10, [x16]
+	movz	x11, #4
+	add	x10, x10, x11
+	str	w9, [x10]
+	adrp	x9, x
+	add	x9, x9, :lo12:x
+	movz	x10, #4
+	add	x9, x9, x10
+	ldrsw	x9, [x9]
+	adrp	x10, x
+	add	x10, x10, :lo12:x
+	movz	x11, #16
+	add	x10, x10, x11
+	ldr	x10, [x10]
+	add	x9, x9, x10
+	mov	x0, x9
+	bl	.Lprintint
+	movz	x9, #257
+	movk	x9, #1, lsl #32
+	sxtb	x9, w9
+	movz	x10, #511
+	and	x10, x10, #0xff
+	add	x9, x9, x10
+	sxtw	x9, w9
+	mov	x0, x9
+	bl	.Lprintint
+	adrp	x16, i
+	add	x16, x16, :lo12:i
+	ldrsw	x9, [x16]
+	sub	x9, x9, #20
+	sxtw	x9, w9
+	mov	w9, w9
+	mov	x0, x9
+	bl	.Lprintuint
+	adrp	x16, i
+	add	x16, x16, :lo12:i
+	ldrsw	x9, [x16]
+	movz	x10, #2
+	cmp	x9, x10
+	cset	x10, gt
+	cbz	x10, .L17
+	movz	x9, #100
+	adrp	x16, i
+	add	x16, x16, :lo12:i
+	ldrsw	x10, [x16]
+	sdiv	x9, x9, x10
+	sxtw	x9, w9
+	b	.L18
+.L17:
+	movz	x10, #200
+	adrp	x16, i
+	add	x16, x16, :lo12:i
+	ldrsw	x11, [x16]
+	sub	x10, x10, x11
+	sxtw	x10, w10
+	mov	x9, x10
+.L18:
+	mov	x0, x9
+	bl	.Lprintint
+	adrp	x16, i
+	add	x16, x16, :lo12:i
+	ldrsw	x9, [x16]
+	movz	x10, #40
+	lsl	x9, x9, x10
+	sxtw	x9, w9
+	movz	x10, #3
+	asr	x9, x9, x10
+	mov	x0, x9
+	bl	.Lprintint
+	movz	x9, #0
+	movz	x10, #65535
+	movk	x10, #65535, lsl #16
+	movk	x10, #65535, lsl #32
+	movk	x10, #32767, lsl #48
+	sub	x9, x9, x10
+	sub	x9, x9, #1
+	mov	x0, x9
+	bl	.Lprintint
+	// Ending users code
+	mov	w0, #0
+	mov	sp, x29
+	ldp	x29, x30, [sp], #16
+	ret
+.Lboundsfail:
+	stp	x29, x30, [sp, #-16]!
+	mov	x29, sp
+	mov	x1, x0
+	adrp	x0, .LC3
+	add	x0, x0, :lo12:.LC3
+	bl	printf
+	mov	x0, #0
+	bl	fflush
+	bl	abort
+	.section	.rodata
+.LC0:
+	.string	"%ld\n"
+.LC1:
+	.string	"%lu\n"
+.LC2:
+	.string	"%ld"
+.LC3:
+	.string	"Array index out of bounds on line %ld\n"
+	.section	.note.GNU-stack,"",@progbits
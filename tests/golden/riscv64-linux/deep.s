@@ -0,0 +1,166 @@
+	# Start of preamble
+	.text
+.Lprintint:
+	addi	sp, sp, -16
+	sd	ra, 8(sp)
+	mv	a1, a0
+	la	a0, .LC0
+	call	printf
+	ld	ra, 8(sp)
+	addi	sp, sp, 16
+	ret
+.Lprintuint:
+	addi	sp, sp, -16
+	sd	ra, 8(sp)
+	mv	a1, a0
+	la	a0, .LC1
+	call	printf
+	ld	ra, 8(sp)
+	addi	sp, sp, 16
+	ret
+.Lprintstr:
+	addi	sp, sp, -16
+	sd	ra, 8(sp)
+	call	puts
+	ld	ra, 8(sp)
+	addi	sp, sp, 16
+	ret
+.Lreadint:
+	addi	sp, sp, -16
+	sd	ra, 8(sp)
+	mv	a1, sp
+	la	a0, .LC2
+	call	scanf
+	sext.w	a0, a0
+	ld	a1, 0(sp)
+	ld	ra, 8(sp)
+	addi	sp, sp, 16
+	ret
+.Lreadline:
+	addi	sp, sp, -32
+	sd	ra, 24(sp)
+	sd	s1, 16(sp)
+	sd	s2, 8(sp)
+	sd	s3, 0(sp)
+	mv	s1, a0
+	addi	s2, a1, -1
+	li	s3, 0
+.Lreadline_next:
+	bge	s3, s2, .Lreadline_done
+	call	getchar
+	li	t5, -1
+	beq	a0, t5, .Lreadline_end
+	li	t5, 10
+	beq	a0, t5, .Lreadline_done
+	add	t4, s1, s3
+	sb	a0, 0(t4)
+	addi	s3, s3, 1
+	j	.Lreadline_next
+.Lreadline_end:
+	add	t4, s1, s3
+	sb	zero, 0(t4)
+	li	a0, -1
+	beqz	s3, .Lreadline_return
+.Lreadline_done:
+	add	t4, s1, s3
+	sb	zero, 0(t4)
+	mv	a0, s3
+.Lreadline_return:
+	ld	ra, 24(sp)
+	ld	s1, 16(sp)
+	ld	s2, 8(sp)
+	ld	s3, 0(sp)
+	addi	sp, sp, 32
+	ret
+	.globl	main
+main:
+	addi	sp, sp, -16
+	sd	ra, 8(sp)
+	sd	s0, 0(sp)
+	mv	s0, sp
+	addi	sp, sp, -16
+	sd	a0, -8(s0)
+	sd	a1, -16(s0)
+	# End of preamble
+	# Starting users code
+	.comm	a,40,8
+	.comm	b,20,4
+	.comm	i,8,8
+	li	t0, 2
+	la	t4, i
+	sd	t0, 0(t4)
+	li	t0, 7
+	la	t1, a
+	li	t2, 3
+	slli	t2, t2, 3
+	add	t1, t1, t2
+	sd	t0, 0(t1)
+	li	t0, 5
+	la	t1, b
+	li	t2, 1
+	slli	t2, t2, 2
+	add	t1, t1, t2
+	sw	t0, 0(t1)
+	la	t0, b
+	la	t4, i
+	ld	t1, 0(t4)
+	slli	t1, t1, 2
+	add	t0, t0, t1
+	lw	t0, 0(t0)
+	la	t1, a
+	la	t4, i
+	ld	t2, 0(t4)
+	addi	t2, t2, 1
+	slli	t2, t2, 3
+	add	t1, t1, t2
+	ld	t1, 0(t1)
+	la	t2, b
+	la	t4, i
+	ld	t3, 0(t4)
+	addi	t3, t3, -1
+	slli	t3, t3, 2
+	add	t2, t2, t3
+	lw	t2, 0(t2)
+	mul	t1, t1, t2
+	add	t0, t0, t1
+	la	t1, a
+	la	t4, i
+	ld	t2, 0(t4)
+	li	t3, 5000
+	add	t2, t2, t3
+	li	t3, 5000
+	sub	t2, t2, t3
+	slli	t2, t2, 3
+	add	t1, t1, t2
+	ld	t1, 0(t1)
+	li	t2, 3
+	mul	t1, t1, t2
+	add	t0, t0, t1
+	la	t1, a
+	la	t4, i
+	ld	t2, 0(t4)
+	slli	t2, t2, 3
+	add	t1, t1, t2
+	sd	t0, 0(t1)
+	la	t0, a
+	li	t1, 2
+	slli	t1, t1, 3
+	add	t0, t0, t1
+	ld	t0, 0(t0)
+	mv	a0, t0
+	call	.Lprintint
+	# Ending users code
+	li	a0, 0
+	mv	sp, s0
+	ld	ra, 8(sp)
+	ld	s0, 0(sp)
+	addi	sp, sp, 16
+	ret
+	.section	.rodata
+.LC0:
+	.string	"%ld\n"
+.LC1:
+	.string	"%lu\n"
+.LC2:
+	.string	"%ld"
+	.section	.note.GNU-stack,"",@progbits
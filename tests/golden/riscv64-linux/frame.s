@@ -0,0 +1,189 @@
+	# Start of preamble
+	.text
+.Lprintint:
+	addi	sp, sp, -16
+	sd	ra, 8(sp)
+	mv	a1, a0
+	la	a0, .LC0
+	call	printf
+	ld	ra, 8(sp)
+	addi	sp, sp, 16
+	ret
+.Lprintuint:
+	addi	sp, sp, -16
+	sd	ra, 8(sp)
+	mv	a1, a0
+	la	a0, .LC1
+	call	printf
+	ld	ra, 8(sp)
+	addi	sp, sp, 16
+	ret
+.Lprintstr:
+	addi	sp, sp, -16
+	sd	ra, 8(sp)
+	call	puts
+	ld	ra, 8(sp)
+	addi	sp, sp, 16
+	ret
+.Lreadint:
+	addi	sp, sp, -16
+	sd	ra, 8(sp)
+	mv	a1, sp
+	la	a0, .LC2
+	call	scanf
+	sext.w	a0, a0
+	ld	a1, 0(sp)
+	ld	ra, 8(sp)
+	addi	sp, sp, 16
+	ret
+.Lreadline:
+	addi	sp, sp, -32
+	sd	ra, 24(sp)
+	sd	s1, 16(sp)
+	sd	s2, 8(sp)
+	sd	s3, 0(sp)
+	mv	s1, a0
+	addi	s2, a1, -1
+	li	s3, 0
+.Lreadline_next:
+	bge	s3, s2, .Lreadline_done
+	call	getchar
+	li	t5, -1
+	beq	a0, t5, .Lreadline_end
+	li	t5, 10
+	beq	a0, t5, .Lreadline_done
+	add	t4, s1, s3
+	sb	a0, 0(t4)
+	addi	s3, s3, 1
+	j	.Lreadline_next
+.Lreadline_end:
+	add	t4, s1, s3
+	sb	zero, 0(t4)
+	li	a0, -1
+	beqz	s3, .Lreadline_return
+.Lreadline_done:
+	add	t4, s1, s3
+	sb	zero, 0(t4)
+	mv	a0, s3
+.Lreadline_return:
+	ld	ra, 24(sp)
+	ld	s1, 16(sp)
+	ld	s2, 8(sp)
+	ld	s3, 0(sp)
+	addi	sp, sp, 32
+	ret
+	.globl	main
+main:
+	addi	sp, sp, -16
+	sd	ra, 8(sp)
+	sd	s0, 0(sp)
+	mv	s0, sp
+	li	t5, 5056
+	sub	sp, sp, t5
+	sd	a0, -8(s0)
+	sd	a1, -16(s0)
+	# End of preamble
+	# Starting users code
+	.comm	total,4,4
+	li	t0, 4
+	li	t1, -5040
+	add	t1, s0, t1
+	li	t2, 0
+	slli	t2, t2, 3
+	add	t1, t1, t2
+	sd	t0, 0(t1)
+	li	t0, 5
+	li	t1, -5040
+	add	t1, s0, t1
+	li	t2, 1
+	slli	t2, t2, 3
+	add	t1, t1, t2
+	sd	t0, 0(t1)
+	li	t0, 6
+	li	t1, -5040
+	add	t1, s0, t1
+	li	t2, 2
+	slli	t2, t2, 3
+	add	t1, t1, t2
+	sd	t0, 0(t1)
+	li	t0, 3
+	li	t4, -5044
+	add	t4, s0, t4
+	sw	t0, 0(t4)
+	li	t0, -5044
+	add	t0, s0, t0
+	li	t4, -5056
+	add	t4, s0, t4
+	sd	t0, 0(t4)
+	li	t0, 9
+	li	t1, -5016
+	add	t1, s0, t1
+	li	t2, 4999
+	add	t1, t1, t2
+	sb	t0, 0(t1)
+	li	t4, -5056
+	add	t4, s0, t4
+	ld	t0, 0(t4)
+	lw	t0, 0(t0)
+	addi	t4, s0, -8
+	lw	t1, 0(t4)
+	add	t0, t0, t1
+	sext.w	t0, t0
+	li	t4, -5056
+	add	t4, s0, t4
+	ld	t1, 0(t4)
+	sw	t0, 0(t1)
+	addi	t4, s0, -16
+	ld	t0, 0(t4)
+	li	t1, 1
+	slli	t1, t1, 3
+	add	t0, t0, t1
+	ld	t0, 0(t0)
+	mv	a0, t0
+	call	.Lprintstr
+	li	t0, -5016
+	add	t0, s0, t0
+	li	t1, 4999
+	add	t0, t0, t1
+	lb	t0, 0(t0)
+	li	t1, -5040
+	add	t1, s0, t1
+	li	t2, 2
+	slli	t2, t2, 3
+	add	t1, t1, t2
+	ld	t1, 0(t1)
+	add	t0, t0, t1
+	li	t4, -5044
+	add	t4, s0, t4
+	lw	t1, 0(t4)
+	add	t0, t0, t1
+	mv	a0, t0
+	call	.Lprintint
+	li	t4, -5044
+	add	t4, s0, t4
+	lw	t0, 0(t4)
+	la	t4, total
+	sw	t0, 0(t4)
+	la	t4, total
+	lw	t0, 0(t4)
+	mv	a0, t0
+	mv	sp, s0
+	ld	ra, 8(sp)
+	ld	s0, 0(sp)
+	addi	sp, sp, 16
+	ret
+	# Ending users code
+	li	a0, 0
+	mv	sp, s0
+	ld	ra, 8(sp)
+	ld	s0, 0(sp)
+	addi	sp, sp, 16
+	ret
+	.section	.rodata
+.LC0:
+	.string	"%ld\n"
+.LC1:
+	.string	"%lu\n"
+.LC2:
+	.string	"%ld"
+	.section	.note.GNU-stack,"",@progbits
@@ -0,0 +1,297 @@
+	# Start of preamble
+	.text
+.Lprintint:
+	addi	sp, sp, -16
+	sd	ra, 8(sp)
+	mv	a1, a0
+	la	a0, .LC0
+	call	printf
+	ld	ra, 8(sp)
+	addi	sp, sp, 16
+	ret
+.Lprintuint:
+	addi	sp, sp, -16
+	sd	ra, 8(sp)
+	mv	a1, a0
+	la	a0, .LC1
+	call	printf
+	ld	ra, 8(sp)
+	addi	sp, sp, 16
+	ret
+.Lprintstr:
+	addi	sp, sp, -16
+	sd	ra, 8(sp)
+	call	puts
+	ld	ra, 8(sp)
+	addi	sp, sp, 16
+	ret
+.Lreadint:
+	addi	sp, sp, -16
+	sd	ra, 8(sp)
+	mv	a1, sp
+	la	a0, .LC2
+	call	scanf
+	sext.w	a0, a0
+	ld	a1, 0(sp)
+	ld	ra, 8(sp)
+	addi	sp, sp, 16
+	ret
+.Lreadline:
+	addi	sp, sp, -32
+	sd	ra, 24(sp)
+	sd	s1, 16(sp)
+	sd	s2, 8(sp)
+	sd	s3, 0(sp)
+	mv	s1, a0
+	addi	s2, a1, -1
+	li	s3, 0
+.Lreadline_next:
+	bge	s3, s2, .Lreadline_done
+	call	getchar
+	li	t5, -1
+	beq	a0, t5, .Lreadline_end
+	li	t5, 10
+	beq	a0, t5, .Lreadline_done
+	add	t4, s1, s3
+	sb	a0, 0(t4)
+	addi	s3, s3, 1
+	j	.Lreadline_next
+.Lreadline_end:
+	add	t4, s1, s3
+	sb	zero, 0(t4)
+	li	a0, -1
+	beqz	s3, .Lreadline_return
+.Lreadline_done:
+	add	t4, s1, s3
+	sb	zero, 0(t4)
+	mv	a0, s3
+.Lreadline_return:
+	ld	ra, 24(sp)
+	ld	s1, 16(sp)
+	ld	s2, 8(sp)
+	ld	s3, 0(sp)
+	addi	sp, sp, 32
+	ret
+	.globl	main
+main:
+	addi	sp, sp, -16
+	sd	ra, 8(sp)
+	sd	s0, 0(sp)
+	mv	s0, sp
+	addi	sp, sp, -16
+	sd	a0, -8(s0)
+	sd	a1, -16(s0)
+	# End of preamble
+	# Starting users code
+	.comm	a,20,4
+	.comm	m,48,4
+	.comm	b,32,8
+	li	t0, 10
+	la	t1, b
+	li	t2, 0
+	slli	t2, t2, 3
+	add	t1, t1, t2
+	sd	t0, 0(t1)
+	li	t0, 20
+	la	t1, b
+	li	t2, 1
+	slli	t2, t2, 3
+	add	t1, t1, t2
+	sd	t0, 0(t1)
+	.comm	x,24,8
+	.comm	pp,8,8
+	.comm	p,8,8
+	.comm	i,4,4
+	li	t0, 1
+	la	t1, a
+	li	t2, 0
+	slli	t2, t2, 2
+	add	t1, t1, t2
+	sw	t0, 0(t1)
+	li	t0, 9
+	la	t1, a
+	li	t2, 4
+	slli	t2, t2, 2
+	add	t1, t1, t2
+	sw	t0, 0(t1)
+	li	t0, 77
+	la	t1, m
+	li	t2, 2
+	slli	t2, t2, 4
+	add	t1, t1, t2
+	li	t2, 3
+	slli	t2, t2, 2
+	add	t1, t1, t2
+	sw	t0, 0(t1)
+	li	t0, 2
+	la	t4, i
+	sw	t0, 0(t4)
+	li	t0, 33
+	la	t1, m
+	la	t4, i
+	lw	t2, 0(t4)
+	slli	t2, t2, 4
+	add	t1, t1, t2
+	la	t4, i
+	lw	t2, 0(t4)
+	addi	t2, t2, 1
+	sext.w	t2, t2
+	slli	t2, t2, 2
+	add	t1, t1, t2
+	sw	t0, 0(t1)
+	la	t0, a
+	li	t1, 0
+	slli	t1, t1, 2
+	add	t0, t0, t1
+	lw	t0, 0(t0)
+	la	t1, a
+	li	t2, 4
+	slli	t2, t2, 2
+	add	t1, t1, t2
+	lw	t1, 0(t1)
+	add	t0, t0, t1
+	sext.w	t0, t0
+	la	t1, m
+	li	t2, 2
+	slli	t2, t2, 4
+	add	t1, t1, t2
+	li	t2, 3
+	slli	t2, t2, 2
+	add	t1, t1, t2
+	lw	t1, 0(t1)
+	add	t0, t0, t1
+	sext.w	t0, t0
+	la	t1, b
+	li	t2, 1
+	slli	t2, t2, 3
+	add	t1, t1, t2
+	ld	t1, 0(t1)
+	add	t0, t0, t1
+	mv	a0, t0
+	call	.Lprintint
+	la	t0, a
+	li	t1, 1
+	slli	t1, t1, 2
+	add	t0, t0, t1
+	la	t4, p
+	sd	t0, 0(t4)
+	la	t4, p
+	ld	t0, 0(t4)
+	li	t1, 3
+	slli	t1, t1, 2
+	add	t0, t0, t1
+	lw	t0, 0(t0)
+	mv	a0, t0
+	call	.Lprintint
+	la	t0, a
+	li	t1, 4
+	slli	t1, t1, 2
+	add	t0, t0, t1
+	la	t1, a
+	li	t2, 0
+	slli	t2, t2, 2
+	add	t1, t1, t2
+	sub	t0, t0, t1
+	li	t1, 4
+	div	t0, t0, t1
+	mv	a0, t0
+	call	.Lprintint
+	li	t0, 123456789012
+	la	t1, x
+	li	t2, 16
+	add	t1, t1, t2
+	sd	t0, 0(t1)
+	la	t0, x
+	la	t4, pp
+	sd	t0, 0(t4)
+	la	t4, pp
+	ld	t0, 0(t4)
+	li	t1, 4
+	add	t0, t0, t1
+	lw	t0, 0(t0)
+	addi	t0, t0, 1
+	sext.w	t0, t0
+	la	t4, pp
+	ld	t1, 0(t4)
+	li	t2, 4
+	add	t1, t1, t2
+	sw	t0, 0(t1)
+	la	t0, x
+	li	t1, 4
+	add	t0, t0, t1
+	lw	t0, 0(t0)
+	la	t1, x
+	li	t2, 16
+	add	t1, t1, t2
+	ld	t1, 0(t1)
+	add	t0, t0, t1
+	mv	a0, t0
+	call	.Lprintint
+	li	t0, 4294967553
+	slli	t0, t0, 56
+	srai	t0, t0, 56
+	li	t1, 511
+	andi	t1, t1, 255
+	add	t0, t0, t1
+	sext.w	t0, t0
+	mv	a0, t0
+	call	.Lprintint
+	la	t4, i
+	lw	t0, 0(t4)
+	addi	t0, t0, -20
+	sext.w	t0, t0
+	slli	t0, t0, 32
+	srli	t0, t0, 32
+	mv	a0, t0
+	call	.Lprintuint
+	la	t4, i
+	lw	t0, 0(t4)
+	li	t1, 2
+	slt	t1, t1, t0
+	beqz	t1, .L1
+	li	t0, 100
+	la	t4, i
+	lw	t1, 0(t4)
+	div	t0, t0, t1
+	sext.w	t0, t0
+	j	.L2
+.L1:
+	li	t1, 200
+	la	t4, i
+	lw	t2, 0(t4)
+	sub	t1, t1, t2
+	sext.w	t1, t1
+	mv	t0, t1
+.L2:
+	mv	a0, t0
+	call	.Lprintint
+	la	t4, i
+	lw	t0, 0(t4)
+	li	t1, 40
+	sll	t0, t0, t1
+	sext.w	t0, t0
+	li	t1, 3
+	sra	t0, t0, t1
+	mv	a0, t0
+	call	.Lprintint
+	li	t0, 0
+	li	t1, 9223372036854775807
+	sub	t0, t0, t1
+	addi	t0, t0, -1
+	mv	a0, t0
+	call	.Lprintint
+	# Ending users code
+	li	a0, 0
+	mv	sp, s0
+	ld	ra, 8(sp)
+	ld	s0, 0(sp)
+	addi	sp, sp, 16
+	ret
+	.section	.rodata
+.LC0:
+	.string	"%ld\n"
+.LC1:
+	.string	"%lu\n"
+.LC2:
+	.string	"%ld"
+	.section	.note.GNU-stack,"",@progbits
@@ -0,0 +1,204 @@
+	# Start of preamble
+	.text
+.Lprintint:
+	addi	sp, sp, -16
+	sd	ra, 8(sp)
+	mv	a1, a0
+	la	a0, .LC0
+	call	printf
+	ld	ra, 8(sp)
+	addi	sp, sp, 16
+	ret
+.Lprintuint:
+	addi	sp, sp, -16
+	sd	ra, 8(sp)
+	mv	a1, a0
+	la	a0, .LC1
+	call	printf
+	ld	ra, 8(sp)
+	addi	sp, sp, 16
+	ret
+.Lprintstr:
+	addi	sp, sp, -16
+	sd	ra, 8(sp)
+	call	puts
+	ld	ra, 8(sp)
+	addi	sp, sp, 16
+	ret
+.Lreadint:
+	addi	sp, sp, -16
+	sd	ra, 8(sp)
+	mv	a1, sp
+	la	a0, .LC2
+	call	scanf
+	sext.w	a0, a0
+	ld	a1, 0(sp)
+	ld	ra, 8(sp)
+	addi	sp, sp, 16
+	ret
+.Lreadline:
+	addi	sp, sp, -32
+	sd	ra, 24(sp)
+	sd	s1, 16(sp)
+	sd	s2, 8(sp)
+	sd	s3, 0(sp)
+	mv	s1, a0
+	addi	s2, a1, -1
+	li	s3, 0
+.Lreadline_next:
+	bge	s3, s2, .Lreadline_done
+	call	getchar
+	li	t5, -1
+	beq	a0, t5, .Lreadline_end
+	li	t5, 10
+	beq	a0, t5, .Lreadline_done
+	add	t4, s1, s3
+	sb	a0, 0(t4)
+	addi	s3, s3, 1
+	j	.Lreadline_next
+.Lreadline_end:
+	add	t4, s1, s3
+	sb	zero, 0(t4)
+	li	a0, -1
+	beqz	s3, .Lreadline_return
+.Lreadline_done:
+	add	t4, s1, s3
+	sb	zero, 0(t4)
+	mv	a0, s3
+.Lreadline_return:
+	ld	ra, 24(sp)
+	ld	s1, 16(sp)
+	ld	s2, 8(sp)
+	ld	s3, 0(sp)
+	addi	sp, sp, 32
+	ret
+	.globl	main
+main:
+	addi	sp, sp, -16
+	sd	ra, 8(sp)
+	sd	s0, 0(sp)
+	mv	s0, sp
+	addi	sp, sp, -16
+	sd	a0, -8(s0)
+	sd	a1, -16(s0)
+	# End of preamble
+	# Starting users code
+	.comm	x,4,4
+	.comm	c,1,1
+	li	t0, 4
+	la	t4, x
+	sw	t0, 0(t4)
+	la	t4, x
+	lw	t0, 0(t4)
+	li	t5, 3
+	beq	t0, t5, .L2
+	li	t5, 4
+	beq	t0, t5, .L3
+	li	t5, 5
+	beq	t0, t5, .L4
+	j	.L5
+.L2:
+	li	t0, 3
+	mv	a0, t0
+	call	.Lprintint
+	j	.L1
+.L3:
+	li	t0, 4
+	mv	a0, t0
+	call	.Lprintint
+.L4:
+	li	t0, 5
+	mv	a0, t0
+	call	.Lprintint
+	j	.L1
+.L5:
+	li	t0, 99
+	mv	a0, t0
+	call	.Lprintint
+.L1:
+	li	t0, 1000
+	la	t4, x
+	sw	t0, 0(t4)
+	la	t4, x
+	lw	t0, 0(t4)
+	li	t5, 1
+	beq	t0, t5, .L7
+	li	t5, 1000
+	beq	t0, t5, .L8
+	li	t5, 705032704
+	beq	t0, t5, .L9
+	j	.L10
+.L7:
+	li	t0, 1
+	mv	a0, t0
+	call	.Lprintint
+	j	.L6
+.L8:
+	li	t0, 1000
+	mv	a0, t0
+	call	.Lprintint
+	j	.L6
+.L9:
+	li	t0, 5
+	mv	a0, t0
+	call	.Lprintint
+	j	.L6
+.L10:
+	li	t0, 0
+	mv	a0, t0
+	call	.Lprintint
+.L6:
+	li	t0, 255
+	la	t4, c
+	sb	t0, 0(t4)
+	la	t4, c
+	lb	t0, 0(t4)
+	li	t5, -1
+	beq	t0, t5, .L12
+	li	t5, 255
+	beq	t0, t5, .L13
+	j	.L14
+.L12:
+	li	t0, 11
+	mv	a0, t0
+	call	.Lprintint
+	j	.L11
+.L13:
+	li	t0, 12
+	mv	a0, t0
+	call	.Lprintint
+	j	.L11
+.L14:
+	li	t0, 13
+	mv	a0, t0
+	call	.Lprintint
+.L11:
+	la	t4, x
+	lw	t0, 0(t4)
+	li	t1, 1000
+	sub	t1, t0, t1
+	seqz	t1, t1
+	la	t4, c
+	lb	t0, 0(t4)
+	li	t2, 0
+	sub	t2, t0, t2
+	snez	t2, t2
+	add	t1, t1, t2
+	sext.w	t1, t1
+	mv	a0, t1
+	call	.Lprintint
+	# Ending users code
+	li	a0, 0
+	mv	sp, s0
+	ld	ra, 8(sp)
+	ld	s0, 0(sp)
+	addi	sp, sp, 16
+	ret
+	.section	.rodata
+.LC0:
+	.string	"%ld\n"
+.LC1:
+	.string	"%lu\n"
+.LC2:
+	.string	"%ld"
+	.section	.note.GNU-stack,"",@progbits
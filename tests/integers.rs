@@ -0,0 +1,93 @@
+mod common;
+
+// Each program prints one line per value, which the tests compare against what C gives for the
+// same expressions on x86-64 Linux
+
+#[test]
+fn signed_int_wraps_at_two_to_the_thirty_one() {
+    let output = common::run("signed_int_wraps", "\
+int i = 2147483647;
+print i;
+print i + 1;
+int n = 0 - 2147483647 - 1;
+print n;
+print n - 1;
+print 1 << 31;
+", &[]);
+    assert_eq!(output, "2147483647\n-2147483648\n-2147483648\n2147483647\n-2147483648\n");
+}
+
+#[test]
+fn unsigned_int_holds_two_to_the_thirty_one() {
+    let output = common::run("unsigned_int_holds", "\
+unsigned int h = 2147483648;
+print h;
+print h > 1;
+print h / 2;
+print h >> 31;
+print h + h;
+", &[]);
+    assert_eq!(output, "2147483648\n1\n1073741824\n1\n0\n");
+}
+
+#[test]
+fn unsigned_int_wraps_at_two_to_the_thirty_two() {
+    let output = common::run("unsigned_int_wraps", "\
+unsigned int u = 4294967295;
+print u;
+print u + 1;
+print u / 2;
+int n = 0 - 1;
+print u > n;
+print n < 1;
+", &[]);
+    assert_eq!(output, "4294967295\n0\n2147483647\n0\n1\n");
+}
+
+#[test]
+fn long_holds_two_to_the_thirty_two() {
+    let output = common::run("long_holds", "\
+long l = 4294967296;
+print l;
+print l - 1;
+print l * 2;
+unsigned long ul = 4294967296;
+print ul + 4294967295;
+print 2147483648;
+print 2147483648 > 2147483647;
+", &[]);
+    assert_eq!(output, "4294967296\n4294967295\n8589934592\n8589934591\n2147483648\n1\n");
+}
+
+#[test]
+fn shifts_and_division_follow_the_signedness() {
+    let output = common::run("shifts_and_division", "\
+long l = 0 - 8;
+print l >> 1;
+print l / 2;
+unsigned long m = l;
+print m >> 1;
+print m / 2;
+unsigned int u = 0 - 8;
+print u >> 1;
+int i = 0 - 8;
+print i >> 1;
+", &[]);
+    assert_eq!(output, "-4\n-4\n9223372036854775804\n9223372036854775804\n2147483644\n-4\n");
+}
+
+#[test]
+fn narrow_types_convert_at_their_width() {
+    let output = common::run("narrow_types", "\
+unsigned char c = 255;
+print c + 1;
+char sc = 255;
+print sc;
+unsigned int wide = 4294967297;
+print wide;
+int truncated = 2147483648;
+print truncated;
+print 18446744073709551615;
+", &[]);
+    assert_eq!(output, "256\n-1\n1\n-2147483648\n18446744073709551615\n");
+}
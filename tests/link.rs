@@ -0,0 +1,31 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::process::Command;
+
+// A `cc` that always fails, found ahead of the real one on the path
+#[test]
+fn failing_to_link_is_an_error() {
+    let directory = common::directory("failing_cc");
+    let cc = directory.join("cc");
+    fs::write(&cc, "#!/bin/sh\nexit 1\n").unwrap();
+    fs::set_permissions(&cc, fs::Permissions::from_mode(0o755)).unwrap();
+    fs::write(directory.join("failing_cc.lang"), "print 1;\n").unwrap();
+
+    let mut path = vec![directory.clone()];
+    path.extend(env::split_paths(&env::var_os("PATH").unwrap_or_default()));
+    for emit in ["asm", "obj"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_compiler"))
+            .current_dir(&directory)
+            .env("PATH", env::join_paths(&path).unwrap())
+            .args(["compile", "-f", "failing_cc.lang", "--emit", emit])
+            .output()
+            .unwrap();
+        assert!(!output.status.success(), "Linking with a failing cc succeeded for --emit {}", emit);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("cc failed"));
+    }
+}
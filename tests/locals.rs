@@ -0,0 +1,55 @@
+mod common;
+
+// The variables of `main` live in its stack frame, and those declared before it are globals
+
+#[test]
+fn arrays_and_structs_in_main_are_on_the_stack() {
+    let source = "\
+int g[3] = {7, 8, 9};
+struct pair { char c; long l; };
+int main() {
+    long big[600];
+    struct pair p;
+    int n = 3;
+    int *q = &n;
+    int k[3] = {4, 5, 6};
+    big[0] = 11;
+    big[599] = 599;
+    p.c = 65;
+    p.l = 123456789012;
+    *q = *q + 4;
+    print n;
+    print big[599] - big[0];
+    print p.c + p.l;
+    print g[1] + k[2];
+    return 0;
+}
+";
+    let output = common::run("locals_on_the_stack", source, &[]);
+    assert_eq!(output, "7\n588\n123456789077\n14\n");
+
+    let assembly = common::read(&common::directory("locals_on_the_stack"), "locals_on_the_stack.s");
+    assert!(assembly.contains(".comm\tg,"), "{}", assembly);
+    for local in ["big", "p", "n", "q", "k"] {
+        assert!(!assembly.contains(&format!(".comm\t{},", local)), "{} is a global: {}", local, assembly);
+    }
+}
+
+#[test]
+fn frames_larger_than_an_immediate_are_set_aside() {
+    let output = common::run("locals_large_frame", "\
+int main() {
+    char pad[5000];
+    long x;
+    char *p;
+    x = 3;
+    pad[4999] = 9;
+    p = &pad[4996];
+    *p = 77;
+    print x;
+    print pad[4999] + pad[4996];
+    return 0;
+}
+", &[]);
+    assert_eq!(output, "3\n86\n");
+}
@@ -0,0 +1,44 @@
+mod common;
+
+#[test]
+fn globals_can_share_the_names_of_runtime_routines() {
+    let output = common::run("runtime_names", "\
+int printint;
+int printuint;
+int printstr;
+int readint;
+int readline;
+int boundsfail;
+printint = 1;
+printuint = 2;
+printstr = 3;
+readint = 4;
+readline = 5;
+boundsfail = 6;
+print printint + printuint + printstr + readint + readline + boundsfail;
+char b[4];
+b[3] = 7;
+print b[3];
+", &["--bounds-check"]);
+    assert_eq!(output, "21\n7\n");
+}
+
+#[test]
+fn globals_can_share_the_names_of_registers_in_intel_syntax() {
+    let source = "\
+int rax;
+char cs[4];
+long offset;
+long r8d;
+rax = 1;
+cs[2] = 2;
+offset = 3;
+r8d = 4;
+long *p = &offset;
+print rax + cs[2] + *p + r8d;
+";
+    for emit in ["asm", "obj"] {
+        let output = common::run("register_names", source, &["--asm-syntax", "intel", "--emit", emit]);
+        assert_eq!(output, "10\n");
+    }
+}
@@ -0,0 +1,78 @@
+mod common;
+
+// Every value is switched on in turn, printing the case it matched
+fn switch_on_each(values: &[&str], cases: &str) -> String {
+    let mut source = String::new();
+    for value in values {
+        source.push_str(&format!("switch ({}) {{\n{}}}\n", value, cases));
+    }
+    source
+}
+
+#[test]
+fn case_labels_are_constant_expressions() {
+    let source = switch_on_each(&["0", "3", "5", "6", "7"], "\
+case 1 + 2: print 3; break;
+case 2 * 3 - 1: print 5; break;
+case (1 << 2) + 2: print 6; break;
+case 7 > 6 ? 7 : 0: print 7; break;
+default: print 0;
+");
+    let output = common::run("case_expressions", &source, &[]);
+    assert_eq!(output, "0\n3\n5\n6\n7\n");
+}
+
+#[test]
+fn case_labels_can_be_characters() {
+    let mut source = "char s[4];\ns[0] = 'a';\ns[1] = '\\n';\ns[2] = 'z';\n".to_string();
+    source.push_str(&switch_on_each(&["s[0]", "s[1]", "s[2]"], "\
+case 'a': print 1; break;
+case '\\n': print 2; break;
+case 'a' + 25: print 3; break;
+"));
+    source.push_str("print 'A';\n");
+    let output = common::run("case_characters", &source, &[]);
+    assert_eq!(output, "1\n2\n3\n65\n");
+}
+
+#[test]
+fn case_labels_wrap_to_the_type_of_the_condition() {
+    let output = common::run("case_wrapping", "\
+char c = 0 - 1;
+switch (c) {
+    case 255: print 1; break;
+    default: print 0;
+}
+unsigned char u = 255;
+switch (u) {
+    case 0 - 1: print 1; break;
+    case 255: print 2; break;
+}
+", &[]);
+    assert_eq!(output, "0\n2\n");
+}
+
+#[test]
+fn case_label_reading_a_variable_is_an_error() {
+    let (_, output) = common::compile("case_variable", "\
+int i = 1;
+switch (i) {
+    case i: print 1;
+}
+", &[]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Case labels must be integer constant expressions"));
+}
+
+#[test]
+fn duplicate_case_expressions_are_an_error() {
+    let (_, output) = common::compile("case_duplicate", "\
+int i = 1;
+switch (i) {
+    case 2: print 1;
+    case 1 + 1: print 2;
+}
+", &[]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Duplicate case value [2]"));
+}
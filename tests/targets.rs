@@ -0,0 +1,142 @@
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+// The machines the assembly is written for cannot run it here. The tests compare it with the
+// assembly kept in tests/golden, and when the LLVM assembler is installed, which takes the syntax
+// of the GNU one for each target, they also check that it assembles. Running them with
+// UPDATE_GOLDEN set writes the kept assembly again instead.
+
+const PROGRAMS: [(&str, &str); 6] = [
+    ("memory", "\
+int a[5];
+int m[3][4];
+long b[4] = {10, 20};
+struct P { char c; int i; char d; long l; };
+struct P x;
+struct P *pp;
+int *p;
+int i;
+a[0] = 1; a[4] = 9;
+m[2][3] = 77;
+i = 2;
+m[i][i+1] = 33;
+print a[0] + a[4] + m[2][3] + b[1];
+p = &a[1];
+print p[3];
+print &a[4] - &a[0];
+x.l = 123456789012;
+pp = &x;
+pp->i = pp->i + 1;
+print x.i + x.l;
+print (char)4294967553 + (unsigned char)511;
+print (unsigned int)(i - 20);
+print i > 2 ? 100 / i : 200 - i;
+print (i << 40) >> 3;
+print 0 - 9223372036854775807 - 1;
+"),
+    ("switch", "\
+int x;
+char c;
+enum E { A = 3, B, C };
+x = 4;
+switch (x) { case A: print 3; break; case B: print 4; case C: print 5; break; default: print 99; }
+x = 1000;
+switch (x) { case 1: print 1; break; case 1000: print 1000; break; case 5000000000: print 5; break; default: print 0; }
+c = 255;
+switch (c) { case 4294967295: print 11; break; case 255: print 12; break; default: print 13; }
+print (x == 1000) + (c != 0);
+"),
+    ("strings", "\
+char *s;
+long n;
+char line[16];
+s = \"hello, world\";
+print s;
+print s[7];
+print \"tab\\there \\\"quoted\\\"\";
+print read(&n);
+print n;
+print readline(line, sizeof line);
+print line;
+"),
+    ("frame", "\
+int total;
+int main(int argc, char **argv) {
+    char pad[5000];
+    long k[3] = {4, 5, 6};
+    int n = 3;
+    int *q = &n;
+    pad[4999] = 9;
+    *q = *q + argc;
+    print argv[1];
+    print pad[4999] + k[2] + n;
+    total = n;
+    return total;
+}
+"),
+    ("deep", "\
+long a[5];
+int b[5];
+long i;
+i = 2;
+a[3] = 7;
+b[1] = 5;
+a[i] = b[i] + a[i + 1] * b[i - 1] + a[i + 5000 - 5000] * 3;
+print a[2];
+"),
+    ("exit", "\
+int x;
+x = 3;
+print x;
+exit(x + 1);
+print 0;
+"),
+];
+
+// Writes each program for the target, with and without bounds checks, and checks the assembly
+fn writes_assembly_for(target: &str, triple: &str, attributes: &str) {
+    let golden = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden").join(target);
+    for (name, source) in PROGRAMS {
+        for (flags, suffix) in [(&[][..], ""), (&["--bounds-check"][..], "_bounds_check")] {
+            let test = format!("{}_{}", target.replace('-', "_"), name);
+            let (directory, output) = common::compile(&test, source, &[&["--target", target][..], flags].concat());
+            assert!(output.status.success(), "Unable to compile [{}]: {}", test, String::from_utf8_lossy(&output.stderr));
+
+            let assembly = common::read(&directory, &format!("{}.s", test));
+            let expected = golden.join(format!("{}{}.s", name, suffix));
+            if env::var_os("UPDATE_GOLDEN").is_some() {
+                fs::create_dir_all(&golden).unwrap();
+                fs::write(&expected, &assembly).unwrap();
+            } else {
+                let kept = fs::read_to_string(&expected).unwrap_or_else(|e| panic!("Unable to read [{}]: {}", expected.display(), e));
+                assert!(assembly == kept, "The assembly for [{}] {:?} differs from [{}]", test, flags, expected.display());
+            }
+
+            if !common::installed("llvm-mc") {
+                continue;
+            }
+            let assembled = Command::new("llvm-mc")
+                .current_dir(&directory)
+                .args(["-triple", triple, "-mattr", attributes, "-filetype=obj", "-o", "/dev/null"])
+                .arg(format!("{}.s", test))
+                .output()
+                .unwrap();
+            assert!(assembled.status.success(), "Unable to assemble [{}] {:?}: {}", test, flags, String::from_utf8_lossy(&assembled.stderr));
+            assert!(assembled.stderr.is_empty(), "Warnings assembling [{}] {:?}: {}", test, flags, String::from_utf8_lossy(&assembled.stderr));
+        }
+    }
+}
+
+#[test]
+fn aarch64_assembly_is_as_kept() {
+    writes_assembly_for("aarch64-linux", "aarch64", "");
+}
+
+#[test]
+fn riscv64_assembly_is_as_kept() {
+    writes_assembly_for("riscv64-linux", "riscv64", "+m");
+}